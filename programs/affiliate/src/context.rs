@@ -0,0 +1,118 @@
+use crate::state::{Partner, User};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+#[derive(Accounts)]
+pub struct InitPartner<'info> {
+    #[account(
+        init,
+        seeds = [vault.key().as_ref(), partner_token.key().as_ref()],
+        bump,
+        payer = admin,
+        space = 8 + std::mem::size_of::<Partner>(),
+    )]
+    pub partner: Box<Account<'info, Partner>>,
+    /// CHECK: Vault account
+    pub vault: AccountInfo<'info>,
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeRatio<'info> {
+    #[account(mut)]
+    pub partner: Box<Account<'info, Partner>>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitUser<'info> {
+    #[account(
+        init,
+        seeds = [partner.key().as_ref(), owner.key().as_ref()],
+        bump,
+        payer = owner,
+        space = 8 + std::mem::size_of::<User>(),
+    )]
+    pub user: Box<Account<'info, User>>,
+    pub partner: Box<Account<'info, Partner>>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// Accounts of `deposit` and `withdraw`, user_lp is owned by the user PDA
+#[derive(Accounts)]
+pub struct DepositWithdrawLiquidity<'info> {
+    #[account(mut, has_one = vault)]
+    pub partner: Box<Account<'info, Partner>>,
+    #[account(mut, has_one = partner, has_one = owner)]
+    pub user: Box<Account<'info, User>>,
+    /// CHECK: Vault program
+    pub vault_program: AccountInfo<'info>,
+    /// CHECK: Vault account
+    #[account(mut)]
+    pub vault: AccountInfo<'info>,
+    #[account(mut)]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    pub user_token: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_lp: Box<Account<'info, TokenAccount>>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawDirectlyFromStrategy<'info> {
+    #[account(mut, has_one = vault)]
+    pub partner: Box<Account<'info, Partner>>,
+    #[account(mut, has_one = partner, has_one = owner)]
+    pub user: Box<Account<'info, User>>,
+    /// CHECK: Vault program
+    pub vault_program: AccountInfo<'info>,
+    /// CHECK: Vault account
+    #[account(mut)]
+    pub vault: AccountInfo<'info>,
+    /// CHECK: Strategy account
+    #[account(mut)]
+    pub strategy: AccountInfo<'info>,
+    /// CHECK: Reserve account
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    /// CHECK: Strategy program
+    pub strategy_program: AccountInfo<'info>,
+    #[account(mut)]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub vault_lp_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_token: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_lp: Box<Account<'info, TokenAccount>>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FundPartner<'info> {
+    #[account(mut, has_one = partner_token)]
+    pub partner: Box<Account<'info, Partner>>,
+    #[account(mut)]
+    pub partner_token: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = funder_token.key() != partner_token.key())]
+    pub funder_token: Box<Account<'info, TokenAccount>>,
+    pub funder: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
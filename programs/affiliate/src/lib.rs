@@ -0,0 +1,84 @@
+#![allow(clippy::result_large_err)]
+#![allow(clippy::needless_lifetimes)]
+
+pub mod context;
+pub mod state;
+pub mod utils;
+
+use anchor_lang::prelude::*;
+use context::*;
+
+declare_id!("GacY9YuN16HNRTy7ZWwULPccwvfFSBeNLuAQP7y38Du3");
+
+#[program]
+pub mod affiliate_vault {
+    use super::*;
+
+    #[allow(unused_variables)]
+    pub fn init_partner(ctx: Context<InitPartner>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn update_fee_ratio(ctx: Context<UpdateFeeRatio>, fee_ratio: u64) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn init_user(ctx: Context<InitUser>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn deposit(
+        ctx: Context<DepositWithdrawLiquidity>,
+        token_amount: u64,
+        minimum_lp_token_amount: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn withdraw(
+        ctx: Context<DepositWithdrawLiquidity>,
+        unmint_amount: u64,
+        min_out_amount: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn withdraw_directly_from_strategy<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, WithdrawDirectlyFromStrategy<'info>>,
+        unmint_amount: u64,
+        min_out_amount: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn fund_partner(ctx: Context<FundPartner>, amount: u64) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[error_code]
+pub enum AffiliateError {
+    #[msg("Math operation overflow")]
+    MathOverflow,
+
+    #[msg("Invalid owner")]
+    InvalidOwner,
+
+    #[msg("Invalid ratio")]
+    InvalidFeeRatio,
+
+    #[msg("Funder token account must be different from partner token account")]
+    WrongFunderToken,
+}
+
+/// Fee accrued to the partner, the name matches the on-chain event
+#[event]
+pub struct ParnerFee {
+    pub fee: u64,
+}
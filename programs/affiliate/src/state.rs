@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+/// Partner of a vault, fee accrues to it on deposits and withdrawals of its users
+#[account]
+#[derive(Default, Debug)]
+pub struct Partner {
+    /// token account receiving the fee of partner
+    pub partner_token: Pubkey,
+    pub vault: Pubkey,
+    /// fee accrued but not yet paid to partner_token
+    pub outstanding_fee: u64,
+    /// share of the vault fee paid to partner, in basis points
+    pub fee_ratio: u64,
+    /// fee accrued since initialization
+    pub cummulative_fee: u128,
+}
+
+/// User of a partner, owns the lp of owner
+#[account]
+#[derive(Default, Debug)]
+pub struct User {
+    pub owner: Pubkey,
+    pub partner: Pubkey,
+    /// virtual price of the lp when fee was last accrued
+    pub current_virtual_price: u64,
+    pub lp_token: u64,
+    pub bump: u8,
+}
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::Pubkey;
+
+pub fn derive_partner_address(vault: Pubkey, partner_token: Pubkey) -> (Pubkey, u8) {
+    derive_partner_address_with_program_id(vault, partner_token, &crate::ID)
+}
+
+pub fn derive_partner_address_with_program_id(
+    vault: Pubkey,
+    partner_token: Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], program_id)
+}
+
+pub fn derive_user_address(partner: Pubkey, owner: Pubkey) -> (Pubkey, u8) {
+    derive_user_address_with_program_id(partner, owner, &crate::ID)
+}
+
+pub fn derive_user_address_with_program_id(
+    partner: Pubkey,
+    owner: Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[partner.as_ref(), owner.as_ref()], program_id)
+}
@@ -0,0 +1,118 @@
+use crate::strategy::base::StrategyType;
+use anchor_lang::prelude::Pubkey;
+use std::str::FromStr;
+
+/// Programs the strategies deposit into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StrategyProgramIds {
+    pub port_finance: Pubkey,
+    pub solend: Pubkey,
+    pub apricot: Pubkey,
+    pub francium: Pubkey,
+    pub tulip: Pubkey,
+    pub drift: Pubkey,
+    pub frakt: Pubkey,
+    pub marginfi: Pubkey,
+    pub kamino: Pubkey,
+    pub cypher: Pubkey,
+}
+
+impl StrategyProgramIds {
+    /// Return program the strategy deposits into, None for strategies which can not be withdrawn from anymore or are unknown
+    pub fn get(&self, strategy_type: StrategyType) -> Option<Pubkey> {
+        match strategy_type {
+            StrategyType::PortFinanceWithoutLM | StrategyType::PortFinanceWithLM => {
+                Some(self.port_finance)
+            }
+            StrategyType::SolendWithoutLM | StrategyType::SolendWithLM => Some(self.solend),
+            StrategyType::ApricotWithoutLM => Some(self.apricot),
+            StrategyType::Francium => Some(self.francium),
+            StrategyType::Tulip => Some(self.tulip),
+            StrategyType::Drift => Some(self.drift),
+            StrategyType::Frakt => Some(self.frakt),
+            StrategyType::Marginfi => Some(self.marginfi),
+            StrategyType::Kamino => Some(self.kamino),
+            StrategyType::Mango | StrategyType::Vault | StrategyType::Unknown(_) => None,
+        }
+    }
+}
+
+/// Addresses which differ between clusters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClusterConfig {
+    pub vault_program_id: Pubkey,
+    /// base key of the standard vaults
+    pub base_key: Pubkey,
+    pub treasury: Pubkey,
+    /// base key of the idle vaults
+    pub idle_vault_base: Pubkey,
+    pub strategy_programs: StrategyProgramIds,
+}
+
+fn pubkey(address: &str) -> Pubkey {
+    Pubkey::from_str(address).unwrap()
+}
+
+impl ClusterConfig {
+    pub fn mainnet() -> Self {
+        ClusterConfig {
+            vault_program_id: pubkey("24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi"),
+            base_key: pubkey("HWzXGcGHy4tcpYfaRDCyLNzXqBTv3E6BttpCH2vJxArv"),
+            treasury: pubkey("9kZeN47U2dubGbbzMrzzoRAUvpuxVLRcjW9XiFpYjUo4"),
+            idle_vault_base: Pubkey::default(),
+            strategy_programs: StrategyProgramIds {
+                port_finance: pubkey("Port7uDYB3wk6GJAw4KT1WpTeMtSu9bTcChBHkX2LfR"),
+                solend: pubkey("So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo"),
+                apricot: pubkey("6UeJYTLU1adaoHWeApWsoj1xNEDbWA2RhM2DLc8CrDDi"),
+                francium: pubkey("FC81tbGt6JWRXidaWYFXxGnTk4VgobhJHATvTRVMqgWj"),
+                tulip: pubkey("4bcFeLv4nydFrsZqV5CgwCVrPhkQKsXtzfy2KyMz7ozM"),
+                drift: pubkey("dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH"),
+                frakt: pubkey("A66HabVL3DzNzeJgcHYtRRNW1ZRMKwBfrdSR4kLsZ9DJ"),
+                marginfi: pubkey("MFv2hWf31Z9kbCa1snEPYctwafyhdvnV7FZnsebVacA"),
+                kamino: pubkey("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD"),
+                cypher: pubkey("CYPH3o83JX6jY6NkbproSpdmQ5VWJtxjfJ5P8veyYVu3"),
+            },
+        }
+    }
+
+    pub fn devnet() -> Self {
+        let mut config = Self::mainnet();
+        config.strategy_programs.port_finance =
+            pubkey("pdQ2rQQU5zH2rDgZ7xH2azMBJegUzUyunJ5Jd637hC4");
+        config.strategy_programs.solend = pubkey("ALend7Ketfx5bxh6ghsCDXAoDrhvEmsXT3cynB6aPLgx");
+        config
+    }
+
+    pub fn staging() -> Self {
+        let mut config = Self::mainnet();
+        config.vault_program_id = pubkey("6YRZW57XsrT2DxSNLXHHQd4QmiqBode4d6btASkRqcFo");
+        config.strategy_programs.kamino = pubkey("SLendK7ySfcEzyaFqy93gDnD3RtrpXJcnRwb6zFHJSh");
+        config
+    }
+}
+
+/// Config selected by the `devnet` and `staging` features, used by the program itself
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        if cfg!(feature = "staging") {
+            Self::staging()
+        } else if cfg!(feature = "devnet") {
+            Self::devnet()
+        } else {
+            Self::mainnet()
+        }
+    }
+}
+
+impl FromStr for ClusterConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "mainnet-beta" => Ok(Self::mainnet()),
+            "devnet" => Ok(Self::devnet()),
+            "staging" => Ok(Self::staging()),
+            _ => Err(format!("Unknown cluster config {}", s)),
+        }
+    }
+}
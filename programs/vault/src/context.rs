@@ -1,12 +1,14 @@
 use crate::get_base_address_for_idle_vault;
 use crate::get_base_key;
-use crate::get_treasury_address;
 use crate::seed;
 use crate::state::{Strategy, Vault, MAX_BUMPS};
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, Token, TokenAccount};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct VaultBumps {
     pub vault_bump: u8,
     pub token_vault_bump: u8,
@@ -15,8 +17,6 @@ pub struct VaultBumps {
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug)]
 pub struct StrategyBumps {
     pub strategy_index: u8,
-    pub strategy_bump: u8,
-    pub collateral_vault_bump: u8,
     pub other_bumps: [u8; MAX_BUMPS],
 }
 
@@ -213,3 +213,187 @@ pub struct InitializeIdleVault<'info> {
     /// system_program
     pub system_program: Program<'info, System>,
 }
+
+/// Accounts for enable_vault, update_locked_profit_degradation
+#[derive(Accounts)]
+pub struct UpdateVault<'info> {
+    #[account(mut, has_one = admin)]
+    pub vault: Box<Account<'info, Vault>>,
+    pub admin: Signer<'info>,
+}
+
+/// Accounts for set_operator
+#[derive(Accounts)]
+pub struct SetOperator<'info> {
+    #[account(mut, has_one = admin)]
+    pub vault: Box<Account<'info, Vault>>,
+    /// CHECK: New operator
+    pub operator: AccountInfo<'info>,
+    pub admin: Signer<'info>,
+}
+
+/// Accounts for transfer_admin, new admin must sign to accept
+#[derive(Accounts)]
+pub struct TransferAdmin<'info> {
+    #[account(mut, has_one = admin)]
+    pub vault: Box<Account<'info, Vault>>,
+    pub admin: Signer<'info>,
+    #[account(constraint = new_admin.key() != admin.key())]
+    pub new_admin: Signer<'info>,
+}
+
+/// Accounts for transfer_fee_vault
+#[derive(Accounts)]
+pub struct TransferFeeVault<'info> {
+    #[account(mut, has_one = admin)]
+    pub vault: Box<Account<'info, Vault>>,
+    pub admin: Signer<'info>,
+    /// New fee vault, must hold lp token
+    #[account(constraint = new_fee_vault.mint == vault.lp_mint)]
+    pub new_fee_vault: Box<Account<'info, TokenAccount>>,
+}
+
+/// Accounts for initialize_strategy
+#[derive(Accounts)]
+#[instruction(bumps: StrategyBumps)]
+pub struct InitializeStrategy<'info> {
+    #[account(mut, has_one = admin)]
+    pub vault: Box<Account<'info, Vault>>,
+    /// CHECK: Strategy program
+    pub strategy_program: AccountInfo<'info>,
+    /// Strategy account
+    #[account(
+        init,
+        seeds = [vault.key().as_ref(), reserve.key().as_ref(), &[bumps.strategy_index]],
+        bump,
+        payer = admin,
+        space = 8 + std::mem::size_of::<Strategy>(),
+    )]
+    pub strategy: Box<Account<'info, Strategy>>,
+    /// CHECK: Reserve account
+    #[account(mut)]
+    pub reserve: AccountInfo<'info>,
+    /// Collateral vault account
+    #[account(
+        init,
+        seeds = [seed::COLLATERAL_VAULT_PREFIX.as_ref(), strategy.key().as_ref()],
+        bump,
+        payer = admin,
+        token::mint = collateral_mint,
+        token::authority = vault,
+    )]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+    /// Collateral mint account
+    pub collateral_mint: Box<Account<'info, Mint>>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// system_program
+    pub system_program: Program<'info, System>,
+    /// rent
+    pub rent: Sysvar<'info, Rent>,
+    /// token_program
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for add_strategy, re-adds a strategy which was initialized for the vault
+#[derive(Accounts)]
+pub struct AddStrategy<'info> {
+    #[account(mut, has_one = admin)]
+    pub vault: Box<Account<'info, Vault>>,
+    #[account(constraint = strategy.vault == vault.key())]
+    pub strategy: Box<Account<'info, Strategy>>,
+    pub admin: Signer<'info>,
+}
+
+/// Accounts for remove_strategy, withdraws all liquidity of the strategy to the token vault
+#[derive(Accounts)]
+pub struct RemoveStrategy<'info> {
+    #[account(
+        mut,
+        has_one = token_vault,
+        has_one = lp_mint,
+        has_one = fee_vault,
+        has_one = admin,
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+    #[account(mut, constraint = strategy.vault == vault.key())]
+    pub strategy: Box<Account<'info, Strategy>>,
+    /// CHECK: Strategy program
+    pub strategy_program: AccountInfo<'info>,
+    #[account(mut, constraint = strategy.collateral_vault == collateral_vault.key())]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Reserve account
+    #[account(mut, constraint = strategy.reserve == reserve.key())]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub lp_mint: Box<Account<'info, Mint>>,
+    pub token_program: Program<'info, Token>,
+    pub admin: Signer<'info>,
+}
+
+/// Accounts for remove_strategy2, admin advances the liquidity the strategy can not withdraw
+#[derive(Accounts)]
+pub struct RemoveStrategy2<'info> {
+    #[account(
+        mut,
+        has_one = token_vault,
+        has_one = lp_mint,
+        has_one = fee_vault,
+        has_one = admin,
+    )]
+    pub vault: Box<Account<'info, Vault>>,
+    #[account(mut, constraint = strategy.vault == vault.key())]
+    pub strategy: Box<Account<'info, Strategy>>,
+    /// CHECK: Strategy program
+    pub strategy_program: AccountInfo<'info>,
+    #[account(mut, constraint = strategy.collateral_vault == collateral_vault.key())]
+    pub collateral_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Reserve account
+    #[account(mut, constraint = strategy.reserve == reserve.key())]
+    pub reserve: AccountInfo<'info>,
+    #[account(mut)]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+    /// Admin token account paying the advance
+    #[account(mut, constraint = token_admin_advance_payment.owner == admin.key())]
+    pub token_admin_advance_payment: Box<Account<'info, TokenAccount>>,
+    /// Vault owned token account receiving the advance
+    #[account(mut, constraint = token_vault_advance_payment.owner == vault.key())]
+    pub token_vault_advance_payment: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub fee_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub lp_mint: Box<Account<'info, Mint>>,
+    pub token_program: Program<'info, Token>,
+    pub admin: Signer<'info>,
+}
+
+/// Accounts for collect_dust, sends tokens of the token vault above total_amount to admin
+#[derive(Accounts)]
+pub struct CollectDust<'info> {
+    #[account(has_one = token_vault, has_one = admin)]
+    pub vault: Box<Account<'info, Vault>>,
+    #[account(mut)]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut, constraint = token_admin.owner == admin.key())]
+    pub token_admin: Box<Account<'info, TokenAccount>>,
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts for claim_rewards, transfers liquidity mining rewards of the strategy to the operator
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    pub vault: Box<Account<'info, Vault>>,
+    #[account(constraint = strategy.vault == vault.key())]
+    pub strategy: Box<Account<'info, Strategy>>,
+    pub token_program: Program<'info, Token>,
+    /// Reward token account owned by the strategy
+    #[account(mut)]
+    pub token_reward_acc: Box<Account<'info, TokenAccount>>,
+    #[account(constraint = vault.admin == operator.key() || vault.operator == operator.key())]
+    pub operator: Signer<'info>,
+}
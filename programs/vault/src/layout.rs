@@ -0,0 +1,170 @@
+//! Byte offsets of the Borsh layouts of vault accounts, including the 8-byte account discriminator.
+//! Each offset is the previous field offset plus the previous field size, in declaration order.
+use crate::state::{MAX_BUMPS, MAX_STRATEGY};
+
+const DISCRIMINATOR_SIZE: usize = 8;
+const PUBKEY_SIZE: usize = 32;
+const U64_SIZE: usize = 8;
+
+pub const VAULT_ENABLED_OFFSET: usize = DISCRIMINATOR_SIZE;
+/// vault_bump then token_vault_bump
+pub const VAULT_BUMPS_OFFSET: usize = VAULT_ENABLED_OFFSET + 1;
+pub const VAULT_TOTAL_AMOUNT_OFFSET: usize = VAULT_BUMPS_OFFSET + 2;
+pub const VAULT_TOKEN_VAULT_OFFSET: usize = VAULT_TOTAL_AMOUNT_OFFSET + U64_SIZE;
+pub const VAULT_FEE_VAULT_OFFSET: usize = VAULT_TOKEN_VAULT_OFFSET + PUBKEY_SIZE;
+pub const VAULT_TOKEN_MINT_OFFSET: usize = VAULT_FEE_VAULT_OFFSET + PUBKEY_SIZE;
+pub const VAULT_LP_MINT_OFFSET: usize = VAULT_TOKEN_MINT_OFFSET + PUBKEY_SIZE;
+pub const VAULT_STRATEGIES_OFFSET: usize = VAULT_LP_MINT_OFFSET + PUBKEY_SIZE;
+pub const VAULT_BASE_OFFSET: usize = VAULT_STRATEGIES_OFFSET + PUBKEY_SIZE * MAX_STRATEGY;
+pub const VAULT_ADMIN_OFFSET: usize = VAULT_BASE_OFFSET + PUBKEY_SIZE;
+pub const VAULT_OPERATOR_OFFSET: usize = VAULT_ADMIN_OFFSET + PUBKEY_SIZE;
+pub const VAULT_LOCKED_PROFIT_TRACKER_OFFSET: usize = VAULT_OPERATOR_OFFSET + PUBKEY_SIZE;
+/// End of the serialized vault, the rest of the account is padding
+pub const VAULT_LEN: usize = VAULT_LOCKED_PROFIT_TRACKER_OFFSET + 3 * U64_SIZE;
+
+pub const STRATEGY_RESERVE_OFFSET: usize = DISCRIMINATOR_SIZE;
+pub const STRATEGY_COLLATERAL_VAULT_OFFSET: usize = STRATEGY_RESERVE_OFFSET + PUBKEY_SIZE;
+/// 1-byte discriminant of StrategyType
+pub const STRATEGY_TYPE_OFFSET: usize = STRATEGY_COLLATERAL_VAULT_OFFSET + PUBKEY_SIZE;
+pub const STRATEGY_CURRENT_LIQUIDITY_OFFSET: usize = STRATEGY_TYPE_OFFSET + 1;
+pub const STRATEGY_BUMPS_OFFSET: usize = STRATEGY_CURRENT_LIQUIDITY_OFFSET + U64_SIZE;
+pub const STRATEGY_VAULT_OFFSET: usize = STRATEGY_BUMPS_OFFSET + MAX_BUMPS;
+pub const STRATEGY_IS_DISABLE_OFFSET: usize = STRATEGY_VAULT_OFFSET + PUBKEY_SIZE;
+/// End of the serialized strategy, the rest of the account is padding
+pub const STRATEGY_LEN: usize = STRATEGY_IS_DISABLE_OFFSET + 1;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::VaultBumps;
+    use crate::state::{LockedProfitTracker, Strategy, Vault};
+    use crate::strategy::base::StrategyType;
+    use anchor_lang::prelude::*;
+    use anchor_lang::Discriminator;
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new_from_array([seed; 32])
+    }
+
+    fn read(data: &[u8], offset: usize, len: usize) -> &[u8] {
+        &data[offset..offset + len]
+    }
+
+    #[test]
+    fn vault_offsets_match_serialized_fields() {
+        let mut strategies = [Pubkey::default(); MAX_STRATEGY];
+        for (i, strategy) in strategies.iter_mut().enumerate() {
+            *strategy = key(100 + i as u8);
+        }
+        let vault = Vault {
+            enabled: 1,
+            bumps: VaultBumps {
+                vault_bump: 2,
+                token_vault_bump: 3,
+            },
+            total_amount: 0x0102_0304_0506_0708,
+            token_vault: key(4),
+            fee_vault: key(5),
+            token_mint: key(6),
+            lp_mint: key(7),
+            strategies,
+            base: key(8),
+            admin: key(9),
+            operator: key(10),
+            locked_profit_tracker: LockedProfitTracker {
+                last_updated_locked_profit: 11,
+                last_report: 12,
+                locked_profit_degradation: 13,
+            },
+        };
+        let mut data = vec![];
+        vault.try_serialize(&mut data).unwrap();
+
+        assert_eq!(data.len(), VAULT_LEN);
+        assert_eq!(read(&data, 0, DISCRIMINATOR_SIZE), Vault::discriminator());
+        assert_eq!(data[VAULT_ENABLED_OFFSET], 1);
+        assert_eq!(read(&data, VAULT_BUMPS_OFFSET, 2), [2, 3]);
+        assert_eq!(
+            read(&data, VAULT_TOTAL_AMOUNT_OFFSET, U64_SIZE),
+            vault.total_amount.to_le_bytes()
+        );
+        for (offset, expected) in [
+            (VAULT_TOKEN_VAULT_OFFSET, vault.token_vault),
+            (VAULT_FEE_VAULT_OFFSET, vault.fee_vault),
+            (VAULT_TOKEN_MINT_OFFSET, vault.token_mint),
+            (VAULT_LP_MINT_OFFSET, vault.lp_mint),
+            (VAULT_BASE_OFFSET, vault.base),
+            (VAULT_ADMIN_OFFSET, vault.admin),
+            (VAULT_OPERATOR_OFFSET, vault.operator),
+        ] {
+            assert_eq!(read(&data, offset, PUBKEY_SIZE), expected.as_ref());
+        }
+        for (i, strategy) in strategies.iter().enumerate() {
+            assert_eq!(
+                read(
+                    &data,
+                    VAULT_STRATEGIES_OFFSET + i * PUBKEY_SIZE,
+                    PUBKEY_SIZE
+                ),
+                strategy.as_ref()
+            );
+        }
+        for (i, expected) in [11u64, 12, 13].iter().enumerate() {
+            assert_eq!(
+                read(
+                    &data,
+                    VAULT_LOCKED_PROFIT_TRACKER_OFFSET + i * U64_SIZE,
+                    U64_SIZE
+                ),
+                expected.to_le_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn strategy_offsets_match_serialized_fields() {
+        let mut bumps = [0u8; MAX_BUMPS];
+        for (i, bump) in bumps.iter_mut().enumerate() {
+            *bump = 200 + i as u8;
+        }
+        let strategy = Strategy {
+            reserve: key(1),
+            collateral_vault: key(2),
+            strategy_type: StrategyType::Marginfi,
+            current_liquidity: 0x0102_0304_0506_0708,
+            bumps,
+            vault: key(3),
+            is_disable: 1,
+        };
+        let mut data = vec![];
+        strategy.try_serialize(&mut data).unwrap();
+
+        assert_eq!(data.len(), STRATEGY_LEN);
+        assert_eq!(
+            read(&data, 0, DISCRIMINATOR_SIZE),
+            Strategy::discriminator()
+        );
+        assert_eq!(
+            read(&data, STRATEGY_RESERVE_OFFSET, PUBKEY_SIZE),
+            strategy.reserve.as_ref()
+        );
+        assert_eq!(
+            read(&data, STRATEGY_COLLATERAL_VAULT_OFFSET, PUBKEY_SIZE),
+            strategy.collateral_vault.as_ref()
+        );
+        assert_eq!(
+            read(&data, STRATEGY_TYPE_OFFSET, 1),
+            StrategyType::Marginfi.try_to_vec().unwrap()
+        );
+        assert_eq!(
+            read(&data, STRATEGY_CURRENT_LIQUIDITY_OFFSET, U64_SIZE),
+            strategy.current_liquidity.to_le_bytes()
+        );
+        assert_eq!(read(&data, STRATEGY_BUMPS_OFFSET, MAX_BUMPS), bumps);
+        assert_eq!(
+            read(&data, STRATEGY_VAULT_OFFSET, PUBKEY_SIZE),
+            strategy.vault.as_ref()
+        );
+        assert_eq!(data[STRATEGY_IS_DISABLE_OFFSET], 1);
+    }
+}
@@ -1,12 +1,20 @@
+pub mod cluster;
 pub mod context;
+pub mod layout;
 pub mod seed;
+#[cfg(feature = "serde")]
+pub mod serde_helper;
+pub mod simulator;
 pub mod state;
 pub mod strategy;
 pub mod utils;
 
 use crate::strategy::base::StrategyType;
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 use context::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::str::FromStr;
 
@@ -39,6 +47,7 @@ pub fn get_base_address_for_idle_vault() -> Pubkey {
 }
 
 #[program]
+#[allow(clippy::result_large_err, clippy::needless_lifetimes)]
 pub mod vault {
     use super::*;
 
@@ -109,6 +118,73 @@ pub mod vault {
     ) -> Result<()> {
         Ok(())
     }
+
+    #[allow(unused_variables)]
+    pub fn enable_vault(ctx: Context<UpdateVault>, enabled: u8) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn set_operator(ctx: Context<SetOperator>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn update_locked_profit_degradation(
+        ctx: Context<UpdateVault>,
+        locked_profit_degradation: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn transfer_admin(ctx: Context<TransferAdmin>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn transfer_fee_vault(ctx: Context<TransferFeeVault>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn initialize_strategy<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, InitializeStrategy<'info>>,
+        bumps: StrategyBumps,
+        strategy_type: StrategyType,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn remove_strategy<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, RemoveStrategy<'info>>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn remove_strategy2<'a, 'b, 'c, 'info>(
+        ctx: Context<'a, 'b, 'c, 'info, RemoveStrategy2<'info>>,
+        max_admin_pay_amount: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn collect_dust(ctx: Context<CollectDust>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn add_strategy(ctx: Context<AddStrategy>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[error_code]
@@ -166,48 +242,141 @@ pub enum VaultError {
 }
 
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AddLiquidity {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub lp_mint_amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub token_amount: u64,
 }
 
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RemoveLiquidity {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub lpunmint_amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub token_amount: u64,
 }
+
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StrategyDeposit {
     pub strategy_type: StrategyType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub token_amount: u64,
 }
 
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StrategyWithdraw {
     pub strategy_type: StrategyType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub collateral_amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub estimated_token_amount: u64,
 }
 
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StakingReward {
     pub strategy_type: StrategyType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub token_amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub mint_account: Pubkey,
 }
 
+/// Emitted by `claim_rewards`, the name of this event on chain
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClaimReward {
+    pub strategy_type: StrategyType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
+    pub token_amount: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
+    pub mint_account: Pubkey,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PerformanceFee {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub lp_mint_more: u64,
 }
 
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ReportLoss {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub strategy: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub loss: u64,
 }
 
 #[event]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TotalAmount {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub total_amount: u64,
 }
+
+/// Any event emitted by the vault program
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "name", content = "data")
+)]
+pub enum VaultEvent {
+    AddLiquidity(AddLiquidity),
+    RemoveLiquidity(RemoveLiquidity),
+    StrategyDeposit(StrategyDeposit),
+    StrategyWithdraw(StrategyWithdraw),
+    ClaimReward(ClaimReward),
+    PerformanceFee(PerformanceFee),
+    ReportLoss(ReportLoss),
+    TotalAmount(TotalAmount),
+}
+
+impl VaultEvent {
+    /// Decode event from the data of a `Program data:` log, data starts with the 8-byte event discriminator
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        fn decode_event<T: AnchorDeserialize>(data: &[u8]) -> Option<T> {
+            T::try_from_slice(&data[8..]).ok()
+        }
+
+        if data.len() < 8 {
+            return None;
+        }
+        let discriminator = &data[..8];
+        if discriminator == AddLiquidity::discriminator() {
+            decode_event(data).map(VaultEvent::AddLiquidity)
+        } else if discriminator == RemoveLiquidity::discriminator() {
+            decode_event(data).map(VaultEvent::RemoveLiquidity)
+        } else if discriminator == StrategyDeposit::discriminator() {
+            decode_event(data).map(VaultEvent::StrategyDeposit)
+        } else if discriminator == StrategyWithdraw::discriminator() {
+            decode_event(data).map(VaultEvent::StrategyWithdraw)
+        } else if discriminator == ClaimReward::discriminator() {
+            decode_event(data).map(VaultEvent::ClaimReward)
+        } else if discriminator == PerformanceFee::discriminator() {
+            decode_event(data).map(VaultEvent::PerformanceFee)
+        } else if discriminator == ReportLoss::discriminator() {
+            decode_event(data).map(VaultEvent::ReportLoss)
+        } else if discriminator == TotalAmount::discriminator() {
+            decode_event(data).map(VaultEvent::TotalAmount)
+        } else {
+            None
+        }
+    }
+}
@@ -3,6 +3,7 @@ pub static TOKEN_VAULT_PREFIX: &str = "token_vault";
 pub static LP_MINT_PREFIX: &str = "lp_mint";
 pub static COLLATERAL_VAULT_PREFIX: &str = "collateral_vault";
 pub static FEE_VAULT_PREFIX: &str = "fee_vault";
+pub static OBLIGATION_PREFIX: &str = "obligation";
 pub static SOLEND_OBLIGATION_PREFIX: &str = "solend_obligation";
 pub static SOLEND_OBLIGATION_OWNER_PREFIX: &str = "solend_obligation_owner";
 pub static APRICOT_USER_INFO_SIGNER_PREFIX: &str = "apricot_user_info_signer";
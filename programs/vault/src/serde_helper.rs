@@ -0,0 +1,285 @@
+//! Serde helpers for stable JSON: pubkeys as base58 strings, u64 as decimal strings
+use anchor_lang::prelude::Pubkey;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+use std::str::FromStr;
+
+pub mod pubkey_string {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&pubkey.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Pubkey::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
+pub mod pubkey_array_string {
+    use super::*;
+    use serde::ser::SerializeSeq;
+    use std::convert::TryInto;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        pubkeys: &[Pubkey; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(N))?;
+        for pubkey in pubkeys.iter() {
+            seq.serialize_element(&pubkey.to_string())?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[Pubkey; N], D::Error> {
+        let pubkeys = Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| Pubkey::from_str(s).map_err(D::Error::custom))
+            .collect::<Result<Vec<Pubkey>, D::Error>>()?;
+        let len = pubkeys.len();
+        pubkeys
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(len, &format!("{} pubkeys", N).as_str()))
+    }
+}
+
+pub mod u64_string {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        u64::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::VaultBumps;
+    use crate::state::{LockedProfitTracker, Strategy, Vault, MAX_STRATEGY};
+    use crate::strategy::base::StrategyType;
+    use crate::*;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    const TOKEN_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const STRATEGY: &str = "So11111111111111111111111111111111111111112";
+    const DEFAULT_PUBKEY: &str = "11111111111111111111111111111111";
+
+    fn pubkey(s: &str) -> Pubkey {
+        Pubkey::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn locked_profit_tracker_json_uses_string_u64() {
+        let tracker = LockedProfitTracker {
+            last_updated_locked_profit: u64::MAX,
+            last_report: 1_700_000_000,
+            locked_profit_degradation: 46_296_296,
+        };
+        let expected = r#"{"last_updated_locked_profit":"18446744073709551615","last_report":"1700000000","locked_profit_degradation":"46296296"}"#;
+        assert_eq!(serde_json::to_string(&tracker).unwrap(), expected);
+
+        let decoded: LockedProfitTracker = serde_json::from_str(expected).unwrap();
+        assert_eq!(decoded.last_updated_locked_profit, u64::MAX);
+        assert_eq!(decoded.last_report, 1_700_000_000);
+        assert_eq!(decoded.locked_profit_degradation, 46_296_296);
+    }
+
+    #[test]
+    fn vault_bumps_json_uses_numbers() {
+        let bumps = VaultBumps {
+            vault_bump: 254,
+            token_vault_bump: 253,
+        };
+        let expected = r#"{"vault_bump":254,"token_vault_bump":253}"#;
+        assert_eq!(serde_json::to_string(&bumps).unwrap(), expected);
+
+        let decoded: VaultBumps = serde_json::from_str(expected).unwrap();
+        assert_eq!((decoded.vault_bump, decoded.token_vault_bump), (254, 253));
+    }
+
+    #[test]
+    fn vault_json_uses_base58_pubkeys_and_string_u64() {
+        let mut strategies = [Pubkey::default(); MAX_STRATEGY];
+        strategies[0] = pubkey(STRATEGY);
+        let vault = Vault {
+            enabled: 1,
+            bumps: VaultBumps {
+                vault_bump: 254,
+                token_vault_bump: 253,
+            },
+            total_amount: 5_000_000_000,
+            token_mint: pubkey(TOKEN_MINT),
+            strategies,
+            locked_profit_tracker: LockedProfitTracker {
+                last_updated_locked_profit: 100,
+                last_report: 1_700_000_000,
+                locked_profit_degradation: 46_296_296,
+            },
+            ..Vault::default()
+        };
+        let mut expected_strategies = vec![DEFAULT_PUBKEY; MAX_STRATEGY];
+        expected_strategies[0] = STRATEGY;
+        let expected = json!({
+            "enabled": 1,
+            "bumps": {"vault_bump": 254, "token_vault_bump": 253},
+            "total_amount": "5000000000",
+            "token_vault": DEFAULT_PUBKEY,
+            "fee_vault": DEFAULT_PUBKEY,
+            "token_mint": TOKEN_MINT,
+            "lp_mint": DEFAULT_PUBKEY,
+            "strategies": expected_strategies,
+            "base": DEFAULT_PUBKEY,
+            "admin": DEFAULT_PUBKEY,
+            "operator": DEFAULT_PUBKEY,
+            "locked_profit_tracker": {
+                "last_updated_locked_profit": "100",
+                "last_report": "1700000000",
+                "locked_profit_degradation": "46296296"
+            }
+        });
+        assert_eq!(serde_json::to_value(vault).unwrap(), expected);
+
+        let decoded: Vault = serde_json::from_value(expected).unwrap();
+        assert_eq!(decoded.total_amount, 5_000_000_000);
+        assert_eq!(decoded.token_mint, pubkey(TOKEN_MINT));
+        assert_eq!(decoded.strategies, strategies);
+        assert_eq!(decoded.bumps.token_vault_bump, 253);
+        assert_eq!(decoded.locked_profit_tracker.last_report, 1_700_000_000);
+    }
+
+    #[test]
+    fn vault_json_rejects_wrong_strategy_count() {
+        let mut value = serde_json::to_value(Vault::default()).unwrap();
+        value["strategies"] = json!([DEFAULT_PUBKEY]);
+        assert!(serde_json::from_value::<Vault>(value).is_err());
+    }
+
+    #[test]
+    fn snapshot_json_flattens_vault_with_derived_amounts() {
+        let vault = Vault {
+            total_amount: 3_000,
+            locked_profit_tracker: LockedProfitTracker {
+                last_updated_locked_profit: 600,
+                last_report: 100,
+                ..LockedProfitTracker::default()
+            },
+            ..Vault::default()
+        };
+        // half of the 6 hours degradation
+        let value = serde_json::to_value(vault.snapshot(100 + 3 * 3600).unwrap()).unwrap();
+        assert_eq!(value["total_amount"], json!("3000"));
+        assert_eq!(value["current_time"], json!("10900"));
+        assert_eq!(value["locked_profit"], json!("300"));
+        assert_eq!(value["unlocked_amount"], json!("2700"));
+        assert_eq!(
+            value["locked_profit_tracker"]["last_updated_locked_profit"],
+            json!("600")
+        );
+    }
+
+    #[test]
+    fn strategy_json_uses_base58_pubkeys_and_string_u64() {
+        let strategy = Strategy {
+            reserve: pubkey(TOKEN_MINT),
+            strategy_type: StrategyType::SolendWithLM,
+            current_liquidity: 1_000,
+            bumps: [255, 254, 0, 0, 0, 0, 0, 0, 0, 0],
+            vault: pubkey(STRATEGY),
+            is_disable: 0,
+            ..Strategy::default()
+        };
+        let expected = format!(
+            r#"{{"reserve":"{}","collateral_vault":"{}","strategy_type":"SolendWithLM","current_liquidity":"1000","bumps":[255,254,0,0,0,0,0,0,0,0],"vault":"{}","is_disable":0}}"#,
+            TOKEN_MINT, DEFAULT_PUBKEY, STRATEGY
+        );
+        assert_eq!(serde_json::to_string(&strategy).unwrap(), expected);
+
+        let decoded: Strategy = serde_json::from_str(&expected).unwrap();
+        assert_eq!(decoded.reserve, pubkey(TOKEN_MINT));
+        assert_eq!(decoded.strategy_type, StrategyType::SolendWithLM);
+        assert_eq!(decoded.current_liquidity, 1_000);
+        assert_eq!(decoded.bumps, strategy.bumps);
+    }
+
+    #[test]
+    fn events_json_are_tagged_by_name() {
+        let events = vec![
+            (
+                VaultEvent::AddLiquidity(AddLiquidity {
+                    lp_mint_amount: 10,
+                    token_amount: u64::MAX,
+                }),
+                r#"{"name":"AddLiquidity","data":{"lp_mint_amount":"10","token_amount":"18446744073709551615"}}"#
+                    .to_string(),
+            ),
+            (
+                VaultEvent::RemoveLiquidity(RemoveLiquidity {
+                    lpunmint_amount: 5,
+                    token_amount: 6,
+                }),
+                r#"{"name":"RemoveLiquidity","data":{"lpunmint_amount":"5","token_amount":"6"}}"#
+                    .to_string(),
+            ),
+            (
+                VaultEvent::StrategyDeposit(StrategyDeposit {
+                    strategy_type: StrategyType::Kamino,
+                    token_amount: 7,
+                }),
+                r#"{"name":"StrategyDeposit","data":{"strategy_type":"Kamino","token_amount":"7"}}"#
+                    .to_string(),
+            ),
+            (
+                VaultEvent::StrategyWithdraw(StrategyWithdraw {
+                    strategy_type: StrategyType::Unknown(13),
+                    collateral_amount: 8,
+                    estimated_token_amount: 9,
+                }),
+                r#"{"name":"StrategyWithdraw","data":{"strategy_type":{"Unknown":13},"collateral_amount":"8","estimated_token_amount":"9"}}"#
+                    .to_string(),
+            ),
+            (
+                VaultEvent::ClaimReward(ClaimReward {
+                    strategy_type: StrategyType::SolendWithLM,
+                    token_amount: 11,
+                    mint_account: pubkey(TOKEN_MINT),
+                }),
+                format!(
+                    r#"{{"name":"ClaimReward","data":{{"strategy_type":"SolendWithLM","token_amount":"11","mint_account":"{}"}}}}"#,
+                    TOKEN_MINT
+                ),
+            ),
+            (
+                VaultEvent::PerformanceFee(PerformanceFee { lp_mint_more: 12 }),
+                r#"{"name":"PerformanceFee","data":{"lp_mint_more":"12"}}"#.to_string(),
+            ),
+            (
+                VaultEvent::ReportLoss(ReportLoss {
+                    strategy: pubkey(STRATEGY),
+                    loss: 13,
+                }),
+                format!(
+                    r#"{{"name":"ReportLoss","data":{{"strategy":"{}","loss":"13"}}}}"#,
+                    STRATEGY
+                ),
+            ),
+            (
+                VaultEvent::TotalAmount(TotalAmount { total_amount: 14 }),
+                r#"{"name":"TotalAmount","data":{"total_amount":"14"}}"#.to_string(),
+            ),
+        ];
+        for (event, expected) in events.iter() {
+            assert_eq!(&serde_json::to_string(event).unwrap(), expected);
+            let decoded: VaultEvent = serde_json::from_str(expected).unwrap();
+            assert_eq!(&serde_json::to_string(&decoded).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! In-memory vault following the accounting of the program, used for backtesting and checking integration math
+// Operations fail with the `VaultError`s of the program, in its anchor `Result`, and a withdrawal above the
+// reserve with the `InsufficientFunds` error of the spl-token transfer which fails on chain
+#![allow(clippy::result_large_err)]
+use crate::state::{Strategy, Vault};
+use crate::strategy::base::StrategyType;
+use crate::{
+    AddLiquidity, PerformanceFee, RemoveLiquidity, ReportLoss, StrategyDeposit, StrategyWithdraw,
+    TotalAmount, VaultError, VaultEvent, PERFORMANCE_FEE_DENOMINATOR, PERFORMANCE_FEE_NUMERATOR,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token::error::TokenError;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// Strategy of the simulated vault
+#[derive(Clone, Debug)]
+pub struct SimulatedStrategy {
+    pub pubkey: Pubkey,
+    pub state: Strategy,
+    /// liquidity the lending protocol owes the strategy, differs from current_liquidity until the next rebalance
+    pub protocol_liquidity: u64,
+}
+
+/// Vault with its token vault, lp mint, fee vault and strategies
+#[derive(Clone, Debug)]
+pub struct VaultSimulator {
+    pub vault: Vault,
+    pub strategies: Vec<SimulatedStrategy>,
+    /// balance of token_vault
+    pub token_vault_amount: u64,
+    /// supply of lp_mint
+    pub lp_supply: u64,
+    /// lp minted to fee_vault as performance fee
+    pub fee_vault_lp_amount: u64,
+    pub current_time: u64,
+    events: Vec<VaultEvent>,
+}
+
+fn math(value: Option<u64>) -> Result<u64> {
+    value.ok_or_else(|| VaultError::MathOverflow.into())
+}
+
+impl VaultSimulator {
+    /// Enabled empty vault with default locked profit degradation
+    pub fn new(current_time: u64) -> Self {
+        let vault = Vault {
+            enabled: 1,
+            ..Default::default()
+        };
+        Self::from_state(vault, vec![], 0, 0, current_time)
+    }
+
+    /// Simulate from existing state, token_vault_amount and lp_supply are the balances of token_vault and lp_mint
+    pub fn from_state(
+        vault: Vault,
+        strategies: Vec<(Pubkey, Strategy)>,
+        token_vault_amount: u64,
+        lp_supply: u64,
+        current_time: u64,
+    ) -> Self {
+        let strategies = strategies
+            .into_iter()
+            .map(|(pubkey, state)| SimulatedStrategy {
+                pubkey,
+                protocol_liquidity: state.current_liquidity,
+                state,
+            })
+            .collect();
+        VaultSimulator {
+            vault,
+            strategies,
+            token_vault_amount,
+            lp_supply,
+            fee_vault_lp_amount: 0,
+            current_time,
+            events: vec![],
+        }
+    }
+
+    /// Events emitted since the last call to take_events
+    pub fn events(&self) -> &[VaultEvent] {
+        &self.events
+    }
+
+    pub fn take_events(&mut self) -> Vec<VaultEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn set_time(&mut self, current_time: u64) {
+        self.current_time = current_time;
+    }
+
+    pub fn advance_time(&mut self, seconds: u64) {
+        self.current_time = self.current_time.saturating_add(seconds);
+    }
+
+    pub fn get_unlocked_amount(&self) -> Option<u64> {
+        self.vault.get_unlocked_amount(self.current_time)
+    }
+
+    /// Token value of lp amount at current time
+    pub fn get_amount_by_share(&self, share: u64) -> Option<u64> {
+        self.vault
+            .get_amount_by_share(self.current_time, share, self.lp_supply)
+    }
+
+    pub fn get_strategy(&self, strategy: &Pubkey) -> Option<&SimulatedStrategy> {
+        self.strategies.iter().find(|item| item.pubkey == *strategy)
+    }
+
+    fn get_strategy_mut(&mut self, strategy: &Pubkey) -> Result<&mut SimulatedStrategy> {
+        self.strategies
+            .iter_mut()
+            .find(|item| item.pubkey == *strategy)
+            .ok_or_else(|| VaultError::StrategyIsNotExisted.into())
+    }
+
+    /// Run operation on a copy of the simulator and keep its changes only when it succeeds,
+    /// a failed operation leaves the state untouched like a reverted transaction
+    fn transact<T>(&mut self, operation: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let mut simulator = self.clone();
+        let result = operation(&mut simulator)?;
+        *self = simulator;
+        Ok(result)
+    }
+
+    fn assert_enabled(&self) -> Result<()> {
+        if self.vault.enabled != 1 {
+            return Err(VaultError::VaultIsDisabled.into());
+        }
+        Ok(())
+    }
+
+    /// Add strategy to the first free slot of the vault
+    pub fn add_strategy(&mut self, strategy: Pubkey, strategy_type: StrategyType) -> Result<()> {
+        if self.vault.is_strategy_existed(strategy) {
+            return Err(VaultError::StrategyExisted.into());
+        }
+        let slot = self
+            .vault
+            .strategies
+            .iter()
+            .position(|item| *item == Pubkey::default())
+            .ok_or(VaultError::MaxStrategyReached)?;
+        self.vault.strategies[slot] = strategy;
+        self.strategies.push(SimulatedStrategy {
+            pubkey: strategy,
+            state: Strategy {
+                strategy_type,
+                ..Default::default()
+            },
+            protocol_liquidity: 0,
+        });
+        Ok(())
+    }
+
+    /// Interest accrued by the lending protocol, reported as profit on the next rebalance
+    pub fn accrue_yield(&mut self, strategy: &Pubkey, amount: u64) -> Result<()> {
+        let strategy = self.get_strategy_mut(strategy)?;
+        strategy.protocol_liquidity = math(strategy.protocol_liquidity.checked_add(amount))?;
+        Ok(())
+    }
+
+    /// Loss of the lending protocol, reported as loss on the next rebalance
+    pub fn apply_loss(&mut self, strategy: &Pubkey, amount: u64) -> Result<()> {
+        let strategy = self.get_strategy_mut(strategy)?;
+        strategy.protocol_liquidity = math(strategy.protocol_liquidity.checked_sub(amount))?;
+        Ok(())
+    }
+
+    /// Deposit token_amount, return the minted lp amount
+    pub fn deposit(&mut self, token_amount: u64, minimum_lp_token_amount: u64) -> Result<u64> {
+        self.assert_enabled()?;
+        if token_amount == 0 {
+            return Err(VaultError::AmountMustGreaterThanZero.into());
+        }
+        let quote = self
+            .vault
+            .quote_deposit(self.current_time, token_amount, self.lp_supply)
+            .ok_or(VaultError::MathOverflow)?;
+        if quote.lp_amount < minimum_lp_token_amount {
+            return Err(VaultError::ExceededSlippage.into());
+        }
+
+        self.vault.total_amount = math(self.vault.total_amount.checked_add(token_amount))?;
+        self.token_vault_amount = math(self.token_vault_amount.checked_add(token_amount))?;
+        self.lp_supply = math(self.lp_supply.checked_add(quote.lp_amount))?;
+
+        self.events.push(VaultEvent::AddLiquidity(AddLiquidity {
+            lp_mint_amount: quote.lp_amount,
+            token_amount,
+        }));
+        Ok(quote.lp_amount)
+    }
+
+    /// Burn unmint_amount of lp for tokens of the reserve, return the withdrawn token amount
+    pub fn withdraw(&mut self, unmint_amount: u64, min_out_amount: u64) -> Result<u64> {
+        self.assert_enabled()?;
+        if unmint_amount == 0 || unmint_amount > self.lp_supply {
+            return Err(VaultError::InvalidUnmintAmount.into());
+        }
+        let out_amount = math(self.get_amount_by_share(unmint_amount))?;
+        if out_amount < min_out_amount {
+            return Err(VaultError::ExceededSlippage.into());
+        }
+        // the token transfer fails on chain when the reserve is short
+        if out_amount > self.token_vault_amount {
+            return Err(ProgramError::from(TokenError::InsufficientFunds).into());
+        }
+
+        self.vault.total_amount = math(self.vault.total_amount.checked_sub(out_amount))?;
+        self.token_vault_amount -= out_amount;
+        self.lp_supply -= unmint_amount;
+
+        self.events
+            .push(VaultEvent::RemoveLiquidity(RemoveLiquidity {
+                lpunmint_amount: unmint_amount,
+                token_amount: out_amount,
+            }));
+        Ok(out_amount)
+    }
+
+    /// Move token_amount from the reserve to strategy, after reporting its profit or loss
+    pub fn deposit_strategy(&mut self, strategy: &Pubkey, token_amount: u64) -> Result<()> {
+        self.transact(|simulator| simulator.apply_deposit_strategy(strategy, token_amount))
+    }
+
+    fn apply_deposit_strategy(&mut self, strategy: &Pubkey, token_amount: u64) -> Result<()> {
+        self.assert_enabled()?;
+        if token_amount == 0 {
+            return Err(VaultError::AmountMustGreaterThanZero.into());
+        }
+        self.update_liquidity(strategy)?;
+        self.token_vault_amount = math(self.token_vault_amount.checked_sub(token_amount))?;
+
+        let item = self.get_strategy_mut(strategy)?;
+        item.protocol_liquidity = math(item.protocol_liquidity.checked_add(token_amount))?;
+        item.state.current_liquidity = item.protocol_liquidity;
+        let strategy_type = item.state.strategy_type;
+
+        self.events
+            .push(VaultEvent::StrategyDeposit(StrategyDeposit {
+                strategy_type,
+                token_amount,
+            }));
+        Ok(())
+    }
+
+    /// Move token_amount from strategy back to the reserve, after reporting its profit or loss.
+    /// Collateral is assumed to be 1:1 with liquidity.
+    pub fn withdraw_strategy(&mut self, strategy: &Pubkey, token_amount: u64) -> Result<()> {
+        self.transact(|simulator| simulator.apply_withdraw_strategy(strategy, token_amount))
+    }
+
+    fn apply_withdraw_strategy(&mut self, strategy: &Pubkey, token_amount: u64) -> Result<()> {
+        self.assert_enabled()?;
+        if token_amount == 0 {
+            return Err(VaultError::AmountMustGreaterThanZero.into());
+        }
+        self.update_liquidity(strategy)?;
+
+        let item = self.get_strategy_mut(strategy)?;
+        item.protocol_liquidity = math(item.protocol_liquidity.checked_sub(token_amount))?;
+        item.state.current_liquidity = item.protocol_liquidity;
+        let strategy_type = item.state.strategy_type;
+        self.token_vault_amount = math(self.token_vault_amount.checked_add(token_amount))?;
+
+        self.events
+            .push(VaultEvent::StrategyWithdraw(StrategyWithdraw {
+                strategy_type,
+                collateral_amount: token_amount,
+                estimated_token_amount: token_amount,
+            }));
+        Ok(())
+    }
+
+    /// Report the difference between protocol and recorded liquidity of strategy.
+    /// Profit is locked and charged the performance fee as lp minted to fee vault,
+    /// loss is deducted at once from the total amount and from the profit still locked.
+    fn update_liquidity(&mut self, strategy: &Pubkey) -> Result<()> {
+        let current_time = self.current_time;
+        let item = self.get_strategy_mut(strategy)?;
+        let (before, after) = (item.state.current_liquidity, item.protocol_liquidity);
+        item.state.current_liquidity = after;
+
+        match after.cmp(&before) {
+            Ordering::Greater => {
+                let gain = after - before;
+                self.vault.total_amount = math(self.vault.total_amount.checked_add(gain))?;
+                self.vault
+                    .locked_profit_tracker
+                    .update_locked_profit(gain, current_time)
+                    .ok_or(VaultError::MathOverflow)?;
+
+                let performance_fee = u64::try_from(
+                    u128::from(gain) * PERFORMANCE_FEE_NUMERATOR / PERFORMANCE_FEE_DENOMINATOR,
+                )
+                .map_err(|_| VaultError::MathOverflow)?;
+                let lp_mint_more = if self.lp_supply == 0 {
+                    0
+                } else {
+                    math(self.vault.get_unmint_amount(
+                        current_time,
+                        performance_fee,
+                        self.lp_supply,
+                    ))?
+                };
+                self.lp_supply = math(self.lp_supply.checked_add(lp_mint_more))?;
+                self.fee_vault_lp_amount =
+                    math(self.fee_vault_lp_amount.checked_add(lp_mint_more))?;
+                self.events
+                    .push(VaultEvent::PerformanceFee(PerformanceFee { lp_mint_more }));
+            }
+            Ordering::Less => {
+                let loss = before - after;
+                self.vault.total_amount = math(self.vault.total_amount.checked_sub(loss))?;
+                self.vault
+                    .locked_profit_tracker
+                    .report_loss(loss, current_time)
+                    .ok_or(VaultError::MathOverflow)?;
+                self.events.push(VaultEvent::ReportLoss(ReportLoss {
+                    strategy: *strategy,
+                    loss,
+                }));
+            }
+            Ordering::Equal => {}
+        }
+        self.events.push(VaultEvent::TotalAmount(TotalAmount {
+            total_amount: self.vault.total_amount,
+        }));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::LOCKED_PROFIT_DEGRADATION_DENOMINATOR;
+
+    const START: u64 = 1_000;
+
+    fn simulator_with_strategy() -> (VaultSimulator, Pubkey) {
+        let mut simulator = VaultSimulator::new(START);
+        let strategy = Pubkey::new_unique();
+        simulator
+            .add_strategy(strategy, StrategyType::SolendWithoutLM)
+            .unwrap();
+        (simulator, strategy)
+    }
+
+    /// Seconds until locked profit is fully released at the default degradation
+    fn unlock_duration(simulator: &VaultSimulator) -> u64 {
+        let degradation = u128::from(
+            simulator
+                .vault
+                .locked_profit_tracker
+                .locked_profit_degradation,
+        );
+        u64::try_from(LOCKED_PROFIT_DEGRADATION_DENOMINATOR / degradation + 1).unwrap()
+    }
+
+    #[test]
+    fn deposit_and_withdraw_follow_share_price() {
+        let mut simulator = VaultSimulator::new(START);
+        assert_eq!(simulator.deposit(1_000, 1_000).unwrap(), 1_000);
+
+        simulator.vault.total_amount = 2_000;
+        simulator.token_vault_amount = 2_000;
+        assert_eq!(simulator.deposit(500, 0).unwrap(), 250);
+        assert_eq!(simulator.lp_supply, 1_250);
+        assert_eq!(simulator.vault.total_amount, 2_500);
+
+        assert!(simulator.withdraw(250, 501).is_err());
+        assert_eq!(simulator.withdraw(250, 500).unwrap(), 500);
+        assert_eq!(simulator.lp_supply, 1_000);
+        assert_eq!(simulator.vault.total_amount, 2_000);
+        assert_eq!(simulator.token_vault_amount, 2_000);
+    }
+
+    #[test]
+    fn withdraw_above_reserve_fails_like_token_transfer() {
+        let (mut simulator, strategy) = simulator_with_strategy();
+        simulator.deposit(1_000, 0).unwrap();
+        simulator.deposit_strategy(&strategy, 600).unwrap();
+
+        let error = simulator.withdraw(500, 0).unwrap_err();
+        assert_eq!(
+            error,
+            Error::from(ProgramError::from(TokenError::InsufficientFunds))
+        );
+        assert_ne!(error, Error::from(VaultError::InvalidUnmintAmount));
+        assert_eq!(simulator.token_vault_amount, 400);
+        assert_eq!(simulator.lp_supply, 1_000);
+        assert_eq!(simulator.withdraw(400, 0).unwrap(), 400);
+    }
+
+    #[test]
+    fn profit_mints_performance_fee_and_is_locked() {
+        let (mut simulator, strategy) = simulator_with_strategy();
+        simulator.deposit(10_000, 0).unwrap();
+        simulator.deposit_strategy(&strategy, 10_000).unwrap();
+        simulator.accrue_yield(&strategy, 1_000).unwrap();
+        simulator.take_events();
+
+        simulator.withdraw_strategy(&strategy, 1).unwrap();
+        // 5% of the gain, minted at the price before the gain unlocks
+        assert_eq!(simulator.fee_vault_lp_amount, 50);
+        assert_eq!(simulator.lp_supply, 10_050);
+        assert_eq!(simulator.vault.total_amount, 11_000);
+        assert_eq!(simulator.get_unlocked_amount(), Some(10_000));
+
+        simulator.advance_time(unlock_duration(&simulator));
+        assert_eq!(simulator.get_unlocked_amount(), Some(11_000));
+        assert_eq!(simulator.get_amount_by_share(10_050), Some(11_000));
+
+        let events = simulator.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                VaultEvent::PerformanceFee(PerformanceFee { lp_mint_more: 50 }),
+                VaultEvent::TotalAmount(TotalAmount {
+                    total_amount: 11_000
+                }),
+                VaultEvent::StrategyWithdraw(StrategyWithdraw {
+                    strategy_type: StrategyType::SolendWithoutLM,
+                    collateral_amount: 1,
+                    estimated_token_amount: 1,
+                }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn loss_is_deducted_from_locked_profit() {
+        let (mut simulator, strategy) = simulator_with_strategy();
+        simulator.deposit(1_000, 0).unwrap();
+        simulator.deposit_strategy(&strategy, 1_000).unwrap();
+        simulator.accrue_yield(&strategy, 100).unwrap();
+        simulator.withdraw_strategy(&strategy, 100).unwrap();
+
+        simulator.apply_loss(&strategy, 600).unwrap();
+        simulator.take_events();
+        simulator.withdraw_strategy(&strategy, 100).unwrap();
+
+        assert_eq!(simulator.vault.total_amount, 500);
+        assert_eq!(
+            simulator
+                .vault
+                .locked_profit_tracker
+                .calculate_locked_profit(simulator.current_time),
+            Some(0)
+        );
+        assert_eq!(simulator.get_unlocked_amount(), Some(500));
+        // 1_000 lp of the deposit and 5 lp of performance fee share the remaining 500
+        assert_eq!(simulator.lp_supply, 1_005);
+        assert_eq!(simulator.withdraw(402, 0).unwrap(), 200);
+
+        let events = simulator.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                VaultEvent::ReportLoss(ReportLoss { loss: 600, .. }),
+                VaultEvent::TotalAmount(TotalAmount { total_amount: 500 }),
+                VaultEvent::StrategyWithdraw(_),
+                VaultEvent::RemoveLiquidity(RemoveLiquidity {
+                    token_amount: 200,
+                    ..
+                }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn failed_strategy_operation_leaves_state_unchanged() {
+        let (mut simulator, strategy) = simulator_with_strategy();
+        simulator.deposit(1_000, 0).unwrap();
+        simulator.deposit_strategy(&strategy, 600).unwrap();
+        simulator.accrue_yield(&strategy, 100).unwrap();
+        let events = simulator.events().len();
+
+        assert!(simulator.deposit_strategy(&strategy, 401).is_err());
+        assert!(simulator.withdraw_strategy(&strategy, 701).is_err());
+
+        assert_eq!(simulator.vault.total_amount, 1_000);
+        assert_eq!(
+            simulator
+                .vault
+                .locked_profit_tracker
+                .last_updated_locked_profit,
+            0
+        );
+        assert_eq!(simulator.lp_supply, 1_000);
+        assert_eq!(simulator.token_vault_amount, 400);
+        assert_eq!(
+            simulator
+                .get_strategy(&strategy)
+                .unwrap()
+                .state
+                .current_liquidity,
+            600
+        );
+        assert_eq!(simulator.events().len(), events);
+    }
+}
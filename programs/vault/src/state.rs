@@ -1,6 +1,8 @@
 use crate::context::VaultBumps;
 use crate::strategy::base::StrategyType;
 use anchor_lang::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::fmt::Debug;
 
@@ -10,29 +12,46 @@ pub const LOCKED_PROFIT_DEGRADATION_DENOMINATOR: u128 = 1_000_000_000_000;
 
 #[account]
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Vault {
     pub enabled: u8,
     pub bumps: VaultBumps,
 
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub total_amount: u64,
 
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub token_vault: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub fee_vault: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub token_mint: Pubkey,
 
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub lp_mint: Pubkey,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "crate::serde_helper::pubkey_array_string")
+    )]
     pub strategies: [Pubkey; MAX_STRATEGY],
 
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub base: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub admin: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub operator: Pubkey, // person to send crank
     pub locked_profit_tracker: LockedProfitTracker,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LockedProfitTracker {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub last_updated_locked_profit: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub last_report: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub locked_profit_degradation: u64,
 }
 
@@ -66,6 +85,128 @@ impl LockedProfitTracker {
         let locked_profit = u64::try_from(locked_profit).ok()?;
         Some(locked_profit)
     }
+
+    /// Lock a newly reported gain on top of the profit still locked at current_time
+    pub fn update_locked_profit(&mut self, gain: u64, current_time: u64) -> Option<()> {
+        let locked_profit = self.calculate_locked_profit(current_time)?;
+        self.last_updated_locked_profit = locked_profit.checked_add(gain)?;
+        self.last_report = current_time;
+        Some(())
+    }
+
+    /// Deduct a reported loss from the profit still locked at current_time, as yearn does,
+    /// so locked profit never exceeds the vault total amount
+    pub fn report_loss(&mut self, loss: u64, current_time: u64) -> Option<()> {
+        let locked_profit = self.calculate_locked_profit(current_time)?;
+        self.last_updated_locked_profit = locked_profit.saturating_sub(loss);
+        self.last_report = current_time;
+        Some(())
+    }
+
+    /// Return unlocked part of last_updated_locked_profit at current_time
+    pub fn calculate_unlocked_profit(&self, current_time: u64) -> Option<u64> {
+        self.last_updated_locked_profit
+            .checked_sub(self.calculate_locked_profit(current_time)?)
+    }
+
+    /// Return the time when locked profit is fully dripped, None if it never unlocks.
+    /// Rounding down may bring locked profit to 0 a few seconds earlier
+    pub fn unlock_finish_time(&self) -> Option<u64> {
+        if self.last_updated_locked_profit == 0 {
+            return Some(self.last_report);
+        }
+        let locked_profit_degradation = u128::from(self.locked_profit_degradation);
+        if locked_profit_degradation == 0 {
+            return None;
+        }
+        // locked profit reaches 0 once duration * degradation >= denominator
+        let duration = (LOCKED_PROFIT_DEGRADATION_DENOMINATOR + locked_profit_degradation - 1)
+            / locked_profit_degradation;
+        self.last_report.checked_add(u64::try_from(duration).ok()?)
+    }
+
+    /// Return the earliest time when at least `amount` of the locked profit is unlocked
+    pub fn unlock_time_for_amount(&self, amount: u64) -> Option<u64> {
+        if amount > self.last_updated_locked_profit {
+            return None;
+        }
+        let finish_time = self.unlock_finish_time()?;
+        // unlocked profit is non decreasing in time, binary search the formula itself to stay consistent with rounding
+        let (mut low, mut high) = (self.last_report, finish_time);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.calculate_unlocked_profit(mid)? >= amount {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Some(low)
+    }
+
+    /// Project locked and unlocked profit every `step` seconds from `start_time` until fully unlocked
+    pub fn locked_profit_timeline(
+        &self,
+        start_time: u64,
+        step: u64,
+    ) -> Option<Vec<LockedProfitPoint>> {
+        if step == 0 {
+            return None;
+        }
+        let start_time = start_time.max(self.last_report);
+        let finish_time = self.unlock_finish_time()?.max(start_time);
+
+        let mut points = vec![];
+        let mut timestamp = start_time;
+        loop {
+            let locked = self.calculate_locked_profit(timestamp)?;
+            points.push(LockedProfitPoint {
+                timestamp,
+                locked,
+                unlocked: self.last_updated_locked_profit.checked_sub(locked)?,
+            });
+            if timestamp >= finish_time {
+                break;
+            }
+            timestamp = timestamp.saturating_add(step).min(finish_time);
+        }
+        Some(points)
+    }
+}
+
+/// A point returned by `LockedProfitTracker::locked_profit_timeline`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockedProfitPoint {
+    pub timestamp: u64,
+    pub locked: u64,
+    pub unlocked: u64,
+}
+
+/// Result of `Vault::quote_deposit`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepositQuote {
+    /// lp amount minted to user
+    pub lp_amount: u64,
+    /// unlocked amount used as share price numerator
+    pub unlocked_amount: u64,
+    /// lp supply used as share price denominator
+    pub lp_supply: u64,
+    /// token amount not backed by the minted lp, stays in vault
+    pub rounding_loss: u64,
+}
+
+/// Result of `Vault::snapshot`
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VaultSnapshot {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub vault: Vault,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
+    pub current_time: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
+    pub locked_profit: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
+    pub unlocked_amount: u64,
 }
 
 impl Vault {
@@ -106,6 +247,46 @@ impl Vault {
         .ok()
     }
 
+    /// Quote the lp amount minted by `deposit`, lp_supply is the current supply of the lp mint
+    pub fn quote_deposit(
+        &self,
+        current_time: u64,
+        token_amount: u64,
+        lp_supply: u64,
+    ) -> Option<DepositQuote> {
+        let unlocked_amount = self.get_unlocked_amount(current_time)?;
+        // first depositor gets lp 1:1 with token amount
+        if lp_supply == 0 {
+            return Some(DepositQuote {
+                lp_amount: token_amount,
+                unlocked_amount,
+                lp_supply,
+                rounding_loss: 0,
+            });
+        }
+        let lp_amount = self.get_unmint_amount(current_time, token_amount, lp_supply)?;
+        let lp_value = self.get_amount_by_share(current_time, lp_amount, lp_supply)?;
+        Some(DepositQuote {
+            lp_amount,
+            unlocked_amount,
+            lp_supply,
+            rounding_loss: token_amount.checked_sub(lp_value)?,
+        })
+    }
+
+    /// Return vault with its locked and unlocked amount at current_time
+    pub fn snapshot(&self, current_time: u64) -> Option<VaultSnapshot> {
+        let locked_profit = self
+            .locked_profit_tracker
+            .calculate_locked_profit(current_time)?;
+        Some(VaultSnapshot {
+            vault: self.clone(),
+            current_time,
+            locked_profit,
+            unlocked_amount: self.total_amount.checked_sub(locked_profit)?,
+        })
+    }
+
     pub fn is_strategy_existed(&self, pubkey: Pubkey) -> bool {
         for item in self.strategies.iter() {
             if *item == pubkey {
@@ -116,6 +297,7 @@ impl Vault {
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for StrategyType {
     fn default() -> Self {
         StrategyType::PortFinanceWithoutLM
@@ -124,12 +306,171 @@ impl Default for StrategyType {
 
 #[account]
 #[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Strategy {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub reserve: Pubkey,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub collateral_vault: Pubkey,
     pub strategy_type: StrategyType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::u64_string"))]
     pub current_liquidity: u64,
     pub bumps: [u8; MAX_BUMPS],
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_helper::pubkey_string"))]
     pub vault: Pubkey,
     pub is_disable: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_with(total_amount: u64, locked_profit: u64, last_report: u64) -> Vault {
+        Vault {
+            total_amount,
+            locked_profit_tracker: LockedProfitTracker {
+                last_updated_locked_profit: locked_profit,
+                last_report,
+                ..LockedProfitTracker::default()
+            },
+            ..Vault::default()
+        }
+    }
+
+    #[test]
+    fn snapshot_derives_locked_and_unlocked_amounts() {
+        let vault = vault_with(3_000, 600, 100);
+        let snapshot = vault.snapshot(100).unwrap();
+        assert_eq!(
+            (
+                snapshot.current_time,
+                snapshot.locked_profit,
+                snapshot.unlocked_amount
+            ),
+            (100, 600, 2_400)
+        );
+        // half of the 6 hours degradation
+        let snapshot = vault.snapshot(100 + 3 * 3600).unwrap();
+        assert_eq!(
+            (snapshot.locked_profit, snapshot.unlocked_amount),
+            (300, 2_700)
+        );
+        assert_eq!(snapshot.vault.total_amount, 3_000);
+        // fully unlocked
+        let snapshot = vault.snapshot(100 + 6 * 3600).unwrap();
+        assert_eq!(
+            (snapshot.locked_profit, snapshot.unlocked_amount),
+            (0, 3_000)
+        );
+        // before the last report
+        assert!(vault.snapshot(99).is_none());
+    }
+
+    #[test]
+    fn quote_deposit_first_deposit_mints_lp_one_to_one() {
+        let vault = vault_with(0, 0, 0);
+        assert_eq!(
+            vault.quote_deposit(100, 1_000, 0),
+            Some(DepositQuote {
+                lp_amount: 1_000,
+                unlocked_amount: 0,
+                lp_supply: 0,
+                rounding_loss: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn quote_deposit_matches_share_price() {
+        // 500 of the 3_000 are still locked right after the report
+        let vault = vault_with(3_000, 500, 100);
+        let quote = vault.quote_deposit(100, 1_000, 2_000).unwrap();
+        assert_eq!(quote.unlocked_amount, 2_500);
+        assert_eq!(quote.lp_supply, 2_000);
+        assert_eq!(quote.lp_amount, 800);
+        assert_eq!(
+            vault.get_amount_by_share(100, quote.lp_amount, 2_000),
+            Some(1_000)
+        );
+        assert_eq!(quote.rounding_loss, 0);
+    }
+
+    #[test]
+    fn quote_deposit_reports_rounding_loss() {
+        let vault = vault_with(3_000, 0, 0);
+        let quote = vault.quote_deposit(0, 100, 1_000).unwrap();
+        // 100 * 1_000 / 3_000 = 33 lp, worth 33 * 3_000 / 1_000 = 99
+        assert_eq!(quote.lp_amount, 33);
+        assert_eq!(vault.get_amount_by_share(0, 33, 1_000), Some(99));
+        assert_eq!(quote.rounding_loss, 1);
+    }
+
+    fn tracker(locked_profit: u64, last_report: u64, degradation: u64) -> LockedProfitTracker {
+        LockedProfitTracker {
+            last_updated_locked_profit: locked_profit,
+            last_report,
+            locked_profit_degradation: degradation,
+        }
+    }
+
+    #[test]
+    fn unlock_finish_time_is_ceil_of_denominator_over_degradation() {
+        // 1e12 / 7 is not an integer, the finish time rounds up
+        let degradation = 7;
+        let tracker = tracker(1_000_000, 1_000, degradation);
+        let duration = (LOCKED_PROFIT_DEGRADATION_DENOMINATOR + 6) / 7;
+        let finish_time = tracker.unlock_finish_time().unwrap();
+        assert_eq!(finish_time, 1_000 + duration as u64);
+        assert_eq!(tracker.calculate_locked_profit(finish_time), Some(0));
+        // one second earlier the locked fund ratio is still below the denominator
+        assert!(u128::from(finish_time - 1 - 1_000) * 7 < LOCKED_PROFIT_DEGRADATION_DENOMINATOR);
+
+        let tracker = LockedProfitTracker {
+            last_updated_locked_profit: 1_000_000,
+            last_report: 1_000,
+            ..LockedProfitTracker::default()
+        };
+        // the default degradation 1e12 / 21_600 is rounded down, so dripping takes one more second
+        assert_eq!(tracker.unlock_finish_time(), Some(1_000 + 6 * 3600 + 1));
+    }
+
+    #[test]
+    fn zero_degradation_never_unlocks() {
+        let tracker = tracker(1_000_000, 1_000, 0);
+        assert_eq!(tracker.unlock_finish_time(), None);
+        assert_eq!(tracker.unlock_time_for_amount(1), None);
+        assert_eq!(tracker.locked_profit_timeline(1_000, 60), None);
+    }
+
+    #[test]
+    fn unlock_time_for_amount_is_earliest_time() {
+        let tracker = tracker(1_000_003, 1_000, 46_296_297);
+        for amount in [1, 333_333, 500_000, 999_999, 1_000_003] {
+            let time = tracker.unlock_time_for_amount(amount).unwrap();
+            assert!(tracker.calculate_unlocked_profit(time).unwrap() >= amount);
+            assert!(tracker.calculate_unlocked_profit(time - 1).unwrap() < amount);
+        }
+        assert_eq!(tracker.unlock_time_for_amount(0), Some(1_000));
+        assert_eq!(tracker.unlock_time_for_amount(1_000_004), None);
+    }
+
+    #[test]
+    fn locked_profit_timeline_goes_from_locked_profit_to_zero() {
+        let tracker = tracker(1_000_003, 1_000, 46_296_297);
+        let points = tracker.locked_profit_timeline(0, 1_000).unwrap();
+        let first = points.first().unwrap();
+        let last = points.last().unwrap();
+        assert_eq!(first.timestamp, 1_000);
+        assert_eq!((first.locked, first.unlocked), (1_000_003, 0));
+        assert_eq!(last.timestamp, tracker.unlock_finish_time().unwrap());
+        assert_eq!((last.locked, last.unlocked), (0, 1_000_003));
+        for window in points.windows(2) {
+            assert!(window[1].timestamp > window[0].timestamp);
+            assert!(window[1].locked <= window[0].locked);
+            assert_eq!(
+                Some(window[1].locked),
+                tracker.calculate_locked_profit(window[1].timestamp)
+            );
+        }
+    }
+}
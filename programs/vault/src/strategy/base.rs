@@ -1,19 +1,11 @@
 use anchor_lang::prelude::*;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(
-    AnchorSerialize,
-    AnchorDeserialize,
-    Clone,
-    Copy,
-    Debug,
-    PartialEq,
-    Serialize,
-    Deserialize,
-    Eq,
-    Hash,
-)]
+/// Borsh encoded as its 1-byte discriminant. Discriminants added on chain after this crate decode as `Unknown`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum StrategyType {
     PortFinanceWithoutLM,
     PortFinanceWithLM,
@@ -29,6 +21,63 @@ pub enum StrategyType {
     Frakt,
     Marginfi,
     Kamino,
+    /// Strategy type unknown to this crate, keeps the on-chain discriminant so it is written back unchanged
+    Unknown(u8),
+}
+
+/// Known strategy types, indexed by discriminant
+const KNOWN_STRATEGY_TYPES: [StrategyType; 13] = [
+    StrategyType::PortFinanceWithoutLM,
+    StrategyType::PortFinanceWithLM,
+    StrategyType::SolendWithoutLM,
+    StrategyType::Mango,
+    StrategyType::SolendWithLM,
+    StrategyType::ApricotWithoutLM,
+    StrategyType::Francium,
+    StrategyType::Tulip,
+    StrategyType::Vault,
+    StrategyType::Drift,
+    StrategyType::Frakt,
+    StrategyType::Marginfi,
+    StrategyType::Kamino,
+];
+
+impl StrategyType {
+    /// Return the on-chain discriminant
+    pub fn discriminant(&self) -> u8 {
+        match self {
+            StrategyType::Unknown(discriminant) => *discriminant,
+            known => KNOWN_STRATEGY_TYPES
+                .iter()
+                .position(|strategy_type| strategy_type == known)
+                .unwrap() as u8,
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, StrategyType::Unknown(_))
+    }
+}
+
+impl From<u8> for StrategyType {
+    fn from(discriminant: u8) -> Self {
+        KNOWN_STRATEGY_TYPES
+            .get(usize::from(discriminant))
+            .copied()
+            .unwrap_or(StrategyType::Unknown(discriminant))
+    }
+}
+
+impl AnchorSerialize for StrategyType {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&[self.discriminant()])
+    }
+}
+
+impl AnchorDeserialize for StrategyType {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        Ok(u8::deserialize_reader(reader)?.into())
+    }
 }
 
 impl std::fmt::Display for StrategyType {
@@ -56,31 +105,112 @@ pub fn get_apricot_program_id() -> Pubkey {
 }
 
 #[cfg(feature = "devnet")]
+#[deprecated(note = "use `ClusterConfig::strategy_programs.get` with the config of the cluster")]
 pub fn get_port_finance_program_id() -> Pubkey {
     Pubkey::from_str("pdQ2rQQU5zH2rDgZ7xH2azMBJegUzUyunJ5Jd637hC4").unwrap()
 }
 
 #[cfg(not(feature = "devnet"))]
+#[deprecated(note = "use `ClusterConfig::strategy_programs.get` with the config of the cluster")]
 pub fn get_port_finance_program_id() -> Pubkey {
     Pubkey::from_str("Port7uDYB3wk6GJAw4KT1WpTeMtSu9bTcChBHkX2LfR").unwrap()
 }
 
 #[cfg(feature = "devnet")]
+#[deprecated(note = "use `ClusterConfig::strategy_programs.get` with the config of the cluster")]
 pub fn get_solend_program_id() -> Pubkey {
     Pubkey::from_str("ALend7Ketfx5bxh6ghsCDXAoDrhvEmsXT3cynB6aPLgx").unwrap()
 }
 
 #[cfg(not(feature = "devnet"))]
+#[deprecated(note = "use `ClusterConfig::strategy_programs.get` with the config of the cluster")]
 pub fn get_solend_program_id() -> Pubkey {
     Pubkey::from_str("So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo").unwrap()
 }
 
 #[cfg(feature = "staging")]
+#[deprecated(note = "use `ClusterConfig::strategy_programs.get` with the config of the cluster")]
 pub fn get_kamino_program_id() -> Pubkey {
     Pubkey::from_str("SLendK7ySfcEzyaFqy93gDnD3RtrpXJcnRwb6zFHJSh").unwrap()
 }
 
 #[cfg(not(feature = "staging"))]
+#[deprecated(note = "use `ClusterConfig::strategy_programs.get` with the config of the cluster")]
 pub fn get_kamino_program_id() -> Pubkey {
     Pubkey::from_str("KLend2g3cP87fffoy8q1mQqGKjrxjC8boSyAYavgmjD").unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Strategy, MAX_BUMPS};
+    use anchor_lang::Discriminator;
+
+    /// Strategy account bytes with the strategy type discriminant at offset 72
+    fn strategy_account_data(discriminant: u8) -> Vec<u8> {
+        let mut data = Strategy::discriminator().to_vec();
+        data.extend([1u8; 32]);
+        data.extend([2u8; 32]);
+        data.push(discriminant);
+        data.extend(500u64.to_le_bytes());
+        data.extend([3u8; MAX_BUMPS]);
+        data.extend([4u8; 32]);
+        data.push(0);
+        data
+    }
+
+    #[test]
+    fn decode_strategy_with_unknown_strategy_type() {
+        for discriminant in [13u8, 14, 255] {
+            let data = strategy_account_data(discriminant);
+            let strategy = Strategy::try_deserialize(&mut data.as_slice()).unwrap();
+            assert_eq!(strategy.strategy_type, StrategyType::Unknown(discriminant));
+            assert_eq!(strategy.current_liquidity, 500);
+            assert_eq!(strategy.vault, Pubkey::new_from_array([4u8; 32]));
+
+            let mut encoded = vec![];
+            strategy.try_serialize(&mut encoded).unwrap();
+            assert_eq!(encoded, data);
+        }
+    }
+
+    #[test]
+    fn known_strategy_types_round_trip() {
+        for (discriminant, strategy_type) in KNOWN_STRATEGY_TYPES.iter().enumerate() {
+            let encoded = strategy_type.try_to_vec().unwrap();
+            assert_eq!(encoded, vec![discriminant as u8]);
+            assert_eq!(
+                StrategyType::try_from_slice(&encoded).unwrap(),
+                *strategy_type
+            );
+            assert!(!strategy_type.is_unknown());
+
+            let data = strategy_account_data(discriminant as u8);
+            let strategy = Strategy::try_deserialize(&mut data.as_slice()).unwrap();
+            assert_eq!(strategy.strategy_type, *strategy_type);
+        }
+        assert_eq!(StrategyType::Kamino.discriminant(), 12);
+        assert_eq!(StrategyType::Unknown(13).discriminant(), 13);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn strategy_type_serde_form() {
+        assert_eq!(
+            serde_json::to_string(&StrategyType::SolendWithLM).unwrap(),
+            r#""SolendWithLM""#
+        );
+        assert_eq!(
+            serde_json::to_string(&StrategyType::Unknown(13)).unwrap(),
+            r#"{"Unknown":13}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<StrategyType>(r#""Kamino""#).unwrap(),
+            StrategyType::Kamino
+        );
+        assert_eq!(
+            serde_json::from_str::<StrategyType>(r#"{"Unknown":13}"#).unwrap(),
+            StrategyType::Unknown(13)
+        );
+    }
+}
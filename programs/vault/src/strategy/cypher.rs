@@ -3,5 +3,13 @@ use anchor_lang::prelude::Pubkey;
 
 /// Return strategy owner PDA
 pub fn get_strategy_owner(strategy_pubkey: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[b"cypher", strategy_pubkey.as_ref()], &ID)
+    get_strategy_owner_with_program_id(strategy_pubkey, &ID)
+}
+
+/// Strategy owner of a vault program deployed at program_id
+pub fn get_strategy_owner_with_program_id(
+    strategy_pubkey: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"cypher", strategy_pubkey.as_ref()], program_id)
 }
@@ -0,0 +1,399 @@
+use crate::cluster::ClusterConfig;
+use crate::state::Strategy;
+use crate::strategy::remaining_accounts::StrategyRemainingAccounts;
+use crate::strategy::reserve::{read_pubkey, read_u128, read_u64};
+use crate::{VaultError, ID};
+use anchor_lang::prelude::*;
+use std::convert::{TryFrom, TryInto};
+
+/// Sub account of the drift user of the strategy owner
+pub const SUB_ACCOUNT_ID: u16 = 0;
+
+/// return strategy owner
+pub fn get_strategy_owner(strategy: &Pubkey) -> (Pubkey, u8) {
+    get_strategy_owner_with_program_id(strategy, &ID)
+}
+
+/// Strategy owner of a vault program deployed at program_id
+pub fn get_strategy_owner_with_program_id(strategy: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&["drift".as_ref(), strategy.as_ref()], program_id)
+}
+
+pub fn get_state(drift_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&["drift_state".as_ref()], drift_program_id)
+}
+
+pub fn get_signer(drift_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&["drift_signer".as_ref()], drift_program_id)
+}
+
+/// Drift user of authority, SUB_ACCOUNT_ID for the strategy owner
+pub fn get_user(
+    authority: &Pubkey,
+    sub_account_id: u16,
+    drift_program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            "user".as_ref(),
+            authority.as_ref(),
+            &sub_account_id.to_le_bytes(),
+        ],
+        drift_program_id,
+    )
+}
+
+pub fn get_user_stats(authority: &Pubkey, drift_program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &["user_stats".as_ref(), authority.as_ref()],
+        drift_program_id,
+    )
+}
+
+/// Precision of the product of a spot balance and a cumulative interest, 1e9 times 1e10
+const SPOT_BALANCE_INTEREST_DECIMALS: u32 = 19;
+
+/// Offset of the spot positions of a drift user
+const USER_SPOT_POSITIONS_OFFSET: usize = 104;
+/// Length and number of the spot positions of a drift user
+const SPOT_POSITION_LEN: usize = 40;
+const MAX_SPOT_POSITIONS: usize = 8;
+
+/// Fields of a drift spot market, the reserve of a drift strategy, used by the vault
+#[derive(Clone, Copy, Debug)]
+pub struct DriftSpotMarket {
+    pub pubkey: Pubkey,
+    pub oracle: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    /// scaled balance of every deposit, SPOT_BALANCE_PRECISION (1e9)
+    pub deposit_balance: u128,
+    /// scaled balance of every borrow, SPOT_BALANCE_PRECISION (1e9)
+    pub borrow_balance: u128,
+    /// SPOT_CUMULATIVE_INTEREST_PRECISION (1e10)
+    pub cumulative_deposit_interest: u128,
+    /// SPOT_CUMULATIVE_INTEREST_PRECISION (1e10)
+    pub cumulative_borrow_interest: u128,
+    pub decimals: u32,
+    pub market_index: u16,
+}
+
+impl DriftSpotMarket {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let decimals = data
+            .get(680..684)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u32::from_le_bytes)
+            .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        let market_index = data
+            .get(684..686)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        Ok(DriftSpotMarket {
+            pubkey: read_pubkey(data, 8)?,
+            oracle: read_pubkey(data, 40)?,
+            mint: read_pubkey(data, 72)?,
+            vault: read_pubkey(data, 104)?,
+            deposit_balance: read_u128(data, 432)?,
+            borrow_balance: read_u128(data, 448)?,
+            cumulative_deposit_interest: read_u128(data, 464)?,
+            cumulative_borrow_interest: read_u128(data, 480)?,
+            decimals,
+            market_index,
+        })
+    }
+
+    /// Token amount of a deposit of scaled_balance (SPOT_BALANCE_PRECISION, 1e9), rounded down
+    pub fn deposit_token_amount(&self, scaled_balance: u64) -> Option<u64> {
+        let token_amount = u128::from(scaled_balance)
+            .checked_mul(self.cumulative_deposit_interest)?
+            .checked_div(self.precision_decrease()?)?;
+        u64::try_from(token_amount).ok()
+    }
+
+    /// Deposits of the spot market not borrowed, which the spot market vault can pay out now.
+    /// Deposits are rounded down and borrows up, as drift does
+    pub fn available_liquidity(&self) -> Option<u64> {
+        let precision_decrease = self.precision_decrease()?;
+        let total_deposits = self
+            .deposit_balance
+            .checked_mul(self.cumulative_deposit_interest)?
+            .checked_div(precision_decrease)?;
+        let total_borrows = self
+            .borrow_balance
+            .checked_mul(self.cumulative_borrow_interest)?
+            .checked_add(precision_decrease - 1)?
+            .checked_div(precision_decrease)?;
+        u64::try_from(total_deposits.saturating_sub(total_borrows)).ok()
+    }
+
+    fn precision_decrease(&self) -> Option<u128> {
+        10u128.checked_pow(SPOT_BALANCE_INTEREST_DECIMALS.checked_sub(self.decimals)?)
+    }
+}
+
+/// Scaled balance deposited by the drift user in the spot market of market_index, 0 without deposit
+pub fn get_spot_deposit(user_data: &[u8], market_index: u16) -> Result<u64> {
+    for index in 0..MAX_SPOT_POSITIONS {
+        let offset = USER_SPOT_POSITIONS_OFFSET + index * SPOT_POSITION_LEN;
+        let position = user_data
+            .get(offset..offset + SPOT_POSITION_LEN)
+            .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        let scaled_balance = read_u64(position, 0)?;
+        let position_market_index = u16::from_le_bytes([position[32], position[33]]);
+        // balance type 0 is a deposit, 1 a borrow
+        if scaled_balance > 0 && position_market_index == market_index && position[34] == 0 {
+            return Ok(scaled_balance);
+        }
+    }
+    Ok(0)
+}
+
+pub struct DriftAccounts;
+
+impl StrategyRemainingAccounts for DriftAccounts {
+    /// drift state, drift user, drift user stats, strategy owner, spot market vault, drift signer, oracle
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        _strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let drift_program_id = config.strategy_programs.drift;
+        let spot_market = DriftSpotMarket::decode(reserve_data)?;
+        let (strategy_owner, _) =
+            get_strategy_owner_with_program_id(strategy, &config.vault_program_id);
+        Ok(vec![
+            AccountMeta::new_readonly(get_state(&drift_program_id).0, false),
+            AccountMeta::new(
+                get_user(&strategy_owner, SUB_ACCOUNT_ID, &drift_program_id).0,
+                false,
+            ),
+            AccountMeta::new(get_user_stats(&strategy_owner, &drift_program_id).0, false),
+            AccountMeta::new_readonly(strategy_owner, false),
+            AccountMeta::new(spot_market.vault, false),
+            AccountMeta::new_readonly(get_signer(&drift_program_id).0, false),
+            AccountMeta::new_readonly(spot_market.oracle, false),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::base::StrategyType;
+    use crate::strategy::remaining_accounts::get_remaining_accounts_builder;
+    use crate::strategy::valuer::get_strategy_valuer;
+
+    /// Scaled deposit and borrow balances of a spot market with their cumulative interests
+    struct SpotMarketBalances {
+        deposit_balance: u128,
+        borrow_balance: u128,
+        cumulative_deposit_interest: u128,
+        cumulative_borrow_interest: u128,
+    }
+
+    /// Spot market without balances, at cumulative_deposit_interest
+    fn deposit_interest(cumulative_deposit_interest: u128) -> SpotMarketBalances {
+        SpotMarketBalances {
+            deposit_balance: 0,
+            borrow_balance: 0,
+            cumulative_deposit_interest,
+            cumulative_borrow_interest: 0,
+        }
+    }
+
+    /// Spot market packed in the declaration order of drift `SpotMarket` up to the market index, padded to its length
+    fn spot_market_data(
+        keys: [Pubkey; 4],
+        balances: SpotMarketBalances,
+        decimals: u32,
+        market_index: u16,
+    ) -> Vec<u8> {
+        let mut data = vec![0u8; 8];
+        // pubkey, oracle, mint, vault
+        for key in keys.iter() {
+            data.extend_from_slice(key.as_ref());
+        }
+        // name, historical oracle and index data, revenue and spot fee pools, insurance fund
+        data.extend_from_slice(&[0u8; 32 + 48 + 40 + 24 + 24 + 112]);
+        // total spot fee
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&balances.deposit_balance.to_le_bytes());
+        data.extend_from_slice(&balances.borrow_balance.to_le_bytes());
+        data.extend_from_slice(&balances.cumulative_deposit_interest.to_le_bytes());
+        data.extend_from_slice(&balances.cumulative_borrow_interest.to_le_bytes());
+        // total social and quote social losses
+        data.extend_from_slice(&[0u8; 2 * 16]);
+        // from withdraw guard threshold to next deposit record id
+        data.extend_from_slice(&[0u8; 14 * 8]);
+        // from initial asset weight to max borrow rate
+        data.extend_from_slice(&[0u8; 10 * 4]);
+        data.extend_from_slice(&decimals.to_le_bytes());
+        data.extend_from_slice(&market_index.to_le_bytes());
+        data.resize(776, 0);
+        data
+    }
+
+    /// Drift user with (market index, balance type, scaled balance) spot positions, packed in declaration order
+    fn user_data(positions: &[(u16, u8, u64)]) -> Vec<u8> {
+        // discriminator, authority, delegate, name
+        let mut data = vec![0u8; 8 + 3 * 32];
+        for (market_index, balance_type, scaled_balance) in positions.iter() {
+            data.extend_from_slice(&scaled_balance.to_le_bytes());
+            // open bids, open asks, cumulative deposits
+            data.extend_from_slice(&[0u8; 3 * 8]);
+            data.extend_from_slice(&market_index.to_le_bytes());
+            data.push(*balance_type);
+            // open orders and padding
+            data.extend_from_slice(&[0u8; 5]);
+        }
+        data.resize(4376, 0);
+        data
+    }
+
+    #[test]
+    fn spot_deposit_is_valued_at_cumulative_deposit_interest() {
+        let config = ClusterConfig::mainnet();
+        let strategy = Pubkey::new_unique();
+        let strategy_state = Strategy {
+            strategy_type: StrategyType::Drift,
+            ..Default::default()
+        };
+        // usdc spot market, deposit interest of 1.05
+        let reserve_data = spot_market_data(
+            [Pubkey::new_unique(); 4],
+            deposit_interest(10_500_000_000),
+            6,
+            3,
+        );
+        let spot_market = DriftSpotMarket::decode(&reserve_data).unwrap();
+        assert_eq!(
+            (
+                spot_market.cumulative_deposit_interest,
+                spot_market.decimals,
+                spot_market.market_index
+            ),
+            (10_500_000_000, 6, 3)
+        );
+
+        let valuer = get_strategy_valuer(StrategyType::Drift);
+        let (strategy_owner, _) = get_strategy_owner(&strategy);
+        assert_eq!(
+            valuer
+                .position_account(&config, &strategy, &strategy_state)
+                .unwrap(),
+            Some(
+                get_user(
+                    &strategy_owner,
+                    SUB_ACCOUNT_ID,
+                    &config.strategy_programs.drift
+                )
+                .0
+            )
+        );
+        // 2 usdc deposited in market 3, a borrow and a deposit in other markets are ignored
+        let user = user_data(&[(0, 0, 7_000_000_000), (3, 1, 5), (3, 0, 2_000_000_000)]);
+        assert_eq!(get_spot_deposit(&user, 3).unwrap(), 2_000_000_000);
+        assert_eq!(
+            valuer.value(&strategy_state, &user, &reserve_data).unwrap(),
+            Some(2_100_000)
+        );
+        assert_eq!(
+            valuer
+                .value(&strategy_state, &user_data(&[]), &reserve_data)
+                .unwrap(),
+            Some(0)
+        );
+        assert!(valuer
+            .value(&strategy_state, &user[..200], &reserve_data)
+            .is_err());
+    }
+
+    #[test]
+    fn available_liquidity_is_deposits_not_borrowed() {
+        // usdc spot market, deposit interest of 1.05 and borrow interest of 1.1
+        let reserve_data = spot_market_data(
+            [Pubkey::new_unique(); 4],
+            SpotMarketBalances {
+                deposit_balance: 10_000_000_000,
+                borrow_balance: 4_000_000_001,
+                cumulative_deposit_interest: 10_500_000_000,
+                cumulative_borrow_interest: 11_000_000_000,
+            },
+            6,
+            3,
+        );
+        let spot_market = DriftSpotMarket::decode(&reserve_data).unwrap();
+        assert_eq!(
+            (spot_market.deposit_balance, spot_market.borrow_balance),
+            (10_000_000_000, 4_000_000_001)
+        );
+        assert_eq!(spot_market.cumulative_borrow_interest, 11_000_000_000);
+        // 10.5 usdc deposited, 4.400_000_0011 usdc borrowed rounded up
+        assert_eq!(spot_market.available_liquidity(), Some(6_099_999));
+
+        let valuer = get_strategy_valuer(StrategyType::Drift);
+        assert_eq!(
+            valuer.available_liquidity(&reserve_data).unwrap(),
+            Some(6_099_999)
+        );
+        assert!(valuer.available_liquidity(&reserve_data[..400]).is_err());
+    }
+
+    #[test]
+    fn remaining_accounts_follow_strategy_owner_and_spot_market() {
+        let config = ClusterConfig::mainnet();
+        let drift_program_id = config.strategy_programs.drift;
+        let (spot_market, oracle, mint, vault) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let strategy = Pubkey::new_unique();
+        let strategy_state = Strategy {
+            strategy_type: StrategyType::Drift,
+            reserve: spot_market,
+            ..Default::default()
+        };
+        let reserve_data = spot_market_data(
+            [spot_market, oracle, mint, vault],
+            deposit_interest(0),
+            6,
+            0,
+        );
+
+        let decoded = DriftSpotMarket::decode(&reserve_data).unwrap();
+        assert_eq!(
+            (decoded.pubkey, decoded.oracle, decoded.mint, decoded.vault),
+            (spot_market, oracle, mint, vault)
+        );
+
+        let accounts = get_remaining_accounts_builder(StrategyType::Drift)
+            .unwrap()
+            .remaining_accounts(&config, &strategy, &strategy_state, &reserve_data)
+            .unwrap();
+        let (strategy_owner, _) = get_strategy_owner(&strategy);
+        assert_eq!(
+            accounts
+                .iter()
+                .map(|account| (account.pubkey, account.is_writable))
+                .collect::<Vec<_>>(),
+            vec![
+                (get_state(&drift_program_id).0, false),
+                (
+                    get_user(&strategy_owner, SUB_ACCOUNT_ID, &drift_program_id).0,
+                    true
+                ),
+                (get_user_stats(&strategy_owner, &drift_program_id).0, true),
+                (strategy_owner, false),
+                (vault, true),
+                (get_signer(&drift_program_id).0, false),
+                (oracle, false),
+            ]
+        );
+    }
+}
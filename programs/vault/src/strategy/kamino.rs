@@ -0,0 +1,403 @@
+use crate::cluster::ClusterConfig;
+use crate::state::Strategy;
+use crate::strategy::remaining_accounts::StrategyRemainingAccounts;
+use crate::strategy::reserve::{read_pubkey, read_u128, read_u64};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar;
+use std::convert::TryFrom;
+
+pub static LENDING_MARKET_AUTHORITY_PREFIX: &str = "lma";
+pub static RESERVE_LIQUIDITY_SUPPLY_PREFIX: &str = "reserve_liq_supply";
+pub static FEE_RECEIVER_PREFIX: &str = "fee_receiver";
+pub static RESERVE_COLLATERAL_MINT_PREFIX: &str = "reserve_coll_mint";
+pub static RESERVE_COLLATERAL_SUPPLY_PREFIX: &str = "reserve_coll_supply";
+
+/// Fractional bits of the scaled fractions (`_sf` fields) of klend
+pub const FRACTION_BITS: u32 = 60;
+
+/// Return lending market authority PDA of klend program_id
+pub fn get_lending_market_authority(lending_market: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            LENDING_MARKET_AUTHORITY_PREFIX.as_ref(),
+            lending_market.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+/// Return vanilla obligation of owner in lending_market, tag 0 and id 0 without seed accounts
+pub fn get_obligation(
+    owner: &Pubkey,
+    lending_market: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &[0],
+            &[0],
+            owner.as_ref(),
+            lending_market.as_ref(),
+            Pubkey::default().as_ref(),
+            Pubkey::default().as_ref(),
+        ],
+        program_id,
+    )
+}
+
+/// PDAs of the reserve of liquidity_mint in lending_market
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KaminoReserveAddresses {
+    pub liquidity_supply: (Pubkey, u8),
+    pub fee_receiver: (Pubkey, u8),
+    pub collateral_mint: (Pubkey, u8),
+    pub collateral_supply: (Pubkey, u8),
+}
+
+impl KaminoReserveAddresses {
+    pub fn derive(lending_market: &Pubkey, liquidity_mint: &Pubkey, program_id: &Pubkey) -> Self {
+        let find_reserve_pda = |prefix: &str| {
+            Pubkey::find_program_address(
+                &[
+                    prefix.as_ref(),
+                    lending_market.as_ref(),
+                    liquidity_mint.as_ref(),
+                ],
+                program_id,
+            )
+        };
+        KaminoReserveAddresses {
+            liquidity_supply: find_reserve_pda(RESERVE_LIQUIDITY_SUPPLY_PREFIX),
+            fee_receiver: find_reserve_pda(FEE_RECEIVER_PREFIX),
+            collateral_mint: find_reserve_pda(RESERVE_COLLATERAL_MINT_PREFIX),
+            collateral_supply: find_reserve_pda(RESERVE_COLLATERAL_SUPPLY_PREFIX),
+        }
+    }
+}
+
+/// Fields of a klend reserve used by the vault, as of the last refresh of the reserve
+#[derive(Clone, Copy, Debug)]
+pub struct KaminoReserve {
+    pub lending_market: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_supply: Pubkey,
+    pub liquidity_fee_vault: Pubkey,
+    pub liquidity_available_amount: u64,
+    pub liquidity_borrowed_amount_sf: u128,
+    pub accumulated_protocol_fees_sf: u128,
+    pub accumulated_referrer_fees_sf: u128,
+    pub pending_referrer_fees_sf: u128,
+    pub collateral_mint: Pubkey,
+    pub collateral_mint_total_supply: u64,
+    pub collateral_supply: Pubkey,
+}
+
+impl KaminoReserve {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        Ok(KaminoReserve {
+            // after discriminator, version and last_update
+            lending_market: read_pubkey(data, 32)?,
+            // liquidity starts after farm_collateral and farm_debt
+            liquidity_mint: read_pubkey(data, 128)?,
+            liquidity_supply: read_pubkey(data, 160)?,
+            liquidity_fee_vault: read_pubkey(data, 192)?,
+            liquidity_available_amount: read_u64(data, 224)?,
+            liquidity_borrowed_amount_sf: read_u128(data, 232)?,
+            accumulated_protocol_fees_sf: read_u128(data, 344)?,
+            accumulated_referrer_fees_sf: read_u128(data, 360)?,
+            pending_referrer_fees_sf: read_u128(data, 376)?,
+            // collateral starts after the liquidity padding
+            collateral_mint: read_pubkey(data, 2560)?,
+            collateral_mint_total_supply: read_u64(data, 2592)?,
+            collateral_supply: read_pubkey(data, 2600)?,
+        })
+    }
+
+    /// Liquidity owned by the collateral holders: available and borrowed amount without fees, rounded down
+    pub fn total_liquidity(&self) -> Option<u64> {
+        let total_sf = (u128::from(self.liquidity_available_amount) << FRACTION_BITS)
+            .checked_add(self.liquidity_borrowed_amount_sf)?
+            .checked_sub(self.accumulated_protocol_fees_sf)?
+            .checked_sub(self.accumulated_referrer_fees_sf)?
+            .checked_sub(self.pending_referrer_fees_sf)?;
+        u64::try_from(total_sf >> FRACTION_BITS).ok()
+    }
+
+    /// Liquidity redeemed for collateral_amount of cToken, 1:1 before the first deposit
+    pub fn collateral_to_liquidity(&self, collateral_amount: u64) -> Option<u64> {
+        let total_liquidity = self.total_liquidity()?;
+        if self.collateral_mint_total_supply == 0 || total_liquidity == 0 {
+            return Some(collateral_amount);
+        }
+        let liquidity = u128::from(collateral_amount)
+            .checked_mul(u128::from(total_liquidity))?
+            .checked_div(u128::from(self.collateral_mint_total_supply))?;
+        u64::try_from(liquidity).ok()
+    }
+
+    /// cToken minted for liquidity_amount, 1:1 before the first deposit
+    pub fn liquidity_to_collateral(&self, liquidity_amount: u64) -> Option<u64> {
+        let total_liquidity = self.total_liquidity()?;
+        if self.collateral_mint_total_supply == 0 || total_liquidity == 0 {
+            return Some(liquidity_amount);
+        }
+        let collateral = u128::from(liquidity_amount)
+            .checked_mul(u128::from(self.collateral_mint_total_supply))?
+            .checked_div(u128::from(total_liquidity))?;
+        u64::try_from(collateral).ok()
+    }
+}
+
+pub struct KaminoAccounts;
+
+impl StrategyRemainingAccounts for KaminoAccounts {
+    /// lending market, lending market authority, liquidity mint, collateral mint, liquidity supply, instructions sysvar.
+    /// The collateral is held in the collateral vault of the strategy, so no obligation is passed
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        _strategy: &Pubkey,
+        _strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let reserve = KaminoReserve::decode(reserve_data)?;
+        let (lending_market_authority, _) =
+            get_lending_market_authority(&reserve.lending_market, &config.strategy_programs.kamino);
+        Ok(vec![
+            AccountMeta::new_readonly(reserve.lending_market, false),
+            AccountMeta::new_readonly(lending_market_authority, false),
+            AccountMeta::new_readonly(reserve.liquidity_mint, false),
+            AccountMeta::new(reserve.collateral_mint, false),
+            AccountMeta::new(reserve.liquidity_supply, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::base::StrategyType;
+    use crate::strategy::remaining_accounts::get_remaining_accounts_builder;
+    use std::str::FromStr;
+
+    /// Kamino main market and its usdc reserve on mainnet
+    const MAIN_MARKET: &str = "7u3HeHxYDLhnCoErrtycNokbQYbWGzLs6JSDqGAv5PfF";
+    const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn pubkey(address: &str) -> Pubkey {
+        Pubkey::from_str(address).unwrap()
+    }
+
+    /// Fields of a klend reserve read by `KaminoReserve::decode`
+    struct ReserveFixture {
+        lending_market: Pubkey,
+        liquidity_mint: Pubkey,
+        liquidity_supply: Pubkey,
+        liquidity_fee_vault: Pubkey,
+        available_amount: u64,
+        borrowed_amount_sf: u128,
+        accumulated_protocol_fees_sf: u128,
+        accumulated_referrer_fees_sf: u128,
+        pending_referrer_fees_sf: u128,
+        collateral_mint: Pubkey,
+        collateral_mint_total_supply: u64,
+        collateral_supply: Pubkey,
+    }
+
+    impl ReserveFixture {
+        /// 1_490 liquidity after fees for 1_000 cToken
+        fn new() -> Self {
+            let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+            ReserveFixture {
+                lending_market: key(1),
+                liquidity_mint: key(2),
+                liquidity_supply: key(3),
+                liquidity_fee_vault: key(4),
+                available_amount: 1_000,
+                // 500.5 borrowed
+                borrowed_amount_sf: (1_001 << FRACTION_BITS) / 2,
+                accumulated_protocol_fees_sf: 6 << FRACTION_BITS,
+                accumulated_referrer_fees_sf: 3 << FRACTION_BITS,
+                pending_referrer_fees_sf: 1 << (FRACTION_BITS - 1),
+                collateral_mint: key(5),
+                collateral_mint_total_supply: 1_000,
+                collateral_supply: key(6),
+            }
+        }
+
+        /// Reserve packed field by field in the declaration order of klend `Reserve`, padded to its length
+        fn pack(&self) -> Vec<u8> {
+            // discriminator, version
+            let mut data = vec![0u8; 16];
+            // last update: slot, stale, price status, placeholder
+            data.extend_from_slice(&[0u8; 16]);
+            data.extend_from_slice(self.lending_market.as_ref());
+            // farm collateral and farm debt
+            data.extend_from_slice(&[0u8; 64]);
+            data.extend_from_slice(self.liquidity_mint.as_ref());
+            data.extend_from_slice(self.liquidity_supply.as_ref());
+            data.extend_from_slice(self.liquidity_fee_vault.as_ref());
+            data.extend_from_slice(&self.available_amount.to_le_bytes());
+            data.extend_from_slice(&self.borrowed_amount_sf.to_le_bytes());
+            // market price, market price updated ts, mint decimals, deposit and borrow limit crossed slots
+            data.extend_from_slice(&[0u8; 16 + 4 * 8]);
+            // cumulative borrow rate, big fraction and padding
+            data.extend_from_slice(&[0u8; 48]);
+            data.extend_from_slice(&self.accumulated_protocol_fees_sf.to_le_bytes());
+            data.extend_from_slice(&self.accumulated_referrer_fees_sf.to_le_bytes());
+            data.extend_from_slice(&self.pending_referrer_fees_sf.to_le_bytes());
+            // absolute referral rate, token program, liquidity padding
+            data.extend_from_slice(&[0u8; 16 + 32 + 51 * 8 + 32 * 16 + 150 * 8]);
+            data.extend_from_slice(self.collateral_mint.as_ref());
+            data.extend_from_slice(&self.collateral_mint_total_supply.to_le_bytes());
+            data.extend_from_slice(self.collateral_supply.as_ref());
+            data.resize(8624, 0);
+            data
+        }
+    }
+
+    #[test]
+    fn decode_reserve() {
+        let fixture = ReserveFixture::new();
+        let reserve = KaminoReserve::decode(&fixture.pack()).unwrap();
+        assert_eq!(
+            (
+                reserve.lending_market,
+                reserve.liquidity_mint,
+                reserve.liquidity_supply,
+                reserve.liquidity_fee_vault,
+                reserve.collateral_mint,
+                reserve.collateral_supply,
+            ),
+            (
+                fixture.lending_market,
+                fixture.liquidity_mint,
+                fixture.liquidity_supply,
+                fixture.liquidity_fee_vault,
+                fixture.collateral_mint,
+                fixture.collateral_supply,
+            )
+        );
+        assert_eq!(
+            (
+                reserve.liquidity_available_amount,
+                reserve.liquidity_borrowed_amount_sf,
+                reserve.accumulated_protocol_fees_sf,
+                reserve.accumulated_referrer_fees_sf,
+                reserve.pending_referrer_fees_sf,
+                reserve.collateral_mint_total_supply,
+            ),
+            (
+                fixture.available_amount,
+                fixture.borrowed_amount_sf,
+                fixture.accumulated_protocol_fees_sf,
+                fixture.accumulated_referrer_fees_sf,
+                fixture.pending_referrer_fees_sf,
+                fixture.collateral_mint_total_supply,
+            )
+        );
+        assert!(KaminoReserve::decode(&fixture.pack()[..2600]).is_err());
+    }
+
+    #[test]
+    fn exchange_rate_rounds_down() {
+        let reserve = KaminoReserve::decode(&ReserveFixture::new().pack()).unwrap();
+        // 1_000 + 500.5 - 6 - 3 - 0.5
+        assert_eq!(reserve.total_liquidity(), Some(1_491));
+        // 3 * 1.491
+        assert_eq!(reserve.collateral_to_liquidity(3), Some(4));
+        // 4 / 1.491
+        assert_eq!(reserve.liquidity_to_collateral(4), Some(2));
+        for amount in [1u64, 7, 999, 1_000_000] {
+            let collateral = reserve.liquidity_to_collateral(amount).unwrap();
+            assert!(reserve.collateral_to_liquidity(collateral).unwrap() <= amount);
+        }
+
+        let mut empty = ReserveFixture::new();
+        empty.collateral_mint_total_supply = 0;
+        let reserve = KaminoReserve::decode(&empty.pack()).unwrap();
+        assert_eq!(reserve.collateral_to_liquidity(10), Some(10));
+        assert_eq!(reserve.liquidity_to_collateral(10), Some(10));
+    }
+
+    #[test]
+    fn pdas_match_mainnet_accounts() {
+        let program_id = ClusterConfig::mainnet().strategy_programs.kamino;
+        let market = pubkey(MAIN_MARKET);
+        assert_eq!(
+            get_lending_market_authority(&market, &program_id).0,
+            pubkey("9DrvZvyWh1HuAoZxvYWMvkf2XCzryCpGgHqrMjyDWpmo")
+        );
+        let addresses = KaminoReserveAddresses::derive(&market, &pubkey(USDC_MINT), &program_id);
+        assert_eq!(
+            addresses.liquidity_supply.0,
+            pubkey("Bgq7trRgVMeq33yt235zM2onQ4bRDBsY5EWiTetF4qw6")
+        );
+        assert_eq!(
+            addresses.collateral_mint.0,
+            pubkey("B8V6WVjPxW1UGwVDfxH2d2r8SyT4cqn7dQRK6XneVa7D")
+        );
+
+        // vanilla obligation: tag, id, owner, market, then two default seed accounts
+        let owner = Pubkey::new_unique();
+        let (obligation, bump) = get_obligation(&owner, &market, &program_id);
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[
+                    &[0],
+                    &[0],
+                    owner.as_ref(),
+                    market.as_ref(),
+                    &[0u8; 32],
+                    &[0u8; 32],
+                    &[bump]
+                ],
+                &program_id
+            )
+            .unwrap(),
+            obligation
+        );
+    }
+
+    #[test]
+    fn remaining_accounts_order() {
+        let config = ClusterConfig::mainnet();
+        let fixture = ReserveFixture::new();
+        let strategy_state = Strategy {
+            strategy_type: StrategyType::Kamino,
+            ..Default::default()
+        };
+        let accounts = get_remaining_accounts_builder(StrategyType::Kamino)
+            .unwrap()
+            .remaining_accounts(
+                &config,
+                &Pubkey::new_unique(),
+                &strategy_state,
+                &fixture.pack(),
+            )
+            .unwrap();
+        assert_eq!(
+            accounts
+                .iter()
+                .map(|account| (account.pubkey, account.is_writable, account.is_signer))
+                .collect::<Vec<_>>(),
+            vec![
+                (fixture.lending_market, false, false),
+                (
+                    get_lending_market_authority(
+                        &fixture.lending_market,
+                        &config.strategy_programs.kamino
+                    )
+                    .0,
+                    false,
+                    false
+                ),
+                (fixture.liquidity_mint, false, false),
+                (fixture.collateral_mint, true, false),
+                (fixture.liquidity_supply, true, false),
+                (sysvar::instructions::id(), false, false),
+            ]
+        );
+    }
+}
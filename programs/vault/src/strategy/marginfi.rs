@@ -1,12 +1,54 @@
+use crate::strategy::reserve::{read_pubkey, read_u128};
 use crate::ID;
-use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::prelude::*;
+
+/// Max balances of a marginfi account
+pub const MAX_BALANCES: usize = 16;
+/// Offset of the first balance of a marginfi account
+const BALANCES_OFFSET: usize = 72;
+const BALANCE_LEN: usize = 104;
 
 /// return strategy owner
 pub fn get_strategy_owner(strategy: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&["marginfi_strategy".as_ref(), strategy.as_ref()], &ID)
+    get_strategy_owner_with_program_id(strategy, &ID)
+}
+
+/// Strategy owner of a vault program deployed at program_id
+pub fn get_strategy_owner_with_program_id(strategy: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &["marginfi_strategy".as_ref(), strategy.as_ref()],
+        program_id,
+    )
 }
 
 /// get_marginfi_account
 pub fn get_marginfi_account(strategy: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&["marginfi_account".as_ref(), strategy.as_ref()], &ID)
+    get_marginfi_account_with_program_id(strategy, &ID)
+}
+
+/// Marginfi account of a vault program deployed at program_id
+pub fn get_marginfi_account_with_program_id(
+    strategy: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &["marginfi_account".as_ref(), strategy.as_ref()],
+        program_id,
+    )
+}
+
+/// Asset shares (I80F48) of the active balance of bank in a marginfi account, 0 without balance for bank
+pub fn get_asset_shares(marginfi_account_data: &[u8], bank: &Pubkey) -> Result<i128> {
+    for index in 0..MAX_BALANCES {
+        let offset = BALANCES_OFFSET + index * BALANCE_LEN;
+        let active = marginfi_account_data
+            .get(offset)
+            .copied()
+            .unwrap_or_default()
+            != 0;
+        if active && read_pubkey(marginfi_account_data, offset + 1)? == *bank {
+            return Ok(read_u128(marginfi_account_data, offset + 40)? as i128);
+        }
+    }
+    Ok(0)
 }
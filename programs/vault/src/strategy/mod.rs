@@ -1,6 +1,17 @@
+// Strategy helpers return the anchor `Result` of the program so errors are `VaultError`s
+#![allow(clippy::result_large_err)]
+
 pub mod apricot_without_lm;
 pub mod base;
 pub mod cypher;
+pub mod drift;
 pub mod frakt;
+pub mod francium;
+pub mod kamino;
 pub mod mango;
 pub mod marginfi;
+pub mod remaining_accounts;
+pub mod reserve;
+pub mod spl_lending;
+pub mod tulip;
+pub mod valuer;
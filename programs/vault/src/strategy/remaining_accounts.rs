@@ -0,0 +1,468 @@
+use crate::cluster::ClusterConfig;
+use crate::state::Strategy;
+use crate::strategy::base::StrategyType;
+use crate::strategy::drift::DriftAccounts;
+use crate::strategy::francium::FRANCIUM_ACCOUNTS;
+use crate::strategy::kamino::KaminoAccounts;
+use crate::strategy::reserve::{
+    MarginfiBank, SplLendingReserve, PORT_RESERVE_LAYOUT, SOLEND_RESERVE_LAYOUT,
+};
+use crate::strategy::tulip::TULIP_ACCOUNTS;
+use crate::utils::StrategyAddresses;
+use crate::VaultError;
+use anchor_lang::prelude::*;
+
+/// Build the protocol specific accounts passed as `remaining_accounts` to
+/// `withdraw_directly_from_strategy`, `deposit_strategy` and `withdraw_strategy`.
+/// Accounts already in the instruction context (reserve, collateral vault, token vault, strategy program) are not repeated.
+pub trait StrategyRemainingAccounts {
+    /// reserve_data is the raw data of strategy.reserve
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>>;
+}
+
+/// Return remaining accounts builder of the strategy type
+pub fn get_remaining_accounts_builder(
+    strategy_type: StrategyType,
+) -> Result<Box<dyn StrategyRemainingAccounts>> {
+    Ok(match strategy_type {
+        StrategyType::SolendWithoutLM | StrategyType::SolendWithLM => Box::new(SolendAccounts),
+        StrategyType::PortFinanceWithoutLM | StrategyType::PortFinanceWithLM => {
+            Box::new(PortFinanceAccounts)
+        }
+        StrategyType::ApricotWithoutLM => Box::new(ApricotAccounts),
+        StrategyType::Frakt => Box::new(FraktAccounts),
+        StrategyType::Marginfi => Box::new(MarginfiAccounts),
+        StrategyType::Kamino => Box::new(KaminoAccounts),
+        StrategyType::Drift => Box::new(DriftAccounts),
+        StrategyType::Tulip => Box::new(TULIP_ACCOUNTS),
+        StrategyType::Francium => Box::new(FRANCIUM_ACCOUNTS),
+        StrategyType::Mango => return Err(VaultError::MangoIsNotSupportedAnymore.into()),
+        StrategyType::Vault | StrategyType::Unknown(_) => {
+            return Err(VaultError::StrategyIsNotSupported.into())
+        }
+    })
+}
+
+/// Accounts to redeem collateral from a spl token-lending reserve
+pub(crate) fn spl_lending_accounts(
+    reserve: &SplLendingReserve,
+    program_id: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(reserve.collateral_mint, false),
+        AccountMeta::new(reserve.liquidity_supply, false),
+        AccountMeta::new_readonly(reserve.lending_market, false),
+        AccountMeta::new_readonly(reserve.lending_market_authority(program_id), false),
+        AccountMeta::new_readonly(reserve.liquidity_oracle, false),
+    ]
+}
+
+pub struct SolendAccounts;
+
+impl StrategyRemainingAccounts for SolendAccounts {
+    /// collateral mint, liquidity supply, lending market, lending market authority, pyth oracle, switchboard oracle,
+    /// followed by obligation, obligation owner and collateral supply for SolendWithLM
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let reserve = SplLendingReserve::decode(reserve_data, &SOLEND_RESERVE_LAYOUT)?;
+        let mut accounts = spl_lending_accounts(&reserve, &config.strategy_programs.solend);
+        if let Some(switchboard_oracle) = reserve.liquidity_switchboard_oracle {
+            accounts.push(AccountMeta::new_readonly(switchboard_oracle, false));
+        }
+        if strategy_state.strategy_type == StrategyType::SolendWithLM {
+            let addresses = StrategyAddresses::derive_with_program_id(
+                *strategy,
+                strategy_state.strategy_type,
+                &config.vault_program_id,
+            );
+            let (obligation, _) = addresses
+                .solend_obligation
+                .ok_or(VaultError::InvalidAccountsForStrategy)?;
+            let (obligation_owner, _) = addresses
+                .solend_obligation_owner
+                .ok_or(VaultError::InvalidAccountsForStrategy)?;
+            accounts.push(AccountMeta::new(obligation, false));
+            accounts.push(AccountMeta::new_readonly(obligation_owner, false));
+            accounts.push(AccountMeta::new(reserve.collateral_supply, false));
+        }
+        Ok(accounts)
+    }
+}
+
+pub struct PortFinanceAccounts;
+
+impl StrategyRemainingAccounts for PortFinanceAccounts {
+    /// collateral mint, liquidity supply, lending market, lending market authority, oracle
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        _strategy: &Pubkey,
+        _strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let reserve = SplLendingReserve::decode(reserve_data, &PORT_RESERVE_LAYOUT)?;
+        Ok(spl_lending_accounts(
+            &reserve,
+            &config.strategy_programs.port_finance,
+        ))
+    }
+}
+
+pub struct ApricotAccounts;
+
+impl StrategyRemainingAccounts for ApricotAccounts {
+    /// user info signer, apricot user info of the signer
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+        _reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let (user_info_signer, _) = StrategyAddresses::derive_with_program_id(
+            *strategy,
+            strategy_state.strategy_type,
+            &config.vault_program_id,
+        )
+        .apricot_user_info_signer
+        .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        let (user_info, _) = Pubkey::find_program_address(
+            &[user_info_signer.as_ref()],
+            &config.strategy_programs.apricot,
+        );
+        Ok(vec![
+            AccountMeta::new_readonly(user_info_signer, false),
+            AccountMeta::new(user_info, false),
+        ])
+    }
+}
+
+pub struct FraktAccounts;
+
+impl StrategyRemainingAccounts for FraktAccounts {
+    /// strategy owner, frakt deposit of the owner, liquidity owner, system program
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+        _reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let frakt_program_id = config.strategy_programs.frakt;
+        let (strategy_owner, _) = StrategyAddresses::derive_with_program_id(
+            *strategy,
+            strategy_state.strategy_type,
+            &config.vault_program_id,
+        )
+        .strategy_owner
+        .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        let (deposit, _) = Pubkey::find_program_address(
+            &[
+                "deposit".as_ref(),
+                strategy_state.reserve.as_ref(),
+                strategy_owner.as_ref(),
+            ],
+            &frakt_program_id,
+        );
+        let (liquidity_owner, _) = Pubkey::find_program_address(
+            &["nftlendingv2".as_ref(), strategy_state.reserve.as_ref()],
+            &frakt_program_id,
+        );
+        Ok(vec![
+            AccountMeta::new(strategy_owner, false),
+            AccountMeta::new(deposit, false),
+            AccountMeta::new(liquidity_owner, false),
+            AccountMeta::new_readonly(System::id(), false),
+        ])
+    }
+}
+
+pub struct MarginfiAccounts;
+
+impl StrategyRemainingAccounts for MarginfiAccounts {
+    /// marginfi group, marginfi account, strategy owner, bank liquidity vault authority, bank liquidity vault, oracle
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let bank = MarginfiBank::decode(reserve_data)?;
+        let addresses = StrategyAddresses::derive_with_program_id(
+            *strategy,
+            strategy_state.strategy_type,
+            &config.vault_program_id,
+        );
+        let (marginfi_account, _) = addresses
+            .marginfi_account
+            .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        let (strategy_owner, _) = addresses
+            .strategy_owner
+            .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        let (liquidity_vault_authority, _) = Pubkey::find_program_address(
+            &[
+                "liquidity_vault_auth".as_ref(),
+                strategy_state.reserve.as_ref(),
+            ],
+            &config.strategy_programs.marginfi,
+        );
+        Ok(vec![
+            AccountMeta::new_readonly(bank.group, false),
+            AccountMeta::new(marginfi_account, false),
+            AccountMeta::new_readonly(strategy_owner, false),
+            AccountMeta::new_readonly(liquidity_vault_authority, false),
+            AccountMeta::new(bank.liquidity_vault, false),
+            AccountMeta::new_readonly(bank.oracle, false),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::reserve::fixtures::{ReserveFixture, SOLEND_RESERVE_LEN};
+    use crate::strategy::{frakt, marginfi};
+    use crate::utils::{derive_solend_obligation_address, derive_solend_obligation_owner_address};
+
+    fn remaining_accounts(
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Vec<(Pubkey, bool, bool)> {
+        get_remaining_accounts_builder(strategy_state.strategy_type)
+            .unwrap()
+            .remaining_accounts(config, strategy, strategy_state, reserve_data)
+            .unwrap()
+            .iter()
+            .map(|account| (account.pubkey, account.is_writable, account.is_signer))
+            .collect()
+    }
+
+    fn strategy_state(strategy_type: StrategyType) -> Strategy {
+        Strategy {
+            strategy_type,
+            reserve: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    fn lending_market_authority(fixture: &ReserveFixture, program_id: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[fixture.lending_market.as_ref()], program_id).0
+    }
+
+    #[test]
+    fn solend_without_lm_accounts_order() {
+        let config = ClusterConfig::mainnet();
+        let fixture = ReserveFixture::new();
+        let [pyth_oracle, switchboard_oracle] = fixture.liquidity_accounts;
+        let accounts = remaining_accounts(
+            &config,
+            &Pubkey::new_unique(),
+            &strategy_state(StrategyType::SolendWithoutLM),
+            &fixture.pack(SOLEND_RESERVE_LEN),
+        );
+        assert_eq!(
+            accounts,
+            vec![
+                (fixture.collateral_mint, true, false),
+                (fixture.liquidity_supply, true, false),
+                (fixture.lending_market, false, false),
+                (
+                    lending_market_authority(&fixture, &config.strategy_programs.solend),
+                    false,
+                    false
+                ),
+                (pyth_oracle, false, false),
+                (switchboard_oracle, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn solend_with_lm_accounts_end_with_obligation_owner_and_collateral_supply() {
+        let config = ClusterConfig::mainnet();
+        let fixture = ReserveFixture::new();
+        let [pyth_oracle, switchboard_oracle] = fixture.liquidity_accounts;
+        let strategy = Pubkey::new_unique();
+        let accounts = remaining_accounts(
+            &config,
+            &strategy,
+            &strategy_state(StrategyType::SolendWithLM),
+            &fixture.pack(SOLEND_RESERVE_LEN),
+        );
+        assert_eq!(
+            accounts,
+            vec![
+                (fixture.collateral_mint, true, false),
+                (fixture.liquidity_supply, true, false),
+                (fixture.lending_market, false, false),
+                (
+                    lending_market_authority(&fixture, &config.strategy_programs.solend),
+                    false,
+                    false
+                ),
+                (pyth_oracle, false, false),
+                (switchboard_oracle, false, false),
+                (derive_solend_obligation_address(strategy).0, true, false),
+                (
+                    derive_solend_obligation_owner_address(strategy).0,
+                    false,
+                    false
+                ),
+                (fixture.collateral_supply, true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn port_accounts_order() {
+        let config = ClusterConfig::mainnet();
+        let fixture = ReserveFixture::new();
+        let [_fee_receiver, oracle] = fixture.liquidity_accounts;
+        for strategy_type in [
+            StrategyType::PortFinanceWithoutLM,
+            StrategyType::PortFinanceWithLM,
+        ] {
+            let accounts = remaining_accounts(
+                &config,
+                &Pubkey::new_unique(),
+                &strategy_state(strategy_type),
+                &fixture.pack_port(),
+            );
+            assert_eq!(
+                accounts,
+                vec![
+                    (fixture.collateral_mint, true, false),
+                    (fixture.liquidity_supply, true, false),
+                    (fixture.lending_market, false, false),
+                    (
+                        lending_market_authority(&fixture, &config.strategy_programs.port_finance),
+                        false,
+                        false
+                    ),
+                    (oracle, false, false),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn apricot_accounts_order() {
+        let config = ClusterConfig::mainnet();
+        let strategy = Pubkey::new_unique();
+        let accounts = remaining_accounts(
+            &config,
+            &strategy,
+            &strategy_state(StrategyType::ApricotWithoutLM),
+            &[],
+        );
+        let (user_info_signer, _) = Pubkey::find_program_address(
+            &[b"apricot_user_info_signer".as_ref(), strategy.as_ref()],
+            &config.vault_program_id,
+        );
+        let (user_info, _) = Pubkey::find_program_address(
+            &[user_info_signer.as_ref()],
+            &config.strategy_programs.apricot,
+        );
+        assert_eq!(
+            accounts,
+            vec![(user_info_signer, false, false), (user_info, true, false)]
+        );
+    }
+
+    #[test]
+    fn frakt_accounts_order() {
+        let config = ClusterConfig::mainnet();
+        let strategy = Pubkey::new_unique();
+        let state = strategy_state(StrategyType::Frakt);
+        let accounts = remaining_accounts(&config, &strategy, &state, &[]);
+        let (strategy_owner, _) = frakt::get_strategy_owner(&strategy);
+        let (deposit, _) = Pubkey::find_program_address(
+            &[
+                b"deposit".as_ref(),
+                state.reserve.as_ref(),
+                strategy_owner.as_ref(),
+            ],
+            &config.strategy_programs.frakt,
+        );
+        let (liquidity_owner, _) = Pubkey::find_program_address(
+            &[b"nftlendingv2".as_ref(), state.reserve.as_ref()],
+            &config.strategy_programs.frakt,
+        );
+        assert_eq!(
+            accounts,
+            vec![
+                (strategy_owner, true, false),
+                (deposit, true, false),
+                (liquidity_owner, true, false),
+                (System::id(), false, false),
+            ]
+        );
+    }
+
+    /// Bank with the fields used by the vault at their offsets in the marginfi `Bank`
+    fn bank_data(group: Pubkey, liquidity_vault: Pubkey, oracle: Pubkey) -> Vec<u8> {
+        let mut data = vec![0u8; 1864];
+        data[41..73].copy_from_slice(group.as_ref());
+        data[112..144].copy_from_slice(liquidity_vault.as_ref());
+        data[610..642].copy_from_slice(oracle.as_ref());
+        data
+    }
+
+    #[test]
+    fn marginfi_accounts_order() {
+        let config = ClusterConfig::mainnet();
+        let strategy = Pubkey::new_unique();
+        let state = strategy_state(StrategyType::Marginfi);
+        let (group, liquidity_vault, oracle) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let accounts = remaining_accounts(
+            &config,
+            &strategy,
+            &state,
+            &bank_data(group, liquidity_vault, oracle),
+        );
+        let (liquidity_vault_authority, _) = Pubkey::find_program_address(
+            &[b"liquidity_vault_auth".as_ref(), state.reserve.as_ref()],
+            &config.strategy_programs.marginfi,
+        );
+        assert_eq!(
+            accounts,
+            vec![
+                (group, false, false),
+                (marginfi::get_marginfi_account(&strategy).0, true, false),
+                (marginfi::get_strategy_owner(&strategy).0, false, false),
+                (liquidity_vault_authority, false, false),
+                (liquidity_vault, true, false),
+                (oracle, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_strategies_have_no_builder() {
+        for strategy_type in [
+            StrategyType::Mango,
+            StrategyType::Vault,
+            StrategyType::Unknown(42),
+        ] {
+            assert!(get_remaining_accounts_builder(strategy_type).is_err());
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use crate::VaultError;
+use anchor_lang::prelude::*;
+use std::convert::{TryFrom, TryInto};
+
+/// Scale of the `_wads` fields of spl token-lending
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// Fractional bits of the I80F48 fields of marginfi
+pub const I80F48_FRACTION_BITS: u32 = 48;
+
+pub(crate) fn read_pubkey(data: &[u8], offset: usize) -> Result<Pubkey> {
+    let bytes: [u8; 32] = data
+        .get(offset..offset + 32)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(VaultError::InvalidAccountsForStrategy)?;
+    Ok(Pubkey::new_from_array(bytes))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    let bytes: [u8; 8] = data
+        .get(offset..offset + 8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(VaultError::InvalidAccountsForStrategy)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u128(data: &[u8], offset: usize) -> Result<u128> {
+    let bytes: [u8; 16] = data
+        .get(offset..offset + 16)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(VaultError::InvalidAccountsForStrategy)?;
+    Ok(u128::from_le_bytes(bytes))
+}
+
+/// Byte offsets of a reserve forked from spl token-lending
+#[derive(Clone, Copy, Debug)]
+pub struct SplLendingReserveLayout {
+    pub lending_market: usize,
+    pub liquidity_mint: usize,
+    pub liquidity_supply: usize,
+    /// pyth oracle for solend, oracle for port and the forks keeping the spl token-lending layout
+    pub liquidity_oracle: usize,
+    /// switchboard oracle for solend
+    pub liquidity_switchboard_oracle: Option<usize>,
+    pub liquidity_available_amount: usize,
+    pub liquidity_borrowed_amount_wads: usize,
+    pub collateral_mint: usize,
+    pub collateral_mint_total_supply: usize,
+    pub collateral_supply: usize,
+}
+
+/// Reserve of spl token-lending, liquidity has a fee receiver then the oracle.
+/// `tulip::RESERVE_LAYOUT` and `francium::RESERVE_LAYOUT` are defined from it
+pub const SPL_TOKEN_LENDING_RESERVE_LAYOUT: SplLendingReserveLayout = SplLendingReserveLayout {
+    lending_market: 10,
+    liquidity_mint: 42,
+    liquidity_supply: 75,
+    liquidity_oracle: 139,
+    liquidity_switchboard_oracle: None,
+    liquidity_available_amount: 171,
+    liquidity_borrowed_amount_wads: 179,
+    collateral_mint: 227,
+    collateral_mint_total_supply: 259,
+    collateral_supply: 267,
+};
+
+/// Solend reserve, liquidity has the pyth and switchboard oracles where spl token-lending has the fee receiver and oracle
+pub const SOLEND_RESERVE_LAYOUT: SplLendingReserveLayout = SplLendingReserveLayout {
+    lending_market: 10,
+    liquidity_mint: 42,
+    liquidity_supply: 75,
+    liquidity_oracle: 107,
+    liquidity_switchboard_oracle: Some(139),
+    liquidity_available_amount: 171,
+    liquidity_borrowed_amount_wads: 179,
+    collateral_mint: 227,
+    collateral_mint_total_supply: 259,
+    collateral_supply: 267,
+};
+
+/// Port reserve, liquidity has a fee receiver and a COption oracle
+pub const PORT_RESERVE_LAYOUT: SplLendingReserveLayout = SplLendingReserveLayout {
+    lending_market: 10,
+    liquidity_mint: 42,
+    liquidity_supply: 75,
+    liquidity_oracle: 143,
+    liquidity_switchboard_oracle: None,
+    liquidity_available_amount: 175,
+    liquidity_borrowed_amount_wads: 183,
+    collateral_mint: 231,
+    collateral_mint_total_supply: 263,
+    collateral_supply: 271,
+};
+
+/// Fields of a spl token-lending reserve used by the vault
+#[derive(Clone, Copy, Debug)]
+pub struct SplLendingReserve {
+    pub lending_market: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_supply: Pubkey,
+    pub liquidity_oracle: Pubkey,
+    pub liquidity_switchboard_oracle: Option<Pubkey>,
+    pub liquidity_available_amount: u64,
+    pub liquidity_borrowed_amount_wads: u128,
+    pub collateral_mint: Pubkey,
+    pub collateral_mint_total_supply: u64,
+    pub collateral_supply: Pubkey,
+}
+
+impl SplLendingReserve {
+    pub fn decode(data: &[u8], layout: &SplLendingReserveLayout) -> Result<Self> {
+        Ok(SplLendingReserve {
+            lending_market: read_pubkey(data, layout.lending_market)?,
+            liquidity_mint: read_pubkey(data, layout.liquidity_mint)?,
+            liquidity_supply: read_pubkey(data, layout.liquidity_supply)?,
+            liquidity_oracle: read_pubkey(data, layout.liquidity_oracle)?,
+            liquidity_switchboard_oracle: layout
+                .liquidity_switchboard_oracle
+                .map(|offset| read_pubkey(data, offset))
+                .transpose()?,
+            liquidity_available_amount: read_u64(data, layout.liquidity_available_amount)?,
+            liquidity_borrowed_amount_wads: read_u128(data, layout.liquidity_borrowed_amount_wads)?,
+            collateral_mint: read_pubkey(data, layout.collateral_mint)?,
+            collateral_mint_total_supply: read_u64(data, layout.collateral_mint_total_supply)?,
+            collateral_supply: read_pubkey(data, layout.collateral_supply)?,
+        })
+    }
+
+    /// Return lending market authority PDA
+    pub fn lending_market_authority(&self, program_id: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[self.lending_market.as_ref()], program_id).0
+    }
+
+    /// Available and borrowed liquidity, rounded down, as of the last refresh of the reserve
+    pub fn total_liquidity(&self) -> Option<u64> {
+        let borrowed_amount = self.liquidity_borrowed_amount_wads / WAD;
+        u64::try_from(borrowed_amount)
+            .ok()?
+            .checked_add(self.liquidity_available_amount)
+    }
+
+    /// Liquidity redeemed for collateral_amount of cToken, 1:1 before the first deposit
+    pub fn collateral_to_liquidity(&self, collateral_amount: u64) -> Option<u64> {
+        let total_liquidity = self.total_liquidity()?;
+        if self.collateral_mint_total_supply == 0 || total_liquidity == 0 {
+            return Some(collateral_amount);
+        }
+        let liquidity = u128::from(collateral_amount)
+            .checked_mul(u128::from(total_liquidity))?
+            .checked_div(u128::from(self.collateral_mint_total_supply))?;
+        u64::try_from(liquidity).ok()
+    }
+}
+
+/// Byte offsets of an obligation of a spl token-lending fork
+#[derive(Clone, Copy, Debug)]
+pub struct SplLendingObligationLayout {
+    pub deposits_len: usize,
+    /// first deposited collateral, each starts with the deposit reserve followed by the deposited cToken amount
+    pub deposits: usize,
+    /// length of a deposited collateral
+    pub collateral_len: usize,
+}
+
+/// Solend obligation, the deposits follow 64 bytes of padding after the obligation values
+/// and each collateral ends with its market value and 32 bytes of padding
+pub const SOLEND_OBLIGATION_LAYOUT: SplLendingObligationLayout = SplLendingObligationLayout {
+    deposits_len: 202,
+    deposits: 204,
+    collateral_len: 88,
+};
+
+/// Port obligation, kept the spl token-lending layout: the deposits follow the obligation values
+/// and each collateral ends with its market value, without padding
+pub const PORT_OBLIGATION_LAYOUT: SplLendingObligationLayout = SplLendingObligationLayout {
+    deposits_len: 138,
+    deposits: 140,
+    collateral_len: 56,
+};
+
+/// cToken amount of reserve deposited in the obligation, 0 when the obligation has no deposit of it
+pub fn get_obligation_deposit(
+    data: &[u8],
+    layout: &SplLendingObligationLayout,
+    reserve: &Pubkey,
+) -> Result<u64> {
+    let deposits_len = *data
+        .get(layout.deposits_len)
+        .ok_or(VaultError::InvalidAccountsForStrategy)?;
+    for index in 0..usize::from(deposits_len) {
+        let offset = layout.deposits + index * layout.collateral_len;
+        if read_pubkey(data, offset)? == *reserve {
+            return read_u64(data, offset + 32);
+        }
+    }
+    Ok(0)
+}
+
+/// Fields of a marginfi bank used by the vault
+#[derive(Clone, Copy, Debug)]
+pub struct MarginfiBank {
+    pub mint: Pubkey,
+    pub group: Pubkey,
+    /// I80F48
+    pub asset_share_value: i128,
+    /// I80F48
+    pub liability_share_value: i128,
+    pub liquidity_vault: Pubkey,
+    /// I80F48
+    pub total_liability_shares: i128,
+    /// I80F48
+    pub total_asset_shares: i128,
+    pub oracle: Pubkey,
+}
+
+impl MarginfiBank {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        Ok(MarginfiBank {
+            mint: read_pubkey(data, 8)?,
+            group: read_pubkey(data, 41)?,
+            asset_share_value: read_u128(data, 80)? as i128,
+            liability_share_value: read_u128(data, 96)? as i128,
+            liquidity_vault: read_pubkey(data, 112)?,
+            total_liability_shares: read_u128(data, 256)? as i128,
+            total_asset_shares: read_u128(data, 272)? as i128,
+            // first of config.oracle_keys
+            oracle: read_pubkey(data, 610)?,
+        })
+    }
+
+    /// Liquidity of asset_shares (I80F48), rounded down
+    pub fn asset_shares_to_liquidity(&self, asset_shares: i128) -> Option<u64> {
+        shares_to_liquidity(asset_shares, self.asset_share_value)
+    }
+
+    /// Deposits not borrowed, which the liquidity vault can pay out now
+    pub fn available_liquidity(&self) -> Option<u64> {
+        let total_assets = shares_to_liquidity(self.total_asset_shares, self.asset_share_value)?;
+        let total_liabilities =
+            shares_to_liquidity(self.total_liability_shares, self.liability_share_value)?;
+        Some(total_assets.saturating_sub(total_liabilities))
+    }
+}
+
+/// Liquidity of shares at share_value, both I80F48, rounded down.
+/// Integer and fractional shares are multiplied separately so the product does not overflow
+fn shares_to_liquidity(shares: i128, share_value: i128) -> Option<u64> {
+    let integer_shares = shares >> I80F48_FRACTION_BITS;
+    let fractional_shares = shares & ((1 << I80F48_FRACTION_BITS) - 1);
+    let liquidity = integer_shares
+        .checked_mul(share_value)?
+        .checked_add(fractional_shares.checked_mul(share_value)? >> I80F48_FRACTION_BITS)?
+        >> I80F48_FRACTION_BITS;
+    u64::try_from(liquidity).ok()
+}
+
+/// Reserve accounts packed field by field in the declaration order of the lending programs,
+/// independently of the layout offsets
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+
+    /// Length of a spl token-lending reserve account
+    pub const SPL_TOKEN_LENDING_RESERVE_LEN: usize = 571;
+    /// Length of a solend reserve account
+    pub const SOLEND_RESERVE_LEN: usize = 619;
+    /// Length of a port reserve account, spl token-lending with the COption tag of the oracle
+    pub const PORT_RESERVE_LEN: usize = 575;
+
+    /// Fields of a spl token-lending reserve up to the collateral supply.
+    /// liquidity_accounts follow the liquidity supply: fee receiver and oracle for spl token-lending,
+    /// pyth and switchboard oracles for solend
+    pub struct ReserveFixture {
+        pub lending_market: Pubkey,
+        pub liquidity_mint: Pubkey,
+        pub liquidity_supply: Pubkey,
+        pub liquidity_accounts: [Pubkey; 2],
+        pub available_amount: u64,
+        pub borrowed_amount_wads: u128,
+        pub collateral_mint: Pubkey,
+        pub collateral_mint_total_supply: u64,
+        pub collateral_supply: Pubkey,
+    }
+
+    impl ReserveFixture {
+        /// Reserve of 1_500 liquidity for 1_000 cToken, 1_000 of it borrowed, with distinct accounts
+        pub fn new() -> Self {
+            let key = |seed: u8| Pubkey::new_from_array([seed; 32]);
+            ReserveFixture {
+                lending_market: key(1),
+                liquidity_mint: key(2),
+                liquidity_supply: key(3),
+                liquidity_accounts: [key(4), key(5)],
+                available_amount: 500,
+                borrowed_amount_wads: 1_000 * WAD,
+                collateral_mint: key(6),
+                collateral_mint_total_supply: 1_000,
+                collateral_supply: key(7),
+            }
+        }
+
+        /// Account data of len bytes, the fields after the collateral supply are zero
+        pub fn pack(&self, len: usize) -> Vec<u8> {
+            self.pack_with_oracle_tag(len, None)
+        }
+
+        /// Port reserve account data, the oracle following the fee receiver is a COption
+        pub fn pack_port(&self) -> Vec<u8> {
+            self.pack_with_oracle_tag(PORT_RESERVE_LEN, Some([1, 0, 0, 0]))
+        }
+
+        fn pack_with_oracle_tag(&self, len: usize, oracle_tag: Option<[u8; 4]>) -> Vec<u8> {
+            let mut data = vec![1u8];
+            // last update slot and stale flag
+            data.extend_from_slice(&42u64.to_le_bytes());
+            data.push(0);
+            data.extend_from_slice(self.lending_market.as_ref());
+            data.extend_from_slice(self.liquidity_mint.as_ref());
+            // mint decimals
+            data.push(6);
+            data.extend_from_slice(self.liquidity_supply.as_ref());
+            data.extend_from_slice(self.liquidity_accounts[0].as_ref());
+            if let Some(oracle_tag) = oracle_tag {
+                data.extend_from_slice(&oracle_tag);
+            }
+            data.extend_from_slice(self.liquidity_accounts[1].as_ref());
+            data.extend_from_slice(&self.available_amount.to_le_bytes());
+            data.extend_from_slice(&self.borrowed_amount_wads.to_le_bytes());
+            // cumulative borrow rate and market price
+            data.extend_from_slice(&WAD.to_le_bytes());
+            data.extend_from_slice(&WAD.to_le_bytes());
+            data.extend_from_slice(self.collateral_mint.as_ref());
+            data.extend_from_slice(&self.collateral_mint_total_supply.to_le_bytes());
+            data.extend_from_slice(self.collateral_supply.as_ref());
+            data.resize(len, 0);
+            data
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::cluster::ClusterConfig;
+use crate::state::Strategy;
+use crate::strategy::remaining_accounts::{spl_lending_accounts, StrategyRemainingAccounts};
+use crate::strategy::reserve::{SplLendingReserve, SplLendingReserveLayout};
+use crate::utils::derive_collateral_vault_address;
+use crate::VaultError;
+use anchor_lang::prelude::*;
+
+/// return collateral vault of the strategy, the only vault owned PDA of a strategy lending to a spl token-lending fork
+pub fn get_collateral_vault(strategy: &Pubkey) -> (Pubkey, u8) {
+    derive_collateral_vault_address(*strategy)
+}
+
+/// Strategies lending to a fork of spl token-lending without extra accounts, the reserve decoded with the layout of the fork
+pub struct SplLendingForkAccounts {
+    pub layout: SplLendingReserveLayout,
+}
+
+impl StrategyRemainingAccounts for SplLendingForkAccounts {
+    /// collateral mint, liquidity supply, lending market, lending market authority, oracle
+    fn remaining_accounts(
+        &self,
+        config: &ClusterConfig,
+        _strategy: &Pubkey,
+        strategy_state: &Strategy,
+        reserve_data: &[u8],
+    ) -> Result<Vec<AccountMeta>> {
+        let program_id = config
+            .strategy_programs
+            .get(strategy_state.strategy_type)
+            .ok_or(VaultError::StrategyIsNotSupported)?;
+        Ok(spl_lending_accounts(
+            &SplLendingReserve::decode(reserve_data, &self.layout)?,
+            &program_id,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::base::StrategyType;
+    use crate::strategy::remaining_accounts::get_remaining_accounts_builder;
+    use crate::strategy::reserve::fixtures::{
+        ReserveFixture, SOLEND_RESERVE_LEN, SPL_TOKEN_LENDING_RESERVE_LEN,
+    };
+    use crate::strategy::reserve::SOLEND_RESERVE_LAYOUT;
+    use crate::utils::StrategyAddresses;
+
+    #[test]
+    fn fork_accounts_pass_oracle_not_fee_receiver() {
+        let config = ClusterConfig::mainnet();
+        let fixture = ReserveFixture::new();
+        let reserve_data = fixture.pack(SPL_TOKEN_LENDING_RESERVE_LEN);
+        let [_fee_receiver, oracle] = fixture.liquidity_accounts;
+
+        for (strategy_type, program_id) in [
+            (StrategyType::Tulip, config.strategy_programs.tulip),
+            (StrategyType::Francium, config.strategy_programs.francium),
+        ] {
+            let strategy_state = Strategy {
+                strategy_type,
+                ..Default::default()
+            };
+            let accounts = get_remaining_accounts_builder(strategy_type)
+                .unwrap()
+                .remaining_accounts(
+                    &config,
+                    &Pubkey::new_unique(),
+                    &strategy_state,
+                    &reserve_data,
+                )
+                .unwrap();
+            let lending_market_authority =
+                Pubkey::find_program_address(&[fixture.lending_market.as_ref()], &program_id).0;
+            assert_eq!(
+                accounts
+                    .iter()
+                    .map(|account| account.pubkey)
+                    .collect::<Vec<_>>(),
+                vec![
+                    fixture.collateral_mint,
+                    fixture.liquidity_supply,
+                    fixture.lending_market,
+                    lending_market_authority,
+                    oracle,
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn solend_layout_reads_both_oracles() {
+        let fixture = ReserveFixture::new();
+        let reserve =
+            SplLendingReserve::decode(&fixture.pack(SOLEND_RESERVE_LEN), &SOLEND_RESERVE_LAYOUT)
+                .unwrap();
+        let [pyth_oracle, switchboard_oracle] = fixture.liquidity_accounts;
+        assert_eq!(reserve.liquidity_oracle, pyth_oracle);
+        assert_eq!(
+            reserve.liquidity_switchboard_oracle,
+            Some(switchboard_oracle)
+        );
+        assert_eq!(reserve.collateral_to_liquidity(100), Some(150));
+    }
+
+    #[test]
+    fn collateral_vault_matches_strategy_addresses() {
+        let strategy = Pubkey::new_unique();
+        for strategy_type in [StrategyType::Tulip, StrategyType::Francium] {
+            assert_eq!(
+                get_collateral_vault(&strategy),
+                StrategyAddresses::derive(strategy, strategy_type).collateral_vault
+            );
+        }
+    }
+}
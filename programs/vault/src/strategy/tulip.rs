@@ -0,0 +1,42 @@
+use crate::strategy::reserve::{
+    SplLendingReserve, SplLendingReserveLayout, SPL_TOKEN_LENDING_RESERVE_LAYOUT,
+};
+use crate::strategy::spl_lending::SplLendingForkAccounts;
+use anchor_lang::prelude::*;
+
+pub use crate::strategy::spl_lending::get_collateral_vault;
+
+/// Tulip lending reserve, the spl token-lending `Reserve` unchanged:
+/// version, last update, lending market, then liquidity mint, decimals, supply, fee receiver and oracle
+pub const RESERVE_LAYOUT: SplLendingReserveLayout = SPL_TOKEN_LENDING_RESERVE_LAYOUT;
+
+pub const TULIP_ACCOUNTS: SplLendingForkAccounts = SplLendingForkAccounts {
+    layout: RESERVE_LAYOUT,
+};
+
+pub fn decode_reserve(data: &[u8]) -> Result<SplLendingReserve> {
+    SplLendingReserve::decode(data, &RESERVE_LAYOUT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::reserve::fixtures::{ReserveFixture, SPL_TOKEN_LENDING_RESERVE_LEN};
+
+    #[test]
+    fn reserve_reads_oracle_after_fee_receiver() {
+        let fixture = ReserveFixture::new();
+        let reserve = decode_reserve(&fixture.pack(SPL_TOKEN_LENDING_RESERVE_LEN)).unwrap();
+        let [_fee_receiver, oracle] = fixture.liquidity_accounts;
+
+        assert_eq!(reserve.lending_market, fixture.lending_market);
+        assert_eq!(reserve.liquidity_mint, fixture.liquidity_mint);
+        assert_eq!(reserve.liquidity_supply, fixture.liquidity_supply);
+        assert_eq!(reserve.liquidity_oracle, oracle);
+        assert_eq!(reserve.liquidity_switchboard_oracle, None);
+        assert_eq!(reserve.liquidity_available_amount, 500);
+        assert_eq!(reserve.collateral_mint, fixture.collateral_mint);
+        assert_eq!(reserve.collateral_supply, fixture.collateral_supply);
+        assert_eq!(reserve.collateral_to_liquidity(100), Some(150));
+    }
+}
@@ -0,0 +1,545 @@
+use crate::cluster::ClusterConfig;
+use crate::state::Strategy;
+use crate::strategy::base::StrategyType;
+use crate::strategy::kamino::KaminoReserve;
+use crate::strategy::marginfi::get_asset_shares;
+use crate::strategy::reserve::{
+    get_obligation_deposit, read_u64, MarginfiBank, SplLendingObligationLayout, SplLendingReserve,
+    SplLendingReserveLayout, PORT_OBLIGATION_LAYOUT, PORT_RESERVE_LAYOUT, SOLEND_OBLIGATION_LAYOUT,
+    SOLEND_RESERVE_LAYOUT,
+};
+use crate::strategy::{drift, francium, tulip};
+use crate::utils::StrategyAddresses;
+use crate::VaultError;
+use anchor_lang::prelude::*;
+
+/// Offset of the amount of a spl token account
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+/// Value the position of a strategy from the live state of its reserve.
+/// `Strategy::current_liquidity` is only updated on rebalance, the difference with the value is the unrealized yield
+pub trait StrategyValuer {
+    /// Account holding the position of the strategy, None when the value does not depend on it
+    fn position_account(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+    ) -> Result<Option<Pubkey>>;
+
+    /// Underlying token amount of the position, rounded down, None when the position can not be valued from live state.
+    /// position_data is the raw data of `position_account`, reserve_data the raw data of strategy.reserve
+    fn value(
+        &self,
+        strategy_state: &Strategy,
+        position_data: &[u8],
+        reserve_data: &[u8],
+    ) -> Result<Option<u64>>;
+
+    /// Liquidity the reserve can pay out now, a withdrawal above it fails.
+    /// None when the reserve does not tell it, the strategy is then limited by its own liquidity only
+    fn available_liquidity(&self, reserve_data: &[u8]) -> Result<Option<u64>>;
+}
+
+/// Return valuer of the strategy type
+pub fn get_strategy_valuer(strategy_type: StrategyType) -> Box<dyn StrategyValuer> {
+    match strategy_type {
+        StrategyType::SolendWithoutLM => Box::new(SplLendingValuer {
+            layout: SOLEND_RESERVE_LAYOUT,
+        }),
+        StrategyType::SolendWithLM => Box::new(SplLendingObligationValuer {
+            layout: SOLEND_RESERVE_LAYOUT,
+            obligation_layout: SOLEND_OBLIGATION_LAYOUT,
+        }),
+        StrategyType::Tulip => Box::new(SplLendingValuer {
+            layout: tulip::RESERVE_LAYOUT,
+        }),
+        StrategyType::Francium => Box::new(SplLendingValuer {
+            layout: francium::RESERVE_LAYOUT,
+        }),
+        StrategyType::PortFinanceWithoutLM => Box::new(SplLendingValuer {
+            layout: PORT_RESERVE_LAYOUT,
+        }),
+        StrategyType::PortFinanceWithLM => Box::new(SplLendingObligationValuer {
+            layout: PORT_RESERVE_LAYOUT,
+            obligation_layout: PORT_OBLIGATION_LAYOUT,
+        }),
+        StrategyType::Kamino => Box::new(KaminoValuer),
+        StrategyType::Marginfi => Box::new(MarginfiValuer),
+        StrategyType::Drift => Box::new(DriftValuer),
+        StrategyType::ApricotWithoutLM
+        | StrategyType::Frakt
+        | StrategyType::Mango
+        | StrategyType::Vault
+        | StrategyType::Unknown(_) => Box::new(BookedOnlyValuer),
+    }
+}
+
+/// cToken of a spl token-lending reserve held in the collateral vault
+pub struct SplLendingValuer {
+    pub layout: SplLendingReserveLayout,
+}
+
+impl StrategyValuer for SplLendingValuer {
+    /// collateral vault
+    fn position_account(
+        &self,
+        _config: &ClusterConfig,
+        _strategy: &Pubkey,
+        strategy_state: &Strategy,
+    ) -> Result<Option<Pubkey>> {
+        Ok(Some(strategy_state.collateral_vault))
+    }
+
+    /// collateral vault balance at the cToken exchange rate of the reserve
+    fn value(
+        &self,
+        _strategy_state: &Strategy,
+        position_data: &[u8],
+        reserve_data: &[u8],
+    ) -> Result<Option<u64>> {
+        let collateral_amount = read_u64(position_data, TOKEN_ACCOUNT_AMOUNT_OFFSET)?;
+        Ok(Some(
+            SplLendingReserve::decode(reserve_data, &self.layout)?
+                .collateral_to_liquidity(collateral_amount)
+                .ok_or(VaultError::MathOverflow)?,
+        ))
+    }
+
+    /// liquidity not borrowed
+    fn available_liquidity(&self, reserve_data: &[u8]) -> Result<Option<u64>> {
+        Ok(Some(
+            SplLendingReserve::decode(reserve_data, &self.layout)?.liquidity_available_amount,
+        ))
+    }
+}
+
+/// LM variants of spl token-lending strategies, whose collateral is deposited in an obligation of the strategy
+pub struct SplLendingObligationValuer {
+    pub layout: SplLendingReserveLayout,
+    pub obligation_layout: SplLendingObligationLayout,
+}
+
+impl StrategyValuer for SplLendingObligationValuer {
+    /// obligation of the strategy
+    fn position_account(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+    ) -> Result<Option<Pubkey>> {
+        let addresses = StrategyAddresses::derive_with_program_id(
+            *strategy,
+            strategy_state.strategy_type,
+            &config.vault_program_id,
+        );
+        let (obligation, _) = addresses
+            .solend_obligation
+            .or(addresses.port_obligation)
+            .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        Ok(Some(obligation))
+    }
+
+    /// cToken of the reserve deposited in the obligation at the cToken exchange rate of the reserve
+    fn value(
+        &self,
+        strategy_state: &Strategy,
+        position_data: &[u8],
+        reserve_data: &[u8],
+    ) -> Result<Option<u64>> {
+        let collateral_amount = get_obligation_deposit(
+            position_data,
+            &self.obligation_layout,
+            &strategy_state.reserve,
+        )?;
+        Ok(Some(
+            SplLendingReserve::decode(reserve_data, &self.layout)?
+                .collateral_to_liquidity(collateral_amount)
+                .ok_or(VaultError::MathOverflow)?,
+        ))
+    }
+
+    /// liquidity not borrowed
+    fn available_liquidity(&self, reserve_data: &[u8]) -> Result<Option<u64>> {
+        Ok(Some(
+            SplLendingReserve::decode(reserve_data, &self.layout)?.liquidity_available_amount,
+        ))
+    }
+}
+
+pub struct KaminoValuer;
+
+impl StrategyValuer for KaminoValuer {
+    /// collateral vault
+    fn position_account(
+        &self,
+        _config: &ClusterConfig,
+        _strategy: &Pubkey,
+        strategy_state: &Strategy,
+    ) -> Result<Option<Pubkey>> {
+        Ok(Some(strategy_state.collateral_vault))
+    }
+
+    /// collateral vault balance at the cToken exchange rate of the reserve
+    fn value(
+        &self,
+        _strategy_state: &Strategy,
+        position_data: &[u8],
+        reserve_data: &[u8],
+    ) -> Result<Option<u64>> {
+        let collateral_amount = read_u64(position_data, TOKEN_ACCOUNT_AMOUNT_OFFSET)?;
+        Ok(Some(
+            KaminoReserve::decode(reserve_data)?
+                .collateral_to_liquidity(collateral_amount)
+                .ok_or(VaultError::MathOverflow)?,
+        ))
+    }
+
+    /// liquidity not borrowed
+    fn available_liquidity(&self, reserve_data: &[u8]) -> Result<Option<u64>> {
+        Ok(Some(
+            KaminoReserve::decode(reserve_data)?.liquidity_available_amount,
+        ))
+    }
+}
+
+pub struct MarginfiValuer;
+
+impl StrategyValuer for MarginfiValuer {
+    /// marginfi account of the strategy
+    fn position_account(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        strategy_state: &Strategy,
+    ) -> Result<Option<Pubkey>> {
+        let (marginfi_account, _) = StrategyAddresses::derive_with_program_id(
+            *strategy,
+            strategy_state.strategy_type,
+            &config.vault_program_id,
+        )
+        .marginfi_account
+        .ok_or(VaultError::InvalidAccountsForStrategy)?;
+        Ok(Some(marginfi_account))
+    }
+
+    /// asset shares of the bank in the marginfi account at the share value of the bank
+    fn value(
+        &self,
+        strategy_state: &Strategy,
+        position_data: &[u8],
+        reserve_data: &[u8],
+    ) -> Result<Option<u64>> {
+        let asset_shares = get_asset_shares(position_data, &strategy_state.reserve)?;
+        Ok(Some(
+            MarginfiBank::decode(reserve_data)?
+                .asset_shares_to_liquidity(asset_shares)
+                .ok_or(VaultError::MathOverflow)?,
+        ))
+    }
+
+    /// deposits of the bank not borrowed
+    fn available_liquidity(&self, reserve_data: &[u8]) -> Result<Option<u64>> {
+        Ok(Some(
+            MarginfiBank::decode(reserve_data)?
+                .available_liquidity()
+                .ok_or(VaultError::MathOverflow)?,
+        ))
+    }
+}
+
+pub struct DriftValuer;
+
+impl StrategyValuer for DriftValuer {
+    /// drift user of the strategy owner
+    fn position_account(
+        &self,
+        config: &ClusterConfig,
+        strategy: &Pubkey,
+        _strategy_state: &Strategy,
+    ) -> Result<Option<Pubkey>> {
+        let (strategy_owner, _) =
+            drift::get_strategy_owner_with_program_id(strategy, &config.vault_program_id);
+        let (user, _) = drift::get_user(
+            &strategy_owner,
+            drift::SUB_ACCOUNT_ID,
+            &config.strategy_programs.drift,
+        );
+        Ok(Some(user))
+    }
+
+    /// spot deposit of the drift user at the cumulative deposit interest of the spot market
+    fn value(
+        &self,
+        _strategy_state: &Strategy,
+        position_data: &[u8],
+        reserve_data: &[u8],
+    ) -> Result<Option<u64>> {
+        let spot_market = drift::DriftSpotMarket::decode(reserve_data)?;
+        let scaled_balance = drift::get_spot_deposit(position_data, spot_market.market_index)?;
+        Ok(Some(
+            spot_market
+                .deposit_token_amount(scaled_balance)
+                .ok_or(VaultError::MathOverflow)?,
+        ))
+    }
+
+    /// deposits of the spot market not borrowed
+    fn available_liquidity(&self, reserve_data: &[u8]) -> Result<Option<u64>> {
+        Ok(Some(
+            drift::DriftSpotMarket::decode(reserve_data)?
+                .available_liquidity()
+                .ok_or(VaultError::MathOverflow)?,
+        ))
+    }
+}
+
+/// Strategies without a position decoded by this crate (apricot, frakt) or which can not be withdrawn from anymore.
+/// Only `Strategy::current_liquidity` is known, their value is unknown
+pub struct BookedOnlyValuer;
+
+impl StrategyValuer for BookedOnlyValuer {
+    fn position_account(
+        &self,
+        _config: &ClusterConfig,
+        _strategy: &Pubkey,
+        _strategy_state: &Strategy,
+    ) -> Result<Option<Pubkey>> {
+        Ok(None)
+    }
+
+    fn value(
+        &self,
+        _strategy_state: &Strategy,
+        _position_data: &[u8],
+        _reserve_data: &[u8],
+    ) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    fn available_liquidity(&self, _reserve_data: &[u8]) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::reserve::fixtures::{
+        ReserveFixture, SOLEND_RESERVE_LEN, SPL_TOKEN_LENDING_RESERVE_LEN,
+    };
+    use crate::strategy::reserve::WAD;
+
+    /// spl token account holding amount
+    fn token_account_data(amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 165];
+        data[TOKEN_ACCOUNT_AMOUNT_OFFSET..TOKEN_ACCOUNT_AMOUNT_OFFSET + 8]
+            .copy_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    fn strategy_state(strategy_type: StrategyType, current_liquidity: u64) -> Strategy {
+        Strategy {
+            strategy_type,
+            collateral_vault: Pubkey::new_unique(),
+            current_liquidity,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn collateral_vault_is_valued_at_exchange_rate() {
+        let config = ClusterConfig::mainnet();
+        let fixture = ReserveFixture::new();
+        for (strategy_type, reserve_len) in [
+            (StrategyType::SolendWithoutLM, SOLEND_RESERVE_LEN),
+            (StrategyType::Tulip, SPL_TOKEN_LENDING_RESERVE_LEN),
+            (StrategyType::Francium, SPL_TOKEN_LENDING_RESERVE_LEN),
+        ] {
+            let state = strategy_state(strategy_type, 120);
+            let valuer = get_strategy_valuer(strategy_type);
+            let reserve_data = fixture.pack(reserve_len);
+
+            assert_eq!(
+                valuer
+                    .position_account(&config, &Pubkey::new_unique(), &state)
+                    .unwrap(),
+                Some(state.collateral_vault)
+            );
+            // 1_500 liquidity for 1_000 cToken
+            assert_eq!(
+                valuer
+                    .value(&state, &token_account_data(100), &reserve_data)
+                    .unwrap(),
+                Some(150)
+            );
+            assert_eq!(
+                valuer.available_liquidity(&reserve_data).unwrap(),
+                Some(500)
+            );
+        }
+    }
+
+    /// Obligation packed in the declaration order of the solend `Obligation`, padded to its length
+    fn solend_obligation_data(deposits: &[(Pubkey, u64)]) -> Vec<u8> {
+        let mut data = vec![1u8];
+        // last update slot and stale flag
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.push(0);
+        // lending market and owner
+        data.extend_from_slice(&[1u8; 64]);
+        // deposited, borrowed, allowed borrow and unhealthy borrow values, then padding
+        data.extend_from_slice(&[0u8; 4 * 16 + 64]);
+        data.push(deposits.len() as u8);
+        // borrows len
+        data.push(0);
+        for (reserve, deposited_amount) in deposits.iter() {
+            data.extend_from_slice(reserve.as_ref());
+            data.extend_from_slice(&deposited_amount.to_le_bytes());
+            // market value and padding
+            data.extend_from_slice(&[0u8; 16 + 32]);
+        }
+        data.resize(1300, 0);
+        data
+    }
+
+    /// Length of a port obligation account, the spl token-lending `OBLIGATION_LEN`
+    const PORT_OBLIGATION_LEN: usize = 916;
+
+    /// Obligation packed in the declaration order of the spl token-lending `Obligation` kept by port,
+    /// each deposit has a non zero market value
+    fn port_obligation_data(deposits: &[(Pubkey, u64)]) -> Vec<u8> {
+        let mut data = vec![1u8];
+        // last update slot and stale flag
+        data.extend_from_slice(&42u64.to_le_bytes());
+        data.push(0);
+        // lending market and owner
+        data.extend_from_slice(&[1u8; 64]);
+        // deposited, borrowed, allowed borrow and unhealthy borrow values
+        data.extend_from_slice(&[2u8; 4 * 16]);
+        data.push(deposits.len() as u8);
+        // borrows len
+        data.push(0);
+        for (reserve, deposited_amount) in deposits.iter() {
+            data.extend_from_slice(reserve.as_ref());
+            data.extend_from_slice(&deposited_amount.to_le_bytes());
+            // market value
+            data.extend_from_slice(&(3 * WAD).to_le_bytes());
+        }
+        data.resize(PORT_OBLIGATION_LEN, 0);
+        data
+    }
+
+    #[test]
+    fn solend_lm_is_valued_from_obligation_deposit() {
+        let config = ClusterConfig::mainnet();
+        let reserve_data = ReserveFixture::new().pack(SOLEND_RESERVE_LEN);
+        let strategy = Pubkey::new_unique();
+        let mut state = strategy_state(StrategyType::SolendWithLM, 1_000);
+        state.reserve = Pubkey::new_unique();
+        let valuer = get_strategy_valuer(StrategyType::SolendWithLM);
+
+        let addresses = StrategyAddresses::derive_with_program_id(
+            strategy,
+            StrategyType::SolendWithLM,
+            &config.vault_program_id,
+        );
+        assert_eq!(
+            valuer.position_account(&config, &strategy, &state).unwrap(),
+            addresses
+                .solend_obligation
+                .map(|(obligation, _)| obligation)
+        );
+        // 1_500 liquidity for 1_000 cToken, the deposit of the strategy reserve is the second one
+        let obligation =
+            solend_obligation_data(&[(Pubkey::new_unique(), 10), (state.reserve, 800)]);
+        assert_eq!(
+            valuer.value(&state, &obligation, &reserve_data).unwrap(),
+            Some(1_200)
+        );
+        assert_eq!(
+            valuer
+                .value(&state, &solend_obligation_data(&[]), &reserve_data)
+                .unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            valuer.available_liquidity(&reserve_data).unwrap(),
+            Some(500)
+        );
+    }
+
+    #[test]
+    fn port_lm_is_valued_from_spl_token_lending_obligation() {
+        let config = ClusterConfig::mainnet();
+        let strategy = Pubkey::new_unique();
+        let mut state = strategy_state(StrategyType::PortFinanceWithLM, 1_000);
+        state.reserve = Pubkey::new_unique();
+        let valuer = get_strategy_valuer(StrategyType::PortFinanceWithLM);
+        assert_eq!(
+            valuer.position_account(&config, &strategy, &state).unwrap(),
+            Some(
+                Pubkey::find_program_address(
+                    &[b"obligation".as_ref(), strategy.as_ref()],
+                    &config.vault_program_id
+                )
+                .0
+            )
+        );
+
+        let reserve_data = ReserveFixture::new().pack_port();
+        // 1_500 liquidity for 1_000 cToken, the deposit of the strategy reserve is the second one
+        let obligation = port_obligation_data(&[(Pubkey::new_unique(), 10), (state.reserve, 100)]);
+        assert_eq!(obligation[PORT_OBLIGATION_LAYOUT.deposits_len], 2);
+        assert_eq!(
+            valuer.value(&state, &obligation, &reserve_data).unwrap(),
+            Some(150)
+        );
+        assert_eq!(
+            valuer
+                .value(&state, &port_obligation_data(&[]), &reserve_data)
+                .unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            valuer.available_liquidity(&reserve_data).unwrap(),
+            Some(500)
+        );
+        // truncated obligation
+        assert!(valuer
+            .value(&state, &obligation[..150], &reserve_data)
+            .is_err());
+    }
+
+    #[test]
+    fn booked_only_strategies_have_unknown_value() {
+        let config = ClusterConfig::mainnet();
+        for strategy_type in [
+            StrategyType::ApricotWithoutLM,
+            StrategyType::Frakt,
+            StrategyType::Mango,
+            StrategyType::Vault,
+            StrategyType::Unknown(42),
+        ] {
+            let state = strategy_state(strategy_type, 1_000);
+            let valuer = get_strategy_valuer(strategy_type);
+            assert_eq!(
+                valuer
+                    .position_account(&config, &Pubkey::new_unique(), &state)
+                    .unwrap(),
+                None
+            );
+            assert_eq!(valuer.value(&state, &[], &[]).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn undecodable_reserve_fails() {
+        let valuer = get_strategy_valuer(StrategyType::SolendWithoutLM);
+        assert!(valuer.available_liquidity(&[]).is_err());
+        let state = strategy_state(StrategyType::SolendWithoutLM, 0);
+        assert!(valuer
+            .value(&state, &token_account_data(1), &[0u8; 100])
+            .is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use anchor_lang::prelude::Pubkey;
 
 use crate::seed;
+use crate::strategy::base::StrategyType;
+use crate::strategy::{cypher, drift, frakt, mango, marginfi};
 
 pub fn derive_vault_address(token_mint: Pubkey, base: Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(
@@ -20,6 +22,17 @@ pub fn derive_token_vault_address(vault: Pubkey) -> (Pubkey, u8) {
     )
 }
 
+pub fn derive_lp_mint_address(vault: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[seed::LP_MINT_PREFIX.as_ref(), vault.as_ref()], &crate::ID)
+}
+
+pub fn derive_fee_vault_address(vault: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[seed::FEE_VAULT_PREFIX.as_ref(), vault.as_ref()],
+        &crate::ID,
+    )
+}
+
 pub fn derive_strategy_address(vault: Pubkey, reserve: Pubkey, index: u8) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[vault.as_ref(), reserve.as_ref(), &[index]], &crate::ID)
 }
@@ -30,3 +43,261 @@ pub fn derive_collateral_vault_address(strategy: Pubkey) -> (Pubkey, u8) {
         &crate::ID,
     )
 }
+
+pub fn derive_solend_obligation_address(strategy: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[seed::SOLEND_OBLIGATION_PREFIX.as_ref(), strategy.as_ref()],
+        &crate::ID,
+    )
+}
+
+pub fn derive_solend_obligation_owner_address(strategy: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            seed::SOLEND_OBLIGATION_OWNER_PREFIX.as_ref(),
+            strategy.as_ref(),
+        ],
+        &crate::ID,
+    )
+}
+
+/// All PDAs of a vault, with bumps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VaultAddresses {
+    pub vault: (Pubkey, u8),
+    pub token_vault: (Pubkey, u8),
+    /// the lp mint of older vaults is not this PDA, read `Vault::lp_mint` for those
+    pub lp_mint: (Pubkey, u8),
+    pub fee_vault: (Pubkey, u8),
+}
+
+impl VaultAddresses {
+    pub fn derive(token_mint: Pubkey, base: Pubkey) -> Self {
+        Self::derive_with_program_id(token_mint, base, &crate::ID)
+    }
+
+    /// Derive PDAs of a vault program deployed at program_id, see `ClusterConfig::vault_program_id`
+    pub fn derive_with_program_id(token_mint: Pubkey, base: Pubkey, program_id: &Pubkey) -> Self {
+        let vault = Pubkey::find_program_address(
+            &[
+                seed::VAULT_PREFIX.as_ref(),
+                token_mint.as_ref(),
+                base.as_ref(),
+            ],
+            program_id,
+        );
+        let find_vault_pda = |prefix: &str| {
+            Pubkey::find_program_address(&[prefix.as_ref(), vault.0.as_ref()], program_id)
+        };
+        VaultAddresses {
+            vault,
+            token_vault: find_vault_pda(seed::TOKEN_VAULT_PREFIX),
+            lp_mint: find_vault_pda(seed::LP_MINT_PREFIX),
+            fee_vault: find_vault_pda(seed::FEE_VAULT_PREFIX),
+        }
+    }
+}
+
+/// All PDAs of a strategy, with bumps. Protocol specific PDAs are None when not used by the strategy type.
+/// Kamino PDAs are seeded by the lending market of the reserve, not the strategy, they are derived in
+/// `strategy::kamino` (`get_obligation`, `get_lending_market_authority` and `KaminoReserveAddresses`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StrategyAddresses {
+    pub collateral_vault: (Pubkey, u8),
+    pub solend_obligation: Option<(Pubkey, u8)>,
+    pub solend_obligation_owner: Option<(Pubkey, u8)>,
+    /// obligation of PortFinanceWithLM
+    pub port_obligation: Option<(Pubkey, u8)>,
+    pub apricot_user_info_signer: Option<(Pubkey, u8)>,
+    /// owner of the position for drift, frakt, mango and marginfi
+    pub strategy_owner: Option<(Pubkey, u8)>,
+    pub marginfi_account: Option<(Pubkey, u8)>,
+    /// owner of the position for cypher. Cypher has no `StrategyType`, a cypher strategy decodes as
+    /// `StrategyType::Unknown` so it is only derived for unknown strategy types
+    pub cypher_strategy_owner: Option<(Pubkey, u8)>,
+}
+
+impl StrategyAddresses {
+    pub fn derive(strategy: Pubkey, strategy_type: StrategyType) -> Self {
+        Self::derive_with_program_id(strategy, strategy_type, &crate::ID)
+    }
+
+    /// Derive PDAs of a vault program deployed at program_id, see `ClusterConfig::vault_program_id`
+    pub fn derive_with_program_id(
+        strategy: Pubkey,
+        strategy_type: StrategyType,
+        program_id: &Pubkey,
+    ) -> Self {
+        let find_strategy_pda = |prefix: &str| {
+            Some(Pubkey::find_program_address(
+                &[prefix.as_ref(), strategy.as_ref()],
+                program_id,
+            ))
+        };
+        let mut addresses = StrategyAddresses {
+            collateral_vault: Pubkey::find_program_address(
+                &[seed::COLLATERAL_VAULT_PREFIX.as_ref(), strategy.as_ref()],
+                program_id,
+            ),
+            solend_obligation: None,
+            solend_obligation_owner: None,
+            port_obligation: None,
+            apricot_user_info_signer: None,
+            strategy_owner: None,
+            marginfi_account: None,
+            cypher_strategy_owner: None,
+        };
+        match strategy_type {
+            StrategyType::SolendWithoutLM | StrategyType::SolendWithLM => {
+                addresses.solend_obligation = find_strategy_pda(seed::SOLEND_OBLIGATION_PREFIX);
+                addresses.solend_obligation_owner =
+                    find_strategy_pda(seed::SOLEND_OBLIGATION_OWNER_PREFIX);
+            }
+            StrategyType::PortFinanceWithLM => {
+                addresses.port_obligation = find_strategy_pda(seed::OBLIGATION_PREFIX);
+            }
+            StrategyType::ApricotWithoutLM => {
+                addresses.apricot_user_info_signer =
+                    find_strategy_pda(seed::APRICOT_USER_INFO_SIGNER_PREFIX);
+            }
+            StrategyType::Drift => {
+                addresses.strategy_owner = Some(drift::get_strategy_owner_with_program_id(
+                    &strategy, program_id,
+                ));
+            }
+            StrategyType::Frakt => {
+                addresses.strategy_owner = Some(frakt::get_strategy_owner_with_program_id(
+                    &strategy, program_id,
+                ));
+            }
+            StrategyType::Mango => {
+                addresses.strategy_owner = Some(mango::get_strategy_owner_with_program_id(
+                    &strategy, program_id,
+                ));
+            }
+            StrategyType::Marginfi => {
+                addresses.strategy_owner = Some(marginfi::get_strategy_owner_with_program_id(
+                    &strategy, program_id,
+                ));
+                addresses.marginfi_account = Some(marginfi::get_marginfi_account_with_program_id(
+                    &strategy, program_id,
+                ));
+            }
+            StrategyType::Unknown(_) => {
+                addresses.cypher_strategy_owner = Some(cypher::get_strategy_owner_with_program_id(
+                    &strategy, program_id,
+                ));
+            }
+            _ => {}
+        }
+        addresses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn pubkey(address: &str) -> Pubkey {
+        Pubkey::from_str(address).unwrap()
+    }
+
+    #[test]
+    fn vault_addresses_match_mainnet() {
+        let base = pubkey("HWzXGcGHy4tcpYfaRDCyLNzXqBTv3E6BttpCH2vJxArv");
+        let sol =
+            VaultAddresses::derive(pubkey("So11111111111111111111111111111111111111112"), base);
+        assert_eq!(
+            sol.vault.0,
+            pubkey("FERjPVNEa7Udq8CEv68h6tPL46Tq7ieE49HrE2wea3XT")
+        );
+        assert_eq!(
+            sol.token_vault.0,
+            pubkey("HZeLxbZ9uHtSpwZC3LBr4Nubd14iHwz7bRSghRZf5VCG")
+        );
+        let usdc =
+            VaultAddresses::derive(pubkey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"), base);
+        assert_eq!(
+            usdc.vault.0,
+            pubkey("3ESUFCnRNgZ7Mn2mPPUMmXYaKU8jpnV9VtA17M7t2mHQ")
+        );
+        assert_eq!(
+            usdc.vault,
+            derive_vault_address(pubkey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"), base)
+        );
+        assert_eq!(usdc.token_vault, derive_token_vault_address(usdc.vault.0));
+        assert_eq!(usdc.lp_mint, derive_lp_mint_address(usdc.vault.0));
+        assert_eq!(usdc.fee_vault, derive_fee_vault_address(usdc.vault.0));
+    }
+
+    #[test]
+    fn strategy_addresses_use_strategy_module_seeds() {
+        let strategy = pubkey("FERjPVNEa7Udq8CEv68h6tPL46Tq7ieE49HrE2wea3XT");
+        let addresses = |strategy_type| StrategyAddresses::derive(strategy, strategy_type);
+
+        let solend = addresses(StrategyType::SolendWithLM);
+        assert_eq!(
+            solend.collateral_vault,
+            derive_collateral_vault_address(strategy)
+        );
+        assert_eq!(
+            solend.solend_obligation,
+            Some(derive_solend_obligation_address(strategy))
+        );
+        assert_eq!(
+            solend.solend_obligation_owner,
+            Some(derive_solend_obligation_owner_address(strategy))
+        );
+        assert_eq!(solend.strategy_owner, None);
+
+        assert_eq!(
+            addresses(StrategyType::Drift).strategy_owner,
+            Some(drift::get_strategy_owner(&strategy))
+        );
+        assert_eq!(
+            addresses(StrategyType::Frakt).strategy_owner,
+            Some(frakt::get_strategy_owner(&strategy))
+        );
+        assert_eq!(
+            addresses(StrategyType::Mango).strategy_owner,
+            Some(mango::get_strategy_owner(&strategy))
+        );
+        let marginfi = addresses(StrategyType::Marginfi);
+        assert_eq!(
+            marginfi.strategy_owner,
+            Some(marginfi::get_strategy_owner(&strategy))
+        );
+        assert_eq!(
+            marginfi.marginfi_account,
+            Some(marginfi::get_marginfi_account(&strategy))
+        );
+        assert_eq!(addresses(StrategyType::Kamino).cypher_strategy_owner, None);
+        assert_eq!(marginfi.cypher_strategy_owner, None);
+        assert_eq!(
+            addresses(StrategyType::Unknown(200)).cypher_strategy_owner,
+            Some(cypher::get_strategy_owner(&strategy))
+        );
+        assert!(addresses(StrategyType::ApricotWithoutLM)
+            .apricot_user_info_signer
+            .is_some());
+    }
+
+    #[test]
+    fn strategy_addresses_follow_program_id() {
+        let strategy = pubkey("3ESUFCnRNgZ7Mn2mPPUMmXYaKU8jpnV9VtA17M7t2mHQ");
+        let staging = pubkey("6YRZW57XsrT2DxSNLXHHQd4QmiqBode4d6btASkRqcFo");
+        let addresses =
+            StrategyAddresses::derive_with_program_id(strategy, StrategyType::Drift, &staging);
+        assert_eq!(
+            addresses.strategy_owner,
+            Some(drift::get_strategy_owner_with_program_id(
+                &strategy, &staging
+            ))
+        );
+        assert_ne!(
+            addresses,
+            StrategyAddresses::derive(strategy, StrategyType::Drift)
+        );
+    }
+}
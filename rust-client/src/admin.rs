@@ -0,0 +1,1027 @@
+use crate::user::get_or_create_ata;
+use anchor_client::solana_sdk::instruction::{AccountMeta, Instruction};
+use anchor_client::solana_sdk::signature::Signer;
+use anchor_client::solana_sdk::signer::keypair::Keypair;
+use anchor_client::solana_sdk::{system_program, sysvar};
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use anyhow::{anyhow, Result};
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::context::StrategyBumps;
+use mercurial_vault::state::{Strategy, Vault, MAX_BUMPS, MAX_STRATEGY};
+use mercurial_vault::strategy::base::StrategyType;
+use mercurial_vault::strategy::remaining_accounts::get_remaining_accounts_builder;
+use mercurial_vault::utils::StrategyAddresses;
+use std::ops::Deref;
+
+fn vault_instruction(
+    program_id: &Pubkey,
+    accounts: impl ToAccountMetas,
+    data: impl InstructionData,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+pub fn enable_vault_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    admin: Pubkey,
+    enabled: bool,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::UpdateVault { vault, admin },
+        mercurial_vault::instruction::EnableVault {
+            enabled: u8::from(enabled),
+        },
+    )
+}
+
+pub fn enable_vault<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    enabled: bool,
+) -> Result<()> {
+    println!("enable vault {} {}", vault, enabled);
+
+    let builder = program_client
+        .request()
+        .instruction(enable_vault_instruction(
+            &program_client.id(),
+            vault,
+            program_client.payer(),
+            enabled,
+        ));
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub fn set_operator_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    admin: Pubkey,
+    operator: Pubkey,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::SetOperator {
+            vault,
+            operator,
+            admin,
+        },
+        mercurial_vault::instruction::SetOperator {},
+    )
+}
+
+pub fn set_operator<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    operator: Pubkey,
+) -> Result<()> {
+    println!("set operator of vault {} to {}", vault, operator);
+
+    let builder = program_client
+        .request()
+        .instruction(set_operator_instruction(
+            &program_client.id(),
+            vault,
+            program_client.payer(),
+            operator,
+        ));
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub fn update_locked_profit_degradation_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    admin: Pubkey,
+    locked_profit_degradation: u64,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::UpdateVault { vault, admin },
+        mercurial_vault::instruction::UpdateLockedProfitDegradation {
+            locked_profit_degradation,
+        },
+    )
+}
+
+pub fn update_locked_profit_degradation<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    locked_profit_degradation: u64,
+) -> Result<()> {
+    println!(
+        "update locked profit degradation of vault {} to {}",
+        vault, locked_profit_degradation
+    );
+
+    let builder =
+        program_client
+            .request()
+            .instruction(update_locked_profit_degradation_instruction(
+                &program_client.id(),
+                vault,
+                program_client.payer(),
+                locked_profit_degradation,
+            ));
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+/// Both admin and new_admin sign
+pub fn transfer_admin_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    admin: Pubkey,
+    new_admin: Pubkey,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::TransferAdmin {
+            vault,
+            admin,
+            new_admin,
+        },
+        mercurial_vault::instruction::TransferAdmin {},
+    )
+}
+
+/// New admin co-signs the transfer
+pub fn transfer_admin<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    new_admin: &Keypair,
+) -> Result<()> {
+    println!(
+        "transfer admin of vault {} to {}",
+        vault,
+        new_admin.pubkey()
+    );
+
+    let builder = program_client
+        .request()
+        .instruction(transfer_admin_instruction(
+            &program_client.id(),
+            vault,
+            program_client.payer(),
+            new_admin.pubkey(),
+        ))
+        .signer(new_admin);
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+pub fn transfer_fee_vault_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    admin: Pubkey,
+    new_fee_vault: Pubkey,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::TransferFeeVault {
+            vault,
+            admin,
+            new_fee_vault,
+        },
+        mercurial_vault::instruction::TransferFeeVault {},
+    )
+}
+
+pub fn transfer_fee_vault<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    new_fee_vault: Pubkey,
+) -> Result<()> {
+    println!("transfer fee vault of vault {} to {}", vault, new_fee_vault);
+
+    let builder = program_client
+        .request()
+        .instruction(transfer_fee_vault_instruction(
+            &program_client.id(),
+            vault,
+            program_client.payer(),
+            new_fee_vault,
+        ));
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+/// Strategy of reserve at strategy_index with its bump
+pub fn derive_strategy_address(
+    config: &ClusterConfig,
+    vault: &Pubkey,
+    reserve: &Pubkey,
+    strategy_index: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[vault.as_ref(), reserve.as_ref(), &[strategy_index]],
+        &config.vault_program_id,
+    )
+}
+
+/// Initialize the strategy of reserve at strategy_index, returning the strategy with the instruction.
+/// The bumps of the protocol PDAs used by strategy_type are packed into `StrategyBumps::other_bumps` in `StrategyAddresses`
+/// field order, e.g. obligation then obligation owner for solend, strategy owner then marginfi account for marginfi.
+/// remaining_accounts are the protocol accounts needed to open the position, e.g. the solend obligation
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_strategy_instruction(
+    config: &ClusterConfig,
+    vault: Pubkey,
+    reserve: Pubkey,
+    collateral_mint: Pubkey,
+    admin: Pubkey,
+    strategy_type: StrategyType,
+    strategy_index: u8,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Result<(Pubkey, Instruction)> {
+    let strategy_program = config
+        .strategy_programs
+        .get(strategy_type)
+        .ok_or_else(|| anyhow!("Strategy {} can not be initialized", strategy_type))?;
+    let (strategy, _) = derive_strategy_address(config, &vault, &reserve, strategy_index);
+
+    let addresses = StrategyAddresses::derive_with_program_id(
+        strategy,
+        strategy_type,
+        &config.vault_program_id,
+    );
+    let mut other_bumps = [0u8; MAX_BUMPS];
+    let protocol_pdas = [
+        addresses.solend_obligation,
+        addresses.solend_obligation_owner,
+        addresses.apricot_user_info_signer,
+        addresses.strategy_owner,
+        addresses.marginfi_account,
+    ];
+    for (other_bump, (_, bump)) in other_bumps.iter_mut().zip(protocol_pdas.iter().flatten()) {
+        *other_bump = *bump;
+    }
+
+    let mut instruction = vault_instruction(
+        &config.vault_program_id,
+        mercurial_vault::accounts::InitializeStrategy {
+            vault,
+            strategy_program,
+            strategy,
+            reserve,
+            collateral_vault: addresses.collateral_vault.0,
+            collateral_mint,
+            admin,
+            system_program: system_program::id(),
+            rent: sysvar::rent::id(),
+            token_program: spl_token::id(),
+        },
+        mercurial_vault::instruction::InitializeStrategy {
+            bumps: StrategyBumps {
+                strategy_index,
+                other_bumps,
+            },
+            strategy_type,
+        },
+    );
+    instruction.accounts.extend(remaining_accounts);
+    Ok((strategy, instruction))
+}
+
+/// Initialize strategy at the first free strategy index of reserve, an index is free when its strategy account does not exist.
+/// See `initialize_strategy_instruction`
+pub fn initialize_strategy<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    config: &ClusterConfig,
+    vault: Pubkey,
+    reserve: Pubkey,
+    collateral_mint: Pubkey,
+    strategy_type: StrategyType,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Result<Pubkey> {
+    let strategy_keys: Vec<Pubkey> = (0..MAX_STRATEGY as u8)
+        .map(|index| derive_strategy_address(config, &vault, &reserve, index).0)
+        .collect();
+    let strategy_accounts = program_client.rpc().get_multiple_accounts(&strategy_keys)?;
+    let strategy_index = strategy_accounts
+        .iter()
+        .position(|account| account.is_none())
+        .ok_or_else(|| anyhow!("No free strategy index for reserve {}", reserve))?
+        as u8;
+
+    let (strategy, instruction) = initialize_strategy_instruction(
+        config,
+        vault,
+        reserve,
+        collateral_mint,
+        program_client.payer(),
+        strategy_type,
+        strategy_index,
+        remaining_accounts,
+    )?;
+    println!(
+        "initialize strategy {} {} at index {}",
+        strategy_type, strategy, strategy_index
+    );
+
+    let signature = program_client.request().instruction(instruction).send()?;
+    println!("{}", signature);
+
+    Ok(strategy)
+}
+
+pub fn add_strategy_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    strategy: Pubkey,
+    admin: Pubkey,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::AddStrategy {
+            vault,
+            strategy,
+            admin,
+        },
+        mercurial_vault::instruction::AddStrategy {},
+    )
+}
+
+pub fn add_strategy<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    strategy: Pubkey,
+) -> Result<()> {
+    println!("add strategy {} to vault {}", strategy, vault);
+
+    let builder = program_client
+        .request()
+        .instruction(add_strategy_instruction(
+            &program_client.id(),
+            vault,
+            strategy,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+/// Admin advance of `remove_strategy2`
+#[derive(Clone, Copy, Debug)]
+pub struct AdvancePayment {
+    pub max_admin_pay_amount: u64,
+    /// admin token account paying the advance
+    pub token_admin_advance_payment: Pubkey,
+    /// vault owned token account receiving the advance
+    pub token_vault_advance_payment: Pubkey,
+}
+
+/// Withdraw all liquidity of strategy to the token vault and remove it from vault, remove_strategy2 with advance_payment.
+/// remaining_accounts are the strategy remaining accounts, see `get_remaining_accounts_builder`
+#[allow(clippy::too_many_arguments)]
+pub fn remove_strategy_instruction(
+    config: &ClusterConfig,
+    vault: Pubkey,
+    vault_state: &Vault,
+    strategy: Pubkey,
+    strategy_state: &Strategy,
+    admin: Pubkey,
+    advance_payment: Option<AdvancePayment>,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Result<Instruction> {
+    let strategy_program = config
+        .strategy_programs
+        .get(strategy_state.strategy_type)
+        .ok_or_else(|| {
+            anyhow!(
+                "Strategy {} can not be removed",
+                strategy_state.strategy_type
+            )
+        })?;
+    let program_id = &config.vault_program_id;
+    let mut instruction = match advance_payment {
+        None => vault_instruction(
+            program_id,
+            mercurial_vault::accounts::RemoveStrategy {
+                vault,
+                strategy,
+                strategy_program,
+                collateral_vault: strategy_state.collateral_vault,
+                reserve: strategy_state.reserve,
+                token_vault: vault_state.token_vault,
+                fee_vault: vault_state.fee_vault,
+                lp_mint: vault_state.lp_mint,
+                token_program: spl_token::id(),
+                admin,
+            },
+            mercurial_vault::instruction::RemoveStrategy {},
+        ),
+        Some(advance_payment) => vault_instruction(
+            program_id,
+            mercurial_vault::accounts::RemoveStrategy2 {
+                vault,
+                strategy,
+                strategy_program,
+                collateral_vault: strategy_state.collateral_vault,
+                reserve: strategy_state.reserve,
+                token_vault: vault_state.token_vault,
+                token_admin_advance_payment: advance_payment.token_admin_advance_payment,
+                token_vault_advance_payment: advance_payment.token_vault_advance_payment,
+                fee_vault: vault_state.fee_vault,
+                lp_mint: vault_state.lp_mint,
+                token_program: spl_token::id(),
+                admin,
+            },
+            mercurial_vault::instruction::RemoveStrategy2 {
+                max_admin_pay_amount: advance_payment.max_admin_pay_amount,
+            },
+        ),
+    };
+    instruction.accounts.extend(remaining_accounts);
+    Ok(instruction)
+}
+
+/// Withdraw all liquidity of strategy to the token vault and remove it from vault.
+/// With max_admin_pay_amount, admin pays up to that amount for liquidity the strategy can not withdraw (remove_strategy2),
+/// token_vault_advance_payment is the vault owned account receiving it
+pub fn remove_strategy<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    config: &ClusterConfig,
+    vault: Pubkey,
+    strategy: Pubkey,
+    advance_payment: Option<(u64, Pubkey)>,
+) -> Result<()> {
+    println!("remove strategy {} from vault {}", strategy, vault);
+
+    let vault_state: Vault = program_client.account(vault)?;
+    let strategy_state: Strategy = program_client.account(strategy)?;
+    let reserve_data = program_client
+        .rpc()
+        .get_account_data(&strategy_state.reserve)?;
+    let remaining_accounts = get_remaining_accounts_builder(strategy_state.strategy_type)?
+        .remaining_accounts(config, &strategy, &strategy_state, &reserve_data)?;
+    let advance_payment = advance_payment
+        .map(|(max_admin_pay_amount, token_vault_advance_payment)| {
+            Ok::<_, anyhow::Error>(AdvancePayment {
+                max_admin_pay_amount,
+                token_admin_advance_payment: get_or_create_ata(
+                    program_client,
+                    vault_state.token_mint,
+                    program_client.payer(),
+                )?,
+                token_vault_advance_payment,
+            })
+        })
+        .transpose()?;
+
+    let instruction = remove_strategy_instruction(
+        config,
+        vault,
+        &vault_state,
+        strategy,
+        &strategy_state,
+        program_client.payer(),
+        advance_payment,
+        remaining_accounts,
+    )?;
+
+    let signature = program_client.request().instruction(instruction).send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+/// token_admin is the admin token account receiving the dust
+pub fn collect_dust_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    vault_state: &Vault,
+    admin: Pubkey,
+    token_admin: Pubkey,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::CollectDust {
+            vault,
+            token_vault: vault_state.token_vault,
+            token_admin,
+            admin,
+            token_program: spl_token::id(),
+        },
+        mercurial_vault::instruction::CollectDust {},
+    )
+}
+
+/// Send tokens of the token vault not accounted in total_amount to the admin token account
+pub fn collect_dust<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+) -> Result<()> {
+    println!("collect dust of vault {}", vault);
+
+    let vault_state: Vault = program_client.account(vault)?;
+    let token_admin = get_or_create_ata(
+        program_client,
+        vault_state.token_mint,
+        program_client.payer(),
+    )?;
+
+    let builder = program_client
+        .request()
+        .instruction(collect_dust_instruction(
+            &program_client.id(),
+            vault,
+            &vault_state,
+            program_client.payer(),
+            token_admin,
+        ));
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+/// operator is the admin or the operator of the vault
+pub fn claim_rewards_instruction(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    strategy: Pubkey,
+    token_reward_acc: Pubkey,
+    operator: Pubkey,
+) -> Instruction {
+    vault_instruction(
+        program_id,
+        mercurial_vault::accounts::ClaimRewards {
+            vault,
+            strategy,
+            token_program: spl_token::id(),
+            token_reward_acc,
+            operator,
+        },
+        mercurial_vault::instruction::ClaimRewards {},
+    )
+}
+
+/// Claim liquidity mining rewards held by token_reward_acc of strategy, signed by admin or operator
+pub fn claim_rewards<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    strategy: Pubkey,
+    token_reward_acc: Pubkey,
+) -> Result<()> {
+    println!("claim rewards of strategy {}", strategy);
+
+    let builder = program_client
+        .request()
+        .instruction(claim_rewards_instruction(
+            &program_client.id(),
+            vault,
+            strategy,
+            token_reward_acc,
+            program_client.payer(),
+        ));
+
+    let signature = builder.send()?;
+    println!("{}", signature);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// pubkey, is_writable and is_signer of each account
+    fn flags(accounts: &[AccountMeta]) -> Vec<(Pubkey, bool, bool)> {
+        accounts
+            .iter()
+            .map(|account| (account.pubkey, account.is_writable, account.is_signer))
+            .collect()
+    }
+
+    fn vault_state() -> Vault {
+        Vault {
+            token_mint: Pubkey::new_unique(),
+            token_vault: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            fee_vault: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    fn strategy_state(strategy_type: StrategyType) -> Strategy {
+        Strategy {
+            strategy_type,
+            reserve: Pubkey::new_unique(),
+            collateral_vault: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn update_vault_accounts_follow_context() {
+        let program_id = Pubkey::new_unique();
+        let (vault, admin) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // vault, admin (signer)
+        let expected = vec![(vault, true, false), (admin, false, true)];
+
+        let enable = enable_vault_instruction(&program_id, vault, admin, true);
+        assert_eq!(enable.program_id, program_id);
+        assert_eq!(flags(&enable.accounts), expected);
+        assert_eq!(
+            enable.data,
+            mercurial_vault::instruction::EnableVault { enabled: 1 }.data()
+        );
+
+        let update = update_locked_profit_degradation_instruction(&program_id, vault, admin, 7);
+        assert_eq!(flags(&update.accounts), expected);
+        assert_eq!(
+            update.data,
+            mercurial_vault::instruction::UpdateLockedProfitDegradation {
+                locked_profit_degradation: 7
+            }
+            .data()
+        );
+    }
+
+    #[test]
+    fn set_operator_accounts_follow_context() {
+        let program_id = Pubkey::new_unique();
+        let (vault, admin, operator) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let instruction = set_operator_instruction(&program_id, vault, admin, operator);
+        // vault, operator, admin (signer)
+        assert_eq!(
+            flags(&instruction.accounts),
+            vec![
+                (vault, true, false),
+                (operator, false, false),
+                (admin, false, true),
+            ]
+        );
+        assert_eq!(
+            instruction.data,
+            mercurial_vault::instruction::SetOperator {}.data()
+        );
+    }
+
+    #[test]
+    fn transfer_accounts_follow_context() {
+        let program_id = Pubkey::new_unique();
+        let (vault, admin, new_admin, new_fee_vault) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let transfer_admin = transfer_admin_instruction(&program_id, vault, admin, new_admin);
+        // vault, admin (signer), new_admin (signer)
+        assert_eq!(
+            flags(&transfer_admin.accounts),
+            vec![
+                (vault, true, false),
+                (admin, false, true),
+                (new_admin, false, true),
+            ]
+        );
+        assert_eq!(
+            transfer_admin.data,
+            mercurial_vault::instruction::TransferAdmin {}.data()
+        );
+
+        let transfer_fee_vault =
+            transfer_fee_vault_instruction(&program_id, vault, admin, new_fee_vault);
+        // vault, admin (signer), new_fee_vault
+        assert_eq!(
+            flags(&transfer_fee_vault.accounts),
+            vec![
+                (vault, true, false),
+                (admin, false, true),
+                (new_fee_vault, false, false),
+            ]
+        );
+        assert_eq!(
+            transfer_fee_vault.data,
+            mercurial_vault::instruction::TransferFeeVault {}.data()
+        );
+    }
+
+    #[test]
+    fn initialize_strategy_accounts_follow_context() {
+        let config = ClusterConfig::mainnet();
+        let (vault, reserve, collateral_mint, admin) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let obligation = AccountMeta::new(Pubkey::new_unique(), false);
+
+        let (strategy, instruction) = initialize_strategy_instruction(
+            &config,
+            vault,
+            reserve,
+            collateral_mint,
+            admin,
+            StrategyType::SolendWithoutLM,
+            2,
+            vec![obligation.clone()],
+        )
+        .unwrap();
+        assert_eq!(
+            strategy,
+            derive_strategy_address(&config, &vault, &reserve, 2).0
+        );
+        let addresses = StrategyAddresses::derive_with_program_id(
+            strategy,
+            StrategyType::SolendWithoutLM,
+            &config.vault_program_id,
+        );
+
+        assert_eq!(instruction.program_id, config.vault_program_id);
+        // vault, strategy_program, strategy, reserve, collateral_vault, collateral_mint, admin (signer),
+        // system_program, rent, token_program, then the remaining accounts
+        assert_eq!(
+            flags(&instruction.accounts),
+            vec![
+                (vault, true, false),
+                (config.strategy_programs.solend, false, false),
+                (strategy, true, false),
+                (reserve, true, false),
+                (addresses.collateral_vault.0, true, false),
+                (collateral_mint, false, false),
+                (admin, true, true),
+                (system_program::id(), false, false),
+                (sysvar::rent::id(), false, false),
+                (spl_token::id(), false, false),
+                (obligation.pubkey, true, false),
+            ]
+        );
+
+        let mut other_bumps = [0u8; MAX_BUMPS];
+        other_bumps[0] = addresses.solend_obligation.unwrap().1;
+        other_bumps[1] = addresses.solend_obligation_owner.unwrap().1;
+        assert_eq!(
+            instruction.data,
+            mercurial_vault::instruction::InitializeStrategy {
+                bumps: StrategyBumps {
+                    strategy_index: 2,
+                    other_bumps,
+                },
+                strategy_type: StrategyType::SolendWithoutLM,
+            }
+            .data()
+        );
+    }
+
+    #[test]
+    fn initialize_strategy_data_follows_idl() {
+        let config = ClusterConfig::mainnet();
+        let (_, instruction) = initialize_strategy_instruction(
+            &config,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            StrategyType::Marginfi,
+            3,
+            vec![],
+        )
+        .unwrap();
+        let strategy = instruction.accounts[2].pubkey;
+        let addresses = StrategyAddresses::derive_with_program_id(
+            strategy,
+            StrategyType::Marginfi,
+            &config.vault_program_id,
+        );
+
+        // sighash, then bumps { strategyIndex: u8, otherBumps: [u8; 10] }, then strategyType as its u8 variant index
+        let mut expected = anchor_lang::solana_program::hash::hash(b"global:initialize_strategy")
+            .to_bytes()[..8]
+            .to_vec();
+        expected.push(3);
+        let mut other_bumps = [0u8; 10];
+        other_bumps[0] = addresses.strategy_owner.unwrap().1;
+        other_bumps[1] = addresses.marginfi_account.unwrap().1;
+        expected.extend_from_slice(&other_bumps);
+        // Marginfi is the 12th variant of StrategyType in the IDL
+        expected.push(11);
+        assert_eq!(instruction.data, expected);
+    }
+
+    #[test]
+    fn add_strategy_accounts_follow_context() {
+        let program_id = Pubkey::new_unique();
+        let (vault, strategy, admin) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let instruction = add_strategy_instruction(&program_id, vault, strategy, admin);
+        // vault, strategy, admin (signer)
+        assert_eq!(
+            flags(&instruction.accounts),
+            vec![
+                (vault, true, false),
+                (strategy, false, false),
+                (admin, false, true),
+            ]
+        );
+        assert_eq!(
+            instruction.data,
+            mercurial_vault::instruction::AddStrategy {}.data()
+        );
+    }
+
+    #[test]
+    fn remove_strategy_accounts_follow_context() {
+        let config = ClusterConfig::mainnet();
+        let (vault, strategy, admin) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let vault_state = vault_state();
+        let strategy_state = strategy_state(StrategyType::PortFinanceWithoutLM);
+        let remaining_account = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+
+        let instruction = remove_strategy_instruction(
+            &config,
+            vault,
+            &vault_state,
+            strategy,
+            &strategy_state,
+            admin,
+            None,
+            vec![remaining_account.clone()],
+        )
+        .unwrap();
+        // vault, strategy, strategy_program, collateral_vault, reserve, token_vault, fee_vault, lp_mint,
+        // token_program, admin (signer), then the remaining accounts
+        assert_eq!(
+            flags(&instruction.accounts),
+            vec![
+                (vault, true, false),
+                (strategy, true, false),
+                (config.strategy_programs.port_finance, false, false),
+                (strategy_state.collateral_vault, true, false),
+                (strategy_state.reserve, true, false),
+                (vault_state.token_vault, true, false),
+                (vault_state.fee_vault, true, false),
+                (vault_state.lp_mint, true, false),
+                (spl_token::id(), false, false),
+                (admin, false, true),
+                (remaining_account.pubkey, false, false),
+            ]
+        );
+        assert_eq!(
+            instruction.data,
+            mercurial_vault::instruction::RemoveStrategy {}.data()
+        );
+
+        let advance_payment = AdvancePayment {
+            max_admin_pay_amount: 100,
+            token_admin_advance_payment: Pubkey::new_unique(),
+            token_vault_advance_payment: Pubkey::new_unique(),
+        };
+        let instruction = remove_strategy_instruction(
+            &config,
+            vault,
+            &vault_state,
+            strategy,
+            &strategy_state,
+            admin,
+            Some(advance_payment),
+            vec![remaining_account.clone()],
+        )
+        .unwrap();
+        // the advance payment accounts follow token_vault
+        assert_eq!(
+            flags(&instruction.accounts),
+            vec![
+                (vault, true, false),
+                (strategy, true, false),
+                (config.strategy_programs.port_finance, false, false),
+                (strategy_state.collateral_vault, true, false),
+                (strategy_state.reserve, true, false),
+                (vault_state.token_vault, true, false),
+                (advance_payment.token_admin_advance_payment, true, false),
+                (advance_payment.token_vault_advance_payment, true, false),
+                (vault_state.fee_vault, true, false),
+                (vault_state.lp_mint, true, false),
+                (spl_token::id(), false, false),
+                (admin, false, true),
+                (remaining_account.pubkey, false, false),
+            ]
+        );
+        assert_eq!(
+            instruction.data,
+            mercurial_vault::instruction::RemoveStrategy2 {
+                max_admin_pay_amount: 100
+            }
+            .data()
+        );
+    }
+
+    #[test]
+    fn remove_strategy_without_strategy_program_fails() {
+        let config = ClusterConfig::mainnet();
+        let result = remove_strategy_instruction(
+            &config,
+            Pubkey::new_unique(),
+            &vault_state(),
+            Pubkey::new_unique(),
+            &strategy_state(StrategyType::Vault),
+            Pubkey::new_unique(),
+            None,
+            vec![],
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn collect_dust_accounts_follow_context() {
+        let program_id = Pubkey::new_unique();
+        let (vault, admin, token_admin) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let vault_state = vault_state();
+
+        let instruction =
+            collect_dust_instruction(&program_id, vault, &vault_state, admin, token_admin);
+        // vault, token_vault, token_admin, admin (signer), token_program
+        assert_eq!(
+            flags(&instruction.accounts),
+            vec![
+                (vault, false, false),
+                (vault_state.token_vault, true, false),
+                (token_admin, true, false),
+                (admin, false, true),
+                (spl_token::id(), false, false),
+            ]
+        );
+        assert_eq!(
+            instruction.data,
+            mercurial_vault::instruction::CollectDust {}.data()
+        );
+    }
+
+    #[test]
+    fn claim_rewards_accounts_follow_context() {
+        let program_id = Pubkey::new_unique();
+        let (vault, strategy, token_reward_acc, operator) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let instruction =
+            claim_rewards_instruction(&program_id, vault, strategy, token_reward_acc, operator);
+        // vault, strategy, token_program, token_reward_acc, operator (signer)
+        assert_eq!(
+            flags(&instruction.accounts),
+            vec![
+                (vault, false, false),
+                (strategy, false, false),
+                (spl_token::id(), false, false),
+                (token_reward_acc, true, false),
+                (operator, false, true),
+            ]
+        );
+        assert_eq!(
+            instruction.data,
+            mercurial_vault::instruction::ClaimRewards {}.data()
+        );
+    }
+}
@@ -0,0 +1,559 @@
+//! Deposits and withdrawals through the affiliate program, same as the affiliate path of ts-client `VaultImpl`
+use crate::instructions::{is_native_mint, wrap_sol, VaultInstructions};
+use crate::withdraw_planner::{WithdrawPath, WithdrawPlan};
+use affiliate_vault::state::{Partner, User};
+use affiliate_vault::utils::{
+    derive_partner_address_with_program_id, derive_user_address_with_program_id,
+};
+use anchor_client::solana_sdk::signature::Signer;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::solana_program::{system_program, sysvar};
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use anyhow::Result;
+use mercurial_vault::state::Vault;
+use std::ops::Deref;
+
+/// Affiliate accounts of owner depositing through partner_wallet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AffiliateAddresses {
+    pub program_id: Pubkey,
+    /// associated token account of partner_wallet receiving the fee
+    pub partner_token: Pubkey,
+    pub partner: Pubkey,
+    /// user PDA, owner of the lp
+    pub user: Pubkey,
+    /// associated lp token account of the user PDA
+    pub user_lp: Pubkey,
+    /// associated token account of owner
+    pub user_token: Pubkey,
+}
+
+impl AffiliateAddresses {
+    pub fn derive(
+        program_id: &Pubkey,
+        vault: Pubkey,
+        vault_state: &Vault,
+        partner_wallet: Pubkey,
+        owner: Pubkey,
+    ) -> Self {
+        let partner_token = spl_associated_token_account::get_associated_token_address(
+            &partner_wallet,
+            &vault_state.token_mint,
+        );
+        let (partner, _) = derive_partner_address_with_program_id(vault, partner_token, program_id);
+        let (user, _) = derive_user_address_with_program_id(partner, owner, program_id);
+        AffiliateAddresses {
+            program_id: *program_id,
+            partner_token,
+            partner,
+            user,
+            user_lp: spl_associated_token_account::get_associated_token_address(
+                &user,
+                &vault_state.lp_mint,
+            ),
+            user_token: spl_associated_token_account::get_associated_token_address(
+                &owner,
+                &vault_state.token_mint,
+            ),
+        }
+    }
+}
+
+pub fn init_partner(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    partner_token: Pubkey,
+    admin: Pubkey,
+) -> Instruction {
+    let (partner, _) = derive_partner_address_with_program_id(vault, partner_token, program_id);
+    Instruction {
+        program_id: *program_id,
+        accounts: affiliate_vault::accounts::InitPartner {
+            partner,
+            vault,
+            partner_token,
+            admin,
+            system_program: system_program::id(),
+            rent: sysvar::rent::id(),
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate_vault::instruction::InitPartner {}.data(),
+    }
+}
+
+pub fn update_fee_ratio(
+    program_id: &Pubkey,
+    partner: Pubkey,
+    admin: Pubkey,
+    fee_ratio: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: affiliate_vault::accounts::UpdateFeeRatio { partner, admin }
+            .to_account_metas(None),
+        data: affiliate_vault::instruction::UpdateFeeRatio { fee_ratio }.data(),
+    }
+}
+
+pub fn init_user(addresses: &AffiliateAddresses, owner: Pubkey) -> Instruction {
+    Instruction {
+        program_id: addresses.program_id,
+        accounts: affiliate_vault::accounts::InitUser {
+            user: addresses.user,
+            partner: addresses.partner,
+            owner,
+            system_program: system_program::id(),
+            rent: sysvar::rent::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate_vault::instruction::InitUser {}.data(),
+    }
+}
+
+/// Idempotent instructions creating the token account of owner and the lp account of the user PDA
+fn user_token_accounts(
+    addresses: &AffiliateAddresses,
+    vault_state: &Vault,
+    payer: &Pubkey,
+    owner: &Pubkey,
+) -> Vec<Instruction> {
+    vec![
+        (owner, vault_state.token_mint),
+        (&addresses.user, vault_state.lp_mint),
+    ]
+    .into_iter()
+    .map(|(wallet, mint)| {
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            payer,
+            wallet,
+            &mint,
+            &spl_token::id(),
+        )
+    })
+    .collect()
+}
+
+/// Deposit token_amount of owner through the partner, init_user when the user PDA does not exist yet.
+/// For a wrapped SOL vault, token_amount lamports of owner are wrapped first
+#[allow(clippy::too_many_arguments)]
+pub fn deposit(
+    addresses: &AffiliateAddresses,
+    vault_program_id: &Pubkey,
+    vault: Pubkey,
+    vault_state: &Vault,
+    payer: Pubkey,
+    owner: Pubkey,
+    token_amount: u64,
+    minimum_lp_token_amount: u64,
+    init_user: bool,
+) -> VaultInstructions {
+    let mut pre_instructions = user_token_accounts(addresses, vault_state, &payer, &owner);
+    if is_native_mint(&vault_state.token_mint) {
+        pre_instructions.extend(wrap_sol(&owner, &addresses.user_token, token_amount));
+    }
+    if init_user {
+        pre_instructions.push(self::init_user(addresses, owner));
+    }
+    let instruction = Instruction {
+        program_id: addresses.program_id,
+        accounts: affiliate_vault::accounts::DepositWithdrawLiquidity {
+            partner: addresses.partner,
+            user: addresses.user,
+            vault_program: *vault_program_id,
+            vault,
+            token_vault: vault_state.token_vault,
+            vault_lp_mint: vault_state.lp_mint,
+            user_token: addresses.user_token,
+            user_lp: addresses.user_lp,
+            owner,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: affiliate_vault::instruction::Deposit {
+            token_amount,
+            minimum_lp_token_amount,
+        }
+        .data(),
+    };
+    VaultInstructions::new(payer, owner, pre_instructions, instruction)
+}
+
+/// Withdraw through the partner along plan, which must be made by `WithdrawPlanner::plan` for owner,
+/// `AffiliateAddresses::user_token` and `AffiliateAddresses::user_lp`.
+/// Affiliate accounts are partner, user and the vault program followed by the accounts of the vault instruction.
+/// For a wrapped SOL vault, the wrapped SOL account of owner is closed afterwards unless unwrap_sol is false
+pub fn withdraw_with_plan(
+    addresses: &AffiliateAddresses,
+    plan: &WithdrawPlan,
+    vault_state: &Vault,
+    payer: Pubkey,
+    owner: Pubkey,
+    min_out_amount: u64,
+    unwrap_sol: bool,
+) -> VaultInstructions {
+    let mut accounts = vec![
+        AccountMeta::new(addresses.partner, false),
+        AccountMeta::new(addresses.user, false),
+        AccountMeta::new_readonly(plan.program_id, false),
+    ];
+    accounts.extend(plan.accounts.iter().cloned());
+    let data = match plan.path {
+        WithdrawPath::Reserve => affiliate_vault::instruction::Withdraw {
+            unmint_amount: plan.unmint_amount,
+            min_out_amount,
+        }
+        .data(),
+        WithdrawPath::Strategy { .. } => {
+            affiliate_vault::instruction::WithdrawDirectlyFromStrategy {
+                unmint_amount: plan.unmint_amount,
+                min_out_amount,
+            }
+            .data()
+        }
+    };
+    let instruction = Instruction {
+        program_id: addresses.program_id,
+        accounts,
+        data,
+    };
+    VaultInstructions::new(
+        payer,
+        owner,
+        user_token_accounts(addresses, vault_state, &payer, &owner),
+        instruction,
+    )
+    .unwrap_sol_after(vault_state, &owner, &addresses.user_token, unwrap_sol)
+}
+
+/// Partner state with its outstanding fee and fee ratio, None if the partner is not initialized.
+/// Same as `getAffiliateInfo` of ts-client
+pub fn get_affiliate_info<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    addresses: &AffiliateAddresses,
+) -> Result<Option<Partner>> {
+    get_affiliate_account(program_client, &addresses.partner)
+}
+
+/// User PDA state, None before the first deposit
+pub fn get_affiliate_user<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    addresses: &AffiliateAddresses,
+) -> Result<Option<User>> {
+    get_affiliate_account(program_client, &addresses.user)
+}
+
+fn get_affiliate_account<C: Deref<Target = impl Signer> + Clone, T: AccountDeserialize>(
+    program_client: &anchor_client::Program<C>,
+    pubkey: &Pubkey,
+) -> Result<Option<T>> {
+    let account = program_client
+        .rpc()
+        .get_account_with_commitment(pubkey, program_client.rpc().commitment())?
+        .value;
+    account
+        .map(|account| Ok(T::try_deserialize(&mut account.data.as_slice())?))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::unwrap_sol;
+    use anchor_lang::solana_program::system_instruction;
+    use mercurial_vault::strategy::base::StrategyType;
+
+    fn vault_state(token_mint: Pubkey) -> Vault {
+        Vault {
+            token_mint,
+            token_vault: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            fee_vault: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    /// pubkey, is_writable and is_signer of each account
+    fn flags(accounts: &[AccountMeta]) -> Vec<(Pubkey, bool, bool)> {
+        accounts
+            .iter()
+            .map(|account| (account.pubkey, account.is_writable, account.is_signer))
+            .collect()
+    }
+
+    fn ata(wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+        spl_associated_token_account::get_associated_token_address(wallet, mint)
+    }
+
+    #[test]
+    fn affiliate_addresses_follow_program_seeds() {
+        let program_id = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state(Pubkey::new_unique());
+        let (partner_wallet, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let addresses =
+            AffiliateAddresses::derive(&program_id, vault, &vault_state, partner_wallet, owner);
+        let partner_token = ata(&partner_wallet, &vault_state.token_mint);
+        // partner: [vault, partner_token], user: [partner, owner]
+        let (partner, _) =
+            Pubkey::find_program_address(&[vault.as_ref(), partner_token.as_ref()], &program_id);
+        let (user, _) =
+            Pubkey::find_program_address(&[partner.as_ref(), owner.as_ref()], &program_id);
+        assert_eq!(
+            addresses,
+            AffiliateAddresses {
+                program_id,
+                partner_token,
+                partner,
+                user,
+                user_lp: ata(&user, &vault_state.lp_mint),
+                user_token: ata(&owner, &vault_state.token_mint),
+            }
+        );
+    }
+
+    #[test]
+    fn deposit_accounts_follow_idl() {
+        let (program_id, vault_program_id) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state(Pubkey::new_unique());
+        let (payer, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let addresses = AffiliateAddresses::derive(
+            &program_id,
+            vault,
+            &vault_state,
+            Pubkey::new_unique(),
+            owner,
+        );
+
+        let deposit = deposit(
+            &addresses,
+            &vault_program_id,
+            vault,
+            &vault_state,
+            payer,
+            owner,
+            10,
+            9,
+            true,
+        );
+        assert_eq!(deposit.instruction.program_id, program_id);
+        // partner, user, vaultProgram, vault, tokenVault, vaultLpMint, userToken, userLp, owner (signer), tokenProgram
+        assert_eq!(
+            flags(&deposit.instruction.accounts),
+            vec![
+                (addresses.partner, true, false),
+                (addresses.user, true, false),
+                (vault_program_id, false, false),
+                (vault, true, false),
+                (vault_state.token_vault, true, false),
+                (vault_state.lp_mint, true, false),
+                (addresses.user_token, true, false),
+                (addresses.user_lp, true, false),
+                (owner, false, true),
+                (spl_token::id(), false, false),
+            ]
+        );
+        assert_eq!(
+            deposit.instruction.data,
+            affiliate_vault::instruction::Deposit {
+                token_amount: 10,
+                minimum_lp_token_amount: 9
+            }
+            .data()
+        );
+        let mut expected = user_token_accounts(&addresses, &vault_state, &payer, &owner);
+        expected.push(init_user(&addresses, owner));
+        assert_eq!(deposit.pre_instructions, expected);
+        assert_eq!(deposit.signers, vec![payer, owner]);
+    }
+
+    #[test]
+    fn native_mint_deposit_wraps_sol_before_init_user() {
+        let program_id = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state(spl_token::native_mint::id());
+        let owner = Pubkey::new_unique();
+        let addresses = AffiliateAddresses::derive(
+            &program_id,
+            vault,
+            &vault_state,
+            Pubkey::new_unique(),
+            owner,
+        );
+        let deposit = |init_user: bool| {
+            deposit(
+                &addresses,
+                &Pubkey::new_unique(),
+                vault,
+                &vault_state,
+                owner,
+                owner,
+                10,
+                9,
+                init_user,
+            )
+        };
+
+        let mut expected = user_token_accounts(&addresses, &vault_state, &owner, &owner);
+        expected.push(system_instruction::transfer(
+            &owner,
+            &addresses.user_token,
+            10,
+        ));
+        expected.push(
+            spl_token::instruction::sync_native(&spl_token::id(), &addresses.user_token).unwrap(),
+        );
+        assert_eq!(deposit(false).pre_instructions, expected);
+        expected.push(init_user(&addresses, owner));
+        assert_eq!(deposit(true).pre_instructions, expected);
+        assert!(deposit(true).post_instructions.is_empty());
+    }
+
+    #[test]
+    fn withdraw_with_plan_from_reserve_follows_idl() {
+        let (program_id, vault_program_id) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state(spl_token::native_mint::id());
+        let owner = Pubkey::new_unique();
+        let addresses = AffiliateAddresses::derive(
+            &program_id,
+            vault,
+            &vault_state,
+            Pubkey::new_unique(),
+            owner,
+        );
+        let plan = WithdrawPlan {
+            program_id: vault_program_id,
+            path: WithdrawPath::Reserve,
+            unmint_amount: 10,
+            out_amount: 11,
+            accounts: mercurial_vault::accounts::DepositWithdrawLiquidity {
+                vault,
+                token_vault: vault_state.token_vault,
+                lp_mint: vault_state.lp_mint,
+                user_token: addresses.user_token,
+                user_lp: addresses.user_lp,
+                user: owner,
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None),
+        };
+
+        let withdraw = withdraw_with_plan(&addresses, &plan, &vault_state, owner, owner, 9, true);
+        assert_eq!(withdraw.instruction.program_id, program_id);
+        // partner, user, vaultProgram, vault, tokenVault, vaultLpMint, userToken, userLp, owner (signer), tokenProgram
+        assert_eq!(
+            flags(&withdraw.instruction.accounts),
+            vec![
+                (addresses.partner, true, false),
+                (addresses.user, true, false),
+                (vault_program_id, false, false),
+                (vault, true, false),
+                (vault_state.token_vault, true, false),
+                (vault_state.lp_mint, true, false),
+                (addresses.user_token, true, false),
+                (addresses.user_lp, true, false),
+                (owner, false, true),
+                (spl_token::id(), false, false),
+            ]
+        );
+        assert_eq!(
+            withdraw.instruction.data,
+            affiliate_vault::instruction::Withdraw {
+                unmint_amount: 10,
+                min_out_amount: 9
+            }
+            .data()
+        );
+        assert_eq!(
+            withdraw.post_instructions,
+            vec![unwrap_sol(&owner, &addresses.user_token)]
+        );
+    }
+
+    #[test]
+    fn withdraw_with_plan_from_strategy_follows_idl() {
+        let (program_id, vault_program_id) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state(Pubkey::new_unique());
+        let owner = Pubkey::new_unique();
+        let addresses = AffiliateAddresses::derive(
+            &program_id,
+            vault,
+            &vault_state,
+            Pubkey::new_unique(),
+            owner,
+        );
+        let (strategy, reserve, strategy_program, collateral_vault) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let remaining_account = AccountMeta::new(Pubkey::new_unique(), false);
+        let mut accounts = mercurial_vault::accounts::WithdrawDirectlyFromStrategy {
+            vault,
+            strategy,
+            reserve,
+            strategy_program,
+            collateral_vault,
+            token_vault: vault_state.token_vault,
+            lp_mint: vault_state.lp_mint,
+            fee_vault: vault_state.fee_vault,
+            user_token: addresses.user_token,
+            user_lp: addresses.user_lp,
+            user: owner,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None);
+        accounts.push(remaining_account.clone());
+        let plan = WithdrawPlan {
+            program_id: vault_program_id,
+            path: WithdrawPath::Strategy {
+                strategy,
+                strategy_type: StrategyType::SolendWithoutLM,
+            },
+            unmint_amount: 10,
+            out_amount: 11,
+            accounts,
+        };
+
+        let withdraw = withdraw_with_plan(&addresses, &plan, &vault_state, owner, owner, 9, true);
+        // partner, user, vaultProgram, vault, strategy, reserve, strategyProgram, collateralVault, tokenVault,
+        // vaultLpMint, feeVault, userToken, userLp, owner (signer), tokenProgram, then the remaining accounts
+        assert_eq!(
+            flags(&withdraw.instruction.accounts),
+            vec![
+                (addresses.partner, true, false),
+                (addresses.user, true, false),
+                (vault_program_id, false, false),
+                (vault, true, false),
+                (strategy, true, false),
+                (reserve, true, false),
+                (strategy_program, false, false),
+                (collateral_vault, true, false),
+                (vault_state.token_vault, true, false),
+                (vault_state.lp_mint, true, false),
+                (vault_state.fee_vault, true, false),
+                (addresses.user_token, true, false),
+                (addresses.user_lp, true, false),
+                (owner, false, true),
+                (spl_token::id(), false, false),
+                (remaining_account.pubkey, true, false),
+            ]
+        );
+        assert_eq!(
+            withdraw.instruction.data,
+            affiliate_vault::instruction::WithdrawDirectlyFromStrategy {
+                unmint_amount: 10,
+                min_out_amount: 9
+            }
+            .data()
+        );
+        assert!(withdraw.post_instructions.is_empty());
+    }
+}
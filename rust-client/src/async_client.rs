@@ -0,0 +1,626 @@
+use crate::error::{map_slippage_error_async, SlippageSide};
+use crate::instructions;
+use crate::utils::apply_slippage;
+use crate::withdraw_planner::{
+    MultiLegWithdrawResult, StrategyWithReserve, WithdrawPlan, WithdrawPlanner,
+    WithdrawPlannerState, WithdrawableLiquidity,
+};
+use anchor_client::solana_client::nonblocking::rpc_client::RpcClient;
+use anchor_client::solana_sdk::instruction::Instruction;
+use anchor_client::solana_sdk::signature::{Signature, Signer};
+use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_lang::solana_program::clock::Clock;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::AccountDeserialize;
+use anchor_spl::token::spl_token::state::{Account as TokenAccount, Mint};
+use anyhow::{anyhow, Result};
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::state::{DepositQuote, Strategy, Vault};
+use mercurial_vault::utils::VaultAddresses;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+/// Async variant of `VaultClient` on the nonblocking rpc client, Send + Sync.
+/// Independent accounts are fetched concurrently or in a single getMultipleAccounts call
+pub struct AsyncVaultClient {
+    pub rpc: Arc<RpcClient>,
+    pub config: ClusterConfig,
+    pub vault: Pubkey,
+    pub vault_state: Vault,
+    pub token_mint: Mint,
+    pub lp_mint: Mint,
+    /// Close the wrapped SOL account after a withdrawal from a SOL vault, true by default
+    pub unwrap_sol: bool,
+}
+
+/// Result of `AsyncVaultClient::deposit`
+#[derive(Clone, Copy, Debug)]
+pub struct AsyncDepositResult {
+    pub signature: Signature,
+    pub quote: DepositQuote,
+}
+
+/// Result of `AsyncVaultClient::withdraw`
+#[derive(Clone, Debug)]
+pub struct AsyncWithdrawResult {
+    pub signature: Signature,
+    pub plan: WithdrawPlan,
+}
+
+pub async fn get_current_node_clock_time(rpc: &RpcClient) -> Result<u64> {
+    let clock_account = rpc.get_account(&sysvar::clock::id()).await?;
+    let clock = bincode::deserialize::<Clock>(&clock_account.data)?;
+    Ok(u64::try_from(clock.unix_timestamp)?)
+}
+
+/// Fetch accounts of pubkeys with getMultipleAccounts, erroring if any is missing
+async fn get_multiple_accounts_data(rpc: &RpcClient, pubkeys: &[Pubkey]) -> Result<Vec<Vec<u8>>> {
+    rpc.get_multiple_accounts(pubkeys)
+        .await?
+        .into_iter()
+        .zip(pubkeys)
+        .map(|(account, pubkey)| {
+            account
+                .map(|account| account.data)
+                .ok_or_else(|| anyhow!("Account {} not found", pubkey))
+        })
+        .collect()
+}
+
+/// Fetch every strategy of the vault and its reserve, in two getMultipleAccounts calls
+pub async fn get_strategies_with_reserve(
+    rpc: &RpcClient,
+    vault_state: &Vault,
+) -> Result<Vec<StrategyWithReserve>> {
+    let strategy_pubkeys: Vec<Pubkey> = vault_state
+        .strategies
+        .iter()
+        .filter(|strategy| **strategy != Pubkey::default())
+        .copied()
+        .collect();
+    if strategy_pubkeys.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut strategies = vec![];
+    for (pubkey, data) in strategy_pubkeys
+        .iter()
+        .zip(get_multiple_accounts_data(rpc, &strategy_pubkeys).await?)
+    {
+        strategies.push((*pubkey, Strategy::try_deserialize(&mut data.as_slice())?));
+    }
+
+    let reserves: Vec<Pubkey> = strategies.iter().map(|(_, state)| state.reserve).collect();
+    let reserve_accounts = rpc.get_multiple_accounts(&reserves).await?;
+    strategies
+        .into_iter()
+        .zip(reserve_accounts)
+        .map(|((pubkey, state), reserve)| {
+            StrategyWithReserve::new(pubkey, state, reserve.map(|account| account.data))
+        })
+        .collect()
+}
+
+impl AsyncVaultClient {
+    /// Load the vault of token_mint with the standard base key of config
+    pub async fn from_token_mint(
+        rpc: Arc<RpcClient>,
+        config: ClusterConfig,
+        token_mint: Pubkey,
+    ) -> Result<Self> {
+        let base = config.base_key;
+        Self::from_token_mint_and_base(rpc, config, token_mint, base).await
+    }
+
+    pub async fn from_token_mint_and_base(
+        rpc: Arc<RpcClient>,
+        config: ClusterConfig,
+        token_mint: Pubkey,
+        base: Pubkey,
+    ) -> Result<Self> {
+        let (vault, _) =
+            VaultAddresses::derive_with_program_id(token_mint, base, &config.vault_program_id)
+                .vault;
+        Self::from_pda(rpc, config, vault).await
+    }
+
+    pub async fn from_pda(
+        rpc: Arc<RpcClient>,
+        config: ClusterConfig,
+        vault: Pubkey,
+    ) -> Result<Self> {
+        let (vault_state, token_mint, lp_mint) = Self::fetch_vault(&rpc, &vault).await?;
+        Ok(AsyncVaultClient {
+            rpc,
+            config,
+            vault,
+            vault_state,
+            token_mint,
+            lp_mint,
+            unwrap_sol: true,
+        })
+    }
+
+    async fn fetch_vault(rpc: &RpcClient, vault: &Pubkey) -> Result<(Vault, Mint, Mint)> {
+        let vault_data = rpc.get_account_data(vault).await?;
+        let vault_state = Vault::try_deserialize(&mut vault_data.as_slice())?;
+        let mints =
+            get_multiple_accounts_data(rpc, &[vault_state.token_mint, vault_state.lp_mint]).await?;
+        let token_mint = Mint::unpack(&mints[0])?;
+        let lp_mint = Mint::unpack(&mints[1])?;
+        Ok((vault_state, token_mint, lp_mint))
+    }
+
+    /// Lp balance of the associated token account of owner, 0 if it does not exist
+    pub async fn get_user_balance(&self, owner: Pubkey) -> Result<u64> {
+        let user_lp = spl_associated_token_account::get_associated_token_address(
+            &owner,
+            &self.vault_state.lp_mint,
+        );
+        match self
+            .rpc
+            .get_account_with_commitment(&user_lp, self.rpc.commitment())
+            .await?
+            .value
+        {
+            Some(account) => Ok(TokenAccount::unpack(&account.data)?.amount),
+            None => Ok(0),
+        }
+    }
+
+    /// Refetch the lp mint and return its supply
+    pub async fn get_vault_supply(&mut self) -> Result<u64> {
+        let data = self.rpc.get_account_data(&self.vault_state.lp_mint).await?;
+        self.lp_mint = Mint::unpack(&data)?;
+        Ok(self.lp_mint.supply)
+    }
+
+    /// Unlocked amount of the cached vault state at the on-chain time
+    pub async fn get_withdrawable_amount(&self) -> Result<u64> {
+        let current_time = get_current_node_clock_time(&self.rpc).await?;
+        self.vault_state
+            .get_unlocked_amount(current_time)
+            .ok_or_else(|| anyhow!("Math overflow"))
+    }
+
+    /// Liquidity withdrawable right now by source, from the cached vault state and lp supply
+    pub async fn get_withdrawable_liquidity(&self) -> Result<WithdrawableLiquidity> {
+        let planner_state = self.fetch_planner_state().await?;
+        WithdrawPlanner::new(&self.config, self.vault, &self.vault_state, &planner_state)
+            .withdrawable_liquidity()
+    }
+
+    /// Fetch the state to plan withdrawals, with the cached vault state and lp supply
+    async fn fetch_planner_state(&self) -> Result<WithdrawPlannerState> {
+        let (current_time, token_vault, strategies) = futures::join!(
+            get_current_node_clock_time(&self.rpc),
+            self.rpc.get_account_data(&self.vault_state.token_vault),
+            get_strategies_with_reserve(&self.rpc, &self.vault_state),
+        );
+        let token_vault = TokenAccount::unpack(&token_vault?)?;
+        Ok(WithdrawPlannerState {
+            token_vault_amount: token_vault.amount,
+            strategies: strategies?,
+            current_time: current_time?,
+            lp_supply: self.lp_mint.supply,
+        })
+    }
+
+    /// Refetch vault state and both mints
+    pub async fn refresh_vault_state(&mut self) -> Result<()> {
+        let (vault_state, token_mint, lp_mint) = Self::fetch_vault(&self.rpc, &self.vault).await?;
+        self.vault_state = vault_state;
+        self.token_mint = token_mint;
+        self.lp_mint = lp_mint;
+        Ok(())
+    }
+
+    pub async fn get_strategies_state(&self) -> Result<Vec<(Pubkey, Strategy)>> {
+        let strategy_pubkeys: Vec<Pubkey> = self
+            .vault_state
+            .strategies
+            .iter()
+            .filter(|strategy| **strategy != Pubkey::default())
+            .copied()
+            .collect();
+        if strategy_pubkeys.is_empty() {
+            return Ok(vec![]);
+        }
+        let accounts = get_multiple_accounts_data(&self.rpc, &strategy_pubkeys).await?;
+        strategy_pubkeys
+            .into_iter()
+            .zip(accounts)
+            .map(|(pubkey, data)| Ok((pubkey, Strategy::try_deserialize(&mut data.as_slice())?)))
+            .collect()
+    }
+
+    async fn send<S: Signer + Sync>(
+        &self,
+        instructions: &[Instruction],
+        payer: &S,
+    ) -> Result<Signature> {
+        let recent_blockhash = self.rpc.get_latest_blockhash().await?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            recent_blockhash,
+        );
+        Ok(self.rpc.send_and_confirm_transaction(&transaction).await?)
+    }
+
+    /// Vault state and lp supply fetched now, with the on-chain time, leaving the cached state untouched
+    async fn fetch_vault_now(&self) -> Result<(Vault, u64, u64)> {
+        let (refresh, current_time) = futures::join!(
+            Self::fetch_vault(&self.rpc, &self.vault),
+            get_current_node_clock_time(&self.rpc),
+        );
+        let (vault_state, _, lp_mint) = refresh?;
+        Ok((vault_state, lp_mint.supply, current_time?))
+    }
+
+    /// Deposit token_amount of payer, the token and lp accounts are created in the same transaction
+    pub async fn deposit<S: Signer + Sync>(
+        &mut self,
+        payer: &S,
+        token_amount: u64,
+        minimum_lp_token_amount: u64,
+    ) -> Result<AsyncDepositResult> {
+        self.deposit_with_minimum(payer, token_amount, |_| Ok(minimum_lp_token_amount))
+            .await
+    }
+
+    /// Deposit with the minimum lp set slippage_bps below a quote at the on-chain time.
+    /// Fails with `SlippageExceeded` when the vault mints less
+    pub async fn deposit_with_slippage<S: Signer + Sync>(
+        &mut self,
+        payer: &S,
+        token_amount: u64,
+        slippage_bps: u64,
+    ) -> Result<AsyncDepositResult> {
+        self.deposit_with_minimum(payer, token_amount, |quote| {
+            apply_slippage(quote.lp_amount, slippage_bps)
+        })
+        .await
+    }
+
+    async fn deposit_with_minimum<S: Signer + Sync>(
+        &mut self,
+        payer: &S,
+        token_amount: u64,
+        minimum_lp_token_amount: impl FnOnce(&DepositQuote) -> Result<u64>,
+    ) -> Result<AsyncDepositResult> {
+        let user = payer.pubkey();
+        let (refresh, current_time) = futures::join!(
+            Self::fetch_vault(&self.rpc, &self.vault),
+            get_current_node_clock_time(&self.rpc),
+        );
+        let (vault_state, token_mint, lp_mint) = refresh?;
+        self.vault_state = vault_state;
+        self.token_mint = token_mint;
+        self.lp_mint = lp_mint;
+
+        let quote = self
+            .vault_state
+            .quote_deposit(current_time?, token_amount, self.lp_mint.supply)
+            .ok_or_else(|| anyhow!("Cannot quote deposit"))?;
+        let minimum_lp_token_amount = minimum_lp_token_amount(&quote)?;
+
+        let instructions = instructions::deposit(
+            &self.config.vault_program_id,
+            self.vault,
+            &self.vault_state,
+            user,
+            user,
+            token_amount,
+            minimum_lp_token_amount,
+        );
+
+        let instructions = instructions.instructions();
+        let signature = match self.send(&instructions, payer).await {
+            Ok(signature) => signature,
+            Err(error) => {
+                let actual = async {
+                    let (vault_state, lp_supply, current_time) =
+                        self.fetch_vault_now().await.ok()?;
+                    vault_state
+                        .quote_deposit(current_time, token_amount, lp_supply)
+                        .map(|quote| quote.lp_amount)
+                };
+                return Err(map_slippage_error_async(
+                    error,
+                    &instructions,
+                    &self.config.vault_program_id,
+                    SlippageSide::Deposit,
+                    quote.lp_amount,
+                    minimum_lp_token_amount,
+                    actual,
+                )
+                .await);
+            }
+        };
+        Ok(AsyncDepositResult { signature, quote })
+    }
+
+    /// Burn unmint_amount of lp of payer, routed like ts-client through the reserve or the strategy with highest liquidity
+    pub async fn withdraw<S: Signer + Sync>(
+        &mut self,
+        payer: &S,
+        unmint_amount: u64,
+        min_out_amount: u64,
+    ) -> Result<AsyncWithdrawResult> {
+        self.withdraw_with_minimum(payer, unmint_amount, |_| Ok(min_out_amount))
+            .await
+    }
+
+    /// Withdraw with the minimum out amount set slippage_bps below the planned out amount.
+    /// Fails with `SlippageExceeded` when the vault pays less
+    pub async fn withdraw_with_slippage<S: Signer + Sync>(
+        &mut self,
+        payer: &S,
+        unmint_amount: u64,
+        slippage_bps: u64,
+    ) -> Result<AsyncWithdrawResult> {
+        self.withdraw_with_minimum(payer, unmint_amount, |plan| {
+            apply_slippage(plan.out_amount, slippage_bps)
+        })
+        .await
+    }
+
+    async fn withdraw_with_minimum<S: Signer + Sync>(
+        &mut self,
+        payer: &S,
+        unmint_amount: u64,
+        min_out_amount: impl FnOnce(&WithdrawPlan) -> Result<u64>,
+    ) -> Result<AsyncWithdrawResult> {
+        let user = payer.pubkey();
+        self.refresh_vault_state().await?;
+        let planner_state = self.fetch_planner_state().await?;
+        let (user_token, user_lp, _) =
+            instructions::user_token_accounts(&user, &user, &self.vault_state);
+
+        let plan =
+            WithdrawPlanner::new(&self.config, self.vault, &self.vault_state, &planner_state)
+                .plan(unmint_amount, user, user_token, user_lp)?;
+        let min_out_amount = min_out_amount(&plan)?;
+        let signature = self.send_withdraw(payer, &plan, min_out_amount).await?;
+        Ok(AsyncWithdrawResult { signature, plan })
+    }
+
+    /// Burn unmint_amount of lp of payer in several transactions when no single strategy has enough liquidity,
+    /// see `WithdrawPlanner::plan_legs`. Each leg has its minimum out amount slippage_bps below its planned out amount.
+    /// Legs are sent in order, a failed leg stops the withdrawal and is reported in the result with the completed legs
+    pub async fn withdraw_multi_leg<S: Signer + Sync>(
+        &mut self,
+        payer: &S,
+        unmint_amount: u64,
+        slippage_bps: u64,
+    ) -> Result<MultiLegWithdrawResult> {
+        let user = payer.pubkey();
+        self.refresh_vault_state().await?;
+        let planner_state = self.fetch_planner_state().await?;
+        let (user_token, user_lp, _) =
+            instructions::user_token_accounts(&user, &user, &self.vault_state);
+
+        let plan =
+            WithdrawPlanner::new(&self.config, self.vault, &self.vault_state, &planner_state)
+                .plan_legs(unmint_amount, user, user_token, user_lp)?;
+        let client = &*self;
+        Ok(plan
+            .send_legs(slippage_bps, move |leg, min_out_amount| async move {
+                client.send_withdraw(payer, &leg, min_out_amount).await
+            })
+            .await)
+    }
+
+    /// Send the withdrawal of plan, mapping a failed minimum out amount to `SlippageExceeded`
+    async fn send_withdraw<S: Signer + Sync>(
+        &self,
+        payer: &S,
+        plan: &WithdrawPlan,
+        min_out_amount: u64,
+    ) -> Result<Signature> {
+        let user = payer.pubkey();
+        let instructions = instructions::withdraw_with_plan(
+            plan,
+            &self.vault_state,
+            user,
+            user,
+            min_out_amount,
+            self.unwrap_sol,
+        );
+        let instructions = instructions.instructions();
+        match self.send(&instructions, payer).await {
+            Err(error) => {
+                let actual = async {
+                    let (vault_state, lp_supply, current_time) =
+                        self.fetch_vault_now().await.ok()?;
+                    vault_state.get_amount_by_share(current_time, plan.unmint_amount, lp_supply)
+                };
+                Err(map_slippage_error_async(
+                    error,
+                    &instructions,
+                    &self.config.vault_program_id,
+                    SlippageSide::Withdraw,
+                    plan.out_amount,
+                    min_out_amount,
+                    actual,
+                )
+                .await)
+            }
+            sent => sent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::solana_client::client_error::{ClientErrorKind, Result as ClientResult};
+    use anchor_client::solana_client::rpc_client::RpcClientConfig;
+    use anchor_client::solana_client::rpc_request::RpcRequest;
+    use anchor_client::solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+    use anchor_client::solana_sdk::signer::keypair::Keypair;
+    use anchor_lang::AccountSerialize;
+    use base64::Engine;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Answers getVersion and getMultipleAccounts from accounts and fails every other request,
+    /// recording each request but the version query of the rpc client
+    #[derive(Default)]
+    struct RecordingSender {
+        accounts: HashMap<String, Vec<u8>>,
+        requests: Arc<Mutex<Vec<RpcRequest>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl RpcSender for RecordingSender {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            if request == RpcRequest::GetVersion {
+                return Ok(json!({ "solana-core": "1.16.14" }));
+            }
+            self.requests.lock().unwrap().push(request);
+            if request != RpcRequest::GetMultipleAccounts {
+                return Err(ClientErrorKind::Custom(format!("{} is not mocked", request)).into());
+            }
+            let accounts: Vec<Value> = params[0]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|pubkey| match self.accounts.get(pubkey.as_str().unwrap()) {
+                    Some(data) => json!({
+                        "lamports": 1,
+                        "data": [base64::engine::general_purpose::STANDARD.encode(data), "base64"],
+                        "owner": mercurial_vault::ID.to_string(),
+                        "executable": false,
+                        "rentEpoch": 0,
+                    }),
+                    None => Value::Null,
+                })
+                .collect();
+            Ok(json!({ "context": { "slot": 1 }, "value": accounts }))
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "recording".to_string()
+        }
+    }
+
+    fn client(sender: RecordingSender, vault_state: Vault) -> AsyncVaultClient {
+        AsyncVaultClient {
+            rpc: Arc::new(RpcClient::new_sender(sender, RpcClientConfig::default())),
+            config: ClusterConfig::mainnet(),
+            vault: Pubkey::new_unique(),
+            vault_state,
+            token_mint: Mint::default(),
+            lp_mint: Mint::default(),
+            unwrap_sol: true,
+        }
+    }
+
+    /// Vault with strategy_count strategies, each with its strategy account and reserve
+    fn vault_with_strategies(strategy_count: usize) -> (Vault, RecordingSender) {
+        let mut vault_state = Vault::default();
+        let mut sender = RecordingSender::default();
+        for strategy in vault_state.strategies.iter_mut().take(strategy_count) {
+            *strategy = Pubkey::new_unique();
+            let state = Strategy {
+                reserve: Pubkey::new_unique(),
+                ..Default::default()
+            };
+            let mut data = vec![];
+            state.try_serialize(&mut data).unwrap();
+            sender.accounts.insert(strategy.to_string(), data);
+            sender
+                .accounts
+                .insert(state.reserve.to_string(), vec![0u8; 619]);
+        }
+        (vault_state, sender)
+    }
+
+    #[test]
+    fn async_vault_client_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<AsyncVaultClient>();
+    }
+
+    #[tokio::test]
+    async fn deposit_and_withdraw_futures_can_be_spawned() {
+        let mut deposit_client = client(RecordingSender::default(), Vault::default());
+        let mut withdraw_client = client(RecordingSender::default(), Vault::default());
+        let payer = Arc::new(Keypair::new());
+
+        let deposit_payer = payer.clone();
+        let deposit = tokio::spawn(async move {
+            deposit_client
+                .deposit(deposit_payer.as_ref(), 10, 9)
+                .await
+                .map(|result| result.signature)
+        });
+        let withdraw = tokio::spawn(async move {
+            withdraw_client
+                .withdraw(payer.as_ref(), 10, 9)
+                .await
+                .map(|result| result.signature)
+        });
+
+        // the vault is not mocked, so both fail once spawned and polled
+        assert!(deposit.await.unwrap().is_err());
+        assert!(withdraw.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn strategies_are_loaded_in_one_get_multiple_accounts_call() {
+        let (vault_state, sender) = vault_with_strategies(3);
+        let requests = sender.requests.clone();
+        let client = client(sender, vault_state);
+
+        let strategies = client.get_strategies_state().await.unwrap();
+        assert_eq!(strategies.len(), 3);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![RpcRequest::GetMultipleAccounts]
+        );
+    }
+
+    #[tokio::test]
+    async fn strategies_with_reserve_are_loaded_in_two_get_multiple_accounts_calls() {
+        let (vault_state, sender) = vault_with_strategies(3);
+        let requests = sender.requests.clone();
+        let rpc = RpcClient::new_sender(sender, RpcClientConfig::default());
+
+        let strategies = get_strategies_with_reserve(&rpc, &vault_state)
+            .await
+            .unwrap();
+        assert_eq!(strategies.len(), 3);
+        assert!(strategies
+            .iter()
+            .all(|strategy| strategy.reserve_data.len() == 619));
+        // one call for the strategies, one for their reserves
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                RpcRequest::GetMultipleAccounts,
+                RpcRequest::GetMultipleAccounts
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn vault_without_strategies_makes_no_call() {
+        let sender = RecordingSender::default();
+        let requests = sender.requests.clone();
+        let client = client(sender, Vault::default());
+
+        assert!(client.get_strategies_state().await.unwrap().is_empty());
+        assert!(requests.lock().unwrap().is_empty());
+    }
+}
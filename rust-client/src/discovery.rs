@@ -0,0 +1,245 @@
+use crate::filters;
+use anchor_client::solana_client::rpc_filter::RpcFilterType;
+use anchor_client::solana_sdk::account::Account;
+use anchor_client::solana_sdk::signature::Signer;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_spl::token::spl_token;
+use anyhow::Result;
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::state::Vault;
+use std::ops::Deref;
+
+/// Max accounts of a getMultipleAccounts request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Which base key the vault was derived from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VaultKind {
+    /// `ClusterConfig::base_key`
+    Standard,
+    /// `ClusterConfig::idle_vault_base`, vault without strategies
+    Idle,
+    /// Any other base key
+    Other,
+}
+
+impl VaultKind {
+    pub fn of(vault_state: &Vault, config: &ClusterConfig) -> Self {
+        if vault_state.base == config.base_key {
+            VaultKind::Standard
+        } else if vault_state.base == config.idle_vault_base {
+            VaultKind::Idle
+        } else {
+            VaultKind::Other
+        }
+    }
+}
+
+/// Fields to match, None matches any vault
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VaultFilter {
+    pub token_mint: Option<Pubkey>,
+    pub base: Option<Pubkey>,
+    pub admin: Option<Pubkey>,
+    pub operator: Option<Pubkey>,
+}
+
+impl VaultFilter {
+    pub fn rpc_filters(&self) -> Vec<RpcFilterType> {
+        vec![
+            self.token_mint.as_ref().map(filters::vault_by_token_mint),
+            self.base.as_ref().map(filters::vault_by_base),
+            self.admin.as_ref().map(filters::vault_by_admin),
+            self.operator.as_ref().map(filters::vault_by_operator),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiscoveredVault {
+    pub pubkey: Pubkey,
+    pub kind: VaultKind,
+    pub state: Vault,
+    /// None when the lp mint could not be fetched
+    pub lp_supply: Option<u64>,
+}
+
+impl DiscoveredVault {
+    pub fn token_mint(&self) -> Pubkey {
+        self.state.token_mint
+    }
+
+    /// Total amount of token managed by the vault, including locked profit
+    pub fn tvl(&self) -> u64 {
+        self.state.total_amount
+    }
+
+    pub fn strategy_count(&self) -> usize {
+        self.state
+            .strategies
+            .iter()
+            .filter(|strategy| **strategy != Pubkey::default())
+            .count()
+    }
+}
+
+/// Return every vault of the vault program matching filter, with its lp supply
+pub fn discover_vaults<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    config: &ClusterConfig,
+    filter: &VaultFilter,
+) -> Result<Vec<DiscoveredVault>> {
+    let vaults = program_client.accounts::<Vault>(filter.rpc_filters())?;
+
+    let lp_mints: Vec<Pubkey> = vaults.iter().map(|(_, state)| state.lp_mint).collect();
+    let mut lp_mint_accounts = Vec::with_capacity(lp_mints.len());
+    for lp_mints in lp_mints.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        lp_mint_accounts.extend(program_client.rpc().get_multiple_accounts(lp_mints)?);
+    }
+
+    Ok(pair_lp_supplies(config, vaults, lp_mint_accounts))
+}
+
+/// Pair each vault with the lp mint account fetched at the same index,
+/// the lp supply is None when the account is missing or is not a mint
+fn pair_lp_supplies(
+    config: &ClusterConfig,
+    vaults: Vec<(Pubkey, Vault)>,
+    lp_mint_accounts: Vec<Option<Account>>,
+) -> Vec<DiscoveredVault> {
+    vaults
+        .into_iter()
+        .zip(lp_mint_accounts)
+        .map(|((pubkey, state), lp_mint_account)| DiscoveredVault {
+            pubkey,
+            kind: VaultKind::of(&state, config),
+            lp_supply: lp_mint_account
+                .and_then(|account| spl_token::state::Mint::unpack(&account.data).ok())
+                .map(|mint| mint.supply),
+            state,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new_from_array([seed; 32])
+    }
+
+    fn vault_state(base: Pubkey, lp_mint: Pubkey) -> Vault {
+        Vault {
+            base,
+            lp_mint,
+            ..Default::default()
+        }
+    }
+
+    fn mint_account(supply: u64) -> Account {
+        let mut data = vec![0u8; spl_token::state::Mint::LEN];
+        spl_token::state::Mint::pack(
+            spl_token::state::Mint {
+                supply,
+                decimals: 6,
+                is_initialized: true,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        Account {
+            data,
+            owner: spl_token::id(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn vault_kind_follows_base_key() {
+        let config = ClusterConfig::mainnet();
+        for (base, kind) in [
+            (config.base_key, VaultKind::Standard),
+            (config.idle_vault_base, VaultKind::Idle),
+            (key(1), VaultKind::Other),
+        ] {
+            assert_eq!(VaultKind::of(&vault_state(base, key(2)), &config), kind);
+        }
+    }
+
+    #[test]
+    fn rpc_filters_hold_only_set_fields_in_order() {
+        assert!(VaultFilter::default().rpc_filters().is_empty());
+
+        let filter = VaultFilter {
+            token_mint: Some(key(1)),
+            operator: Some(key(4)),
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.rpc_filters(),
+            vec![
+                filters::vault_by_token_mint(&key(1)),
+                filters::vault_by_operator(&key(4)),
+            ]
+        );
+
+        let filter = VaultFilter {
+            token_mint: Some(key(1)),
+            base: Some(key(2)),
+            admin: Some(key(3)),
+            operator: Some(key(4)),
+        };
+        assert_eq!(
+            filter.rpc_filters(),
+            vec![
+                filters::vault_by_token_mint(&key(1)),
+                filters::vault_by_base(&key(2)),
+                filters::vault_by_admin(&key(3)),
+                filters::vault_by_operator(&key(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn vaults_are_paired_with_the_supply_of_their_lp_mint() {
+        let config = ClusterConfig::mainnet();
+        let vaults = vec![
+            (key(10), vault_state(config.base_key, key(20))),
+            (key(11), vault_state(config.idle_vault_base, key(21))),
+            (key(12), vault_state(key(1), key(22))),
+        ];
+        let not_a_mint = Account {
+            data: vec![1, 2, 3],
+            ..Default::default()
+        };
+
+        let discovered = pair_lp_supplies(
+            &config,
+            vaults,
+            vec![Some(mint_account(1_000)), None, Some(not_a_mint)],
+        );
+
+        assert_eq!(
+            discovered
+                .iter()
+                .map(|vault| (
+                    vault.pubkey,
+                    vault.kind,
+                    vault.state.lp_mint,
+                    vault.lp_supply
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (key(10), VaultKind::Standard, key(20), Some(1_000)),
+                (key(11), VaultKind::Idle, key(21), None),
+                (key(12), VaultKind::Other, key(22), None),
+            ]
+        );
+    }
+}
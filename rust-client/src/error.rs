@@ -0,0 +1,473 @@
+use anchor_client::solana_client::client_error::{
+    ClientError as SolanaClientError, ClientErrorKind,
+};
+use anchor_client::solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use anchor_client::solana_sdk::instruction::{Instruction, InstructionError};
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::transaction::TransactionError;
+use anchor_client::ClientError;
+use mercurial_vault::VaultError;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+
+/// Every VaultError variant, in declaration order
+const VAULT_ERRORS: [VaultError; 17] = [
+    VaultError::VaultIsDisabled,
+    VaultError::ExceededSlippage,
+    VaultError::StrategyIsNotExisted,
+    VaultError::UnAuthorized,
+    VaultError::MathOverflow,
+    VaultError::ProtocolIsNotSupported,
+    VaultError::UnMatchReserve,
+    VaultError::InvalidLockedProfitDegradation,
+    VaultError::MaxStrategyReached,
+    VaultError::StrategyExisted,
+    VaultError::InvalidUnmintAmount,
+    VaultError::InvalidAccountsForStrategy,
+    VaultError::InvalidBump,
+    VaultError::AmountMustGreaterThanZero,
+    VaultError::MangoIsNotSupportedAnymore,
+    VaultError::StrategyIsNotSupported,
+    VaultError::PayAmountIsExeeced,
+];
+
+/// Return VaultError of a custom program error code
+pub fn vault_error_from_code(code: u32) -> Option<VaultError> {
+    VAULT_ERRORS
+        .iter()
+        .copied()
+        .find(|error| u32::from(*error) == code)
+}
+
+/// Whether sending the same transaction again may succeed
+pub fn is_retryable(error: VaultError) -> bool {
+    // depends on vault state which changes between quote and execution
+    matches!(error, VaultError::ExceededSlippage)
+}
+
+/// Hint printed to user for a vault error
+pub fn error_hint(error: VaultError) -> &'static str {
+    match error {
+        VaultError::ExceededSlippage => {
+            "vault state changed since the quote, retry with a fresh quote or a larger slippage"
+        }
+        VaultError::VaultIsDisabled => "vault is disabled, deposits and withdrawals are paused",
+        VaultError::MangoIsNotSupportedAnymore => {
+            "mango strategy can not be used anymore, withdraw from another strategy"
+        }
+        VaultError::StrategyIsNotSupported | VaultError::ProtocolIsNotSupported => {
+            "strategy can not be used for this instruction, withdraw from another strategy"
+        }
+        VaultError::StrategyIsNotExisted => "strategy is not part of the vault",
+        VaultError::InvalidAccountsForStrategy | VaultError::UnMatchReserve => {
+            "strategy accounts do not match the strategy, refresh strategy and reserve state"
+        }
+        VaultError::InvalidUnmintAmount | VaultError::AmountMustGreaterThanZero => {
+            "amount is invalid, check lp balance and amount"
+        }
+        VaultError::UnAuthorized => "signer is not allowed to perform this instruction",
+        _ => "transaction can not succeed as is",
+    }
+}
+
+/// VaultError returned by an instruction of a transaction
+#[derive(Clone, Copy, Debug)]
+pub struct VaultTransactionError {
+    pub instruction_index: u8,
+    pub error: VaultError,
+}
+
+/// Program which raised the custom error of a failed transaction.
+/// The first program logging `failed:` raised it, a CPI caller only forwards it.
+/// Without logs the program of the failed instruction is used, which misses errors raised through CPI
+pub fn failed_program_id(
+    logs: Option<&[String]>,
+    instruction_index: u8,
+    instructions: &[Instruction],
+) -> Option<Pubkey> {
+    match logs {
+        Some(logs) => logs.iter().find_map(|log| {
+            let mut words = log.strip_prefix("Program ")?.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some(program), Some("failed:")) => Pubkey::from_str(program).ok(),
+                _ => None,
+            }
+        }),
+        None => instructions
+            .get(usize::from(instruction_index))
+            .map(|instruction| instruction.program_id),
+    }
+}
+
+/// Logs of a failed preflight simulation
+fn preflight_logs(error: &SolanaClientError) -> Option<&[String]> {
+    match error.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => result.logs.as_deref(),
+        _ => None,
+    }
+}
+
+impl VaultTransactionError {
+    /// Custom error raised by the program vault_program_id, see `failed_program_id`.
+    /// Errors of other anchor programs share the same code range and are not vault errors
+    pub fn from_transaction_error(
+        error: &TransactionError,
+        logs: Option<&[String]>,
+        instructions: &[Instruction],
+        vault_program_id: &Pubkey,
+    ) -> Option<Self> {
+        match error {
+            TransactionError::InstructionError(
+                instruction_index,
+                InstructionError::Custom(code),
+            ) if failed_program_id(logs, *instruction_index, instructions).as_ref()
+                == Some(vault_program_id) =>
+            {
+                Some(VaultTransactionError {
+                    instruction_index: *instruction_index,
+                    error: vault_error_from_code(*code)?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Decode send and preflight simulation errors of a transaction made of instructions.
+    /// instructions may be empty when unknown, the error is then only decoded from preflight logs
+    pub fn from_solana_client_error(
+        error: &SolanaClientError,
+        instructions: &[Instruction],
+        vault_program_id: &Pubkey,
+    ) -> Option<Self> {
+        Self::from_transaction_error(
+            &error.get_transaction_error()?,
+            preflight_logs(error),
+            instructions,
+            vault_program_id,
+        )
+    }
+
+    pub fn from_client_error(
+        error: &ClientError,
+        instructions: &[Instruction],
+        vault_program_id: &Pubkey,
+    ) -> Option<Self> {
+        match error {
+            ClientError::SolanaClientError(error) => {
+                Self::from_solana_client_error(error, instructions, vault_program_id)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn from_anyhow(
+        error: &anyhow::Error,
+        instructions: &[Instruction],
+        vault_program_id: &Pubkey,
+    ) -> Option<Self> {
+        if let Some(error) = error.downcast_ref::<ClientError>() {
+            return Self::from_client_error(error, instructions, vault_program_id);
+        }
+        if let Some(error) = error.downcast_ref::<SolanaClientError>() {
+            return Self::from_solana_client_error(error, instructions, vault_program_id);
+        }
+        error.downcast_ref::<TransactionError>().and_then(|error| {
+            Self::from_transaction_error(error, None, instructions, vault_program_id)
+        })
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        is_retryable(self.error)
+    }
+}
+
+impl fmt::Display for VaultTransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) at instruction {}: {}{}",
+            self.error,
+            self.error.name(),
+            self.instruction_index,
+            error_hint(self.error),
+            if self.is_retryable() {
+                ", retryable"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// Whether error of a transaction made of instructions is an `ExceededSlippage` failure of the vault program
+pub fn is_exceeded_slippage(
+    error: &anyhow::Error,
+    instructions: &[Instruction],
+    vault_program_id: &Pubkey,
+) -> bool {
+    matches!(
+        VaultTransactionError::from_anyhow(error, instructions, vault_program_id),
+        Some(VaultTransactionError {
+            error: VaultError::ExceededSlippage,
+            ..
+        })
+    )
+}
+
+/// Map error of a transaction made of instructions to `SlippageExceeded` when the vault rejected it for slippage,
+/// other errors are returned as is. actual quotes the amount again and is only called for a slippage failure
+pub fn map_slippage_error(
+    error: anyhow::Error,
+    instructions: &[Instruction],
+    vault_program_id: &Pubkey,
+    side: SlippageSide,
+    expected: u64,
+    minimum: u64,
+    actual: impl FnOnce() -> Option<u64>,
+) -> anyhow::Error {
+    if !is_exceeded_slippage(&error, instructions, vault_program_id) {
+        return error;
+    }
+    SlippageExceeded {
+        side,
+        expected,
+        minimum,
+        actual: actual(),
+    }
+    .into()
+}
+
+/// Same as `map_slippage_error` for async clients, actual is only awaited for a slippage failure
+pub async fn map_slippage_error_async(
+    error: anyhow::Error,
+    instructions: &[Instruction],
+    vault_program_id: &Pubkey,
+    side: SlippageSide,
+    expected: u64,
+    minimum: u64,
+    actual: impl Future<Output = Option<u64>>,
+) -> anyhow::Error {
+    if !is_exceeded_slippage(&error, instructions, vault_program_id) {
+        return error;
+    }
+    SlippageExceeded {
+        side,
+        expected,
+        minimum,
+        actual: actual.await,
+    }
+    .into()
+}
+
+/// Which amount the slippage protects
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlippageSide {
+    /// lp minted by a deposit
+    Deposit,
+    /// token paid by a withdrawal
+    Withdraw,
+}
+
+/// `ExceededSlippage` failure with the amounts of the quote and of the vault state after the failure
+#[derive(Clone, Copy, Debug)]
+pub struct SlippageExceeded {
+    pub side: SlippageSide,
+    /// amount quoted before sending
+    pub expected: u64,
+    /// minimum amount passed to the instruction
+    pub minimum: u64,
+    /// amount quoted again after the failure, None if it could not be fetched
+    pub actual: Option<u64>,
+}
+
+impl fmt::Display for SlippageExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (action, unit) = match self.side {
+            SlippageSide::Deposit => ("deposit", "lp"),
+            SlippageSide::Withdraw => ("withdraw", "token"),
+        };
+        write!(
+            f,
+            "{} exceeded slippage: expected {} {}, minimum {} {}, ",
+            action, self.expected, unit, self.minimum, unit
+        )?;
+        match self.actual {
+            Some(actual) => write!(f, "vault now gives {} {}", actual, unit),
+            None => write!(f, "current amount unknown"),
+        }
+    }
+}
+
+impl std::error::Error for SlippageExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFFILIATE: &str = "GacY9YuN16HNRTy7ZWwULPccwvfFSBeNLuAQP7y38Du3";
+
+    /// Declaration order of VaultError. The match is exhaustive so a new variant
+    /// fails to compile here until it is also added to VAULT_ERRORS
+    fn declaration_index(error: VaultError) -> usize {
+        match error {
+            VaultError::VaultIsDisabled => 0,
+            VaultError::ExceededSlippage => 1,
+            VaultError::StrategyIsNotExisted => 2,
+            VaultError::UnAuthorized => 3,
+            VaultError::MathOverflow => 4,
+            VaultError::ProtocolIsNotSupported => 5,
+            VaultError::UnMatchReserve => 6,
+            VaultError::InvalidLockedProfitDegradation => 7,
+            VaultError::MaxStrategyReached => 8,
+            VaultError::StrategyExisted => 9,
+            VaultError::InvalidUnmintAmount => 10,
+            VaultError::InvalidAccountsForStrategy => 11,
+            VaultError::InvalidBump => 12,
+            VaultError::AmountMustGreaterThanZero => 13,
+            VaultError::MangoIsNotSupportedAnymore => 14,
+            VaultError::StrategyIsNotSupported => 15,
+            VaultError::PayAmountIsExeeced => 16,
+        }
+    }
+
+    fn custom_error(instruction_index: u8, error: VaultError) -> TransactionError {
+        TransactionError::InstructionError(
+            instruction_index,
+            InstructionError::Custom(u32::from(error)),
+        )
+    }
+
+    fn instruction(program_id: Pubkey) -> Instruction {
+        Instruction::new_with_bytes(program_id, &[], vec![])
+    }
+
+    #[test]
+    fn vault_errors_cover_every_variant() {
+        assert_eq!(
+            declaration_index(VaultError::PayAmountIsExeeced) + 1,
+            VAULT_ERRORS.len()
+        );
+        for (i, error) in VAULT_ERRORS.iter().enumerate() {
+            assert_eq!(declaration_index(*error), i, "{}", error.name());
+            assert_eq!(u32::from(*error), 6000 + i as u32, "{}", error.name());
+            let decoded = vault_error_from_code(6000 + i as u32).unwrap();
+            assert_eq!(decoded.name(), error.name());
+        }
+        assert!(vault_error_from_code(6000 + VAULT_ERRORS.len() as u32).is_none());
+    }
+
+    #[test]
+    fn custom_error_of_another_program_is_not_a_vault_error() {
+        let affiliate = Pubkey::from_str(AFFILIATE).unwrap();
+        let instructions = [instruction(mercurial_vault::ID), instruction(affiliate)];
+        let error = custom_error(1, VaultError::ExceededSlippage);
+
+        assert!(VaultTransactionError::from_transaction_error(
+            &error,
+            None,
+            &instructions,
+            &mercurial_vault::ID
+        )
+        .is_none());
+
+        let logs = vec![
+            format!("Program {} invoke [1]", AFFILIATE),
+            format!("Program {} failed: custom program error: 0x1771", AFFILIATE),
+        ];
+        assert!(VaultTransactionError::from_transaction_error(
+            &error,
+            Some(&logs),
+            &instructions,
+            &mercurial_vault::ID
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn vault_error_is_decoded_from_failed_instruction() {
+        let instructions = [instruction(mercurial_vault::ID)];
+        let error = custom_error(0, VaultError::ExceededSlippage);
+
+        let vault_error = VaultTransactionError::from_transaction_error(
+            &error,
+            None,
+            &instructions,
+            &mercurial_vault::ID,
+        )
+        .unwrap();
+        assert_eq!(vault_error.instruction_index, 0);
+        assert!(matches!(vault_error.error, VaultError::ExceededSlippage));
+    }
+
+    #[test]
+    fn slippage_error_is_mapped_with_actual_amount() {
+        let instructions = [instruction(mercurial_vault::ID)];
+        let error = anyhow::Error::from(custom_error(0, VaultError::ExceededSlippage));
+
+        let error = map_slippage_error(
+            error,
+            &instructions,
+            &mercurial_vault::ID,
+            SlippageSide::Withdraw,
+            100,
+            99,
+            || Some(98),
+        );
+        let slippage = error.downcast_ref::<SlippageExceeded>().unwrap();
+        assert_eq!(slippage.side, SlippageSide::Withdraw);
+        assert_eq!(
+            (slippage.expected, slippage.minimum, slippage.actual),
+            (100, 99, Some(98))
+        );
+    }
+
+    #[test]
+    fn other_errors_are_not_mapped_to_slippage() {
+        let instructions = [instruction(mercurial_vault::ID)];
+        let error = anyhow::Error::from(custom_error(0, VaultError::VaultIsDisabled));
+
+        let error = map_slippage_error(
+            error,
+            &instructions,
+            &mercurial_vault::ID,
+            SlippageSide::Deposit,
+            100,
+            99,
+            || panic!("actual amount is only quoted for a slippage failure"),
+        );
+        assert!(error.downcast_ref::<SlippageExceeded>().is_none());
+        assert!(error.downcast_ref::<TransactionError>().is_some());
+    }
+
+    #[test]
+    fn vault_error_raised_through_cpi_is_decoded_from_logs() {
+        let affiliate = Pubkey::from_str(AFFILIATE).unwrap();
+        let vault = mercurial_vault::ID.to_string();
+        let instructions = [instruction(affiliate)];
+        let error = custom_error(0, VaultError::ExceededSlippage);
+        let logs = vec![
+            format!("Program {} invoke [1]", AFFILIATE),
+            format!("Program {} invoke [2]", vault),
+            "Program log: AnchorError occurred. Error Code: ExceededSlippage.".to_string(),
+            format!("Program {} failed: custom program error: 0x1771", vault),
+            format!("Program {} failed: custom program error: 0x1771", AFFILIATE),
+        ];
+
+        assert_eq!(
+            failed_program_id(Some(&logs), 0, &instructions),
+            Some(mercurial_vault::ID)
+        );
+        let vault_error = VaultTransactionError::from_transaction_error(
+            &error,
+            Some(&logs),
+            &instructions,
+            &mercurial_vault::ID,
+        )
+        .unwrap();
+        assert!(matches!(vault_error.error, VaultError::ExceededSlippage));
+    }
+}
@@ -0,0 +1,198 @@
+use anchor_client::solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::Discriminator;
+use mercurial_vault::layout::*;
+use mercurial_vault::state::{Strategy, Vault};
+use mercurial_vault::strategy::base::StrategyType;
+
+fn memcmp(offset: usize, bytes: &[u8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, bytes))
+}
+
+/// Match vault accounts, combine with the vault_by_* filters
+pub fn vault_discriminator() -> RpcFilterType {
+    memcmp(0, &Vault::discriminator())
+}
+
+/// Match strategy accounts, combine with the strategy_by_* filters
+pub fn strategy_discriminator() -> RpcFilterType {
+    memcmp(0, &Strategy::discriminator())
+}
+
+pub fn vault_by_token_mint(token_mint: &Pubkey) -> RpcFilterType {
+    memcmp(VAULT_TOKEN_MINT_OFFSET, token_mint.as_ref())
+}
+
+pub fn vault_by_lp_mint(lp_mint: &Pubkey) -> RpcFilterType {
+    memcmp(VAULT_LP_MINT_OFFSET, lp_mint.as_ref())
+}
+
+pub fn vault_by_base(base: &Pubkey) -> RpcFilterType {
+    memcmp(VAULT_BASE_OFFSET, base.as_ref())
+}
+
+pub fn vault_by_admin(admin: &Pubkey) -> RpcFilterType {
+    memcmp(VAULT_ADMIN_OFFSET, admin.as_ref())
+}
+
+pub fn vault_by_operator(operator: &Pubkey) -> RpcFilterType {
+    memcmp(VAULT_OPERATOR_OFFSET, operator.as_ref())
+}
+
+pub fn strategy_by_vault(vault: &Pubkey) -> RpcFilterType {
+    memcmp(STRATEGY_VAULT_OFFSET, vault.as_ref())
+}
+
+pub fn strategy_by_reserve(reserve: &Pubkey) -> RpcFilterType {
+    memcmp(STRATEGY_RESERVE_OFFSET, reserve.as_ref())
+}
+
+pub fn strategy_by_type(strategy_type: StrategyType) -> RpcFilterType {
+    memcmp(STRATEGY_TYPE_OFFSET, &[strategy_type.discriminant()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::AccountSerialize;
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new_from_array([seed; 32])
+    }
+
+    /// Memcmp comparing bytes at offset, from the layout offset rather than the filter builder
+    fn expected(offset: usize, bytes: &[u8]) -> RpcFilterType {
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, bytes))
+    }
+
+    fn bytes_match(filter: &RpcFilterType, data: &[u8]) -> bool {
+        match filter {
+            RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(data),
+            _ => panic!("not a memcmp filter: {:?}", filter),
+        }
+    }
+
+    fn assert_matches(filter: &RpcFilterType, offset: usize, bytes: &[u8], data: &[u8]) {
+        assert_eq!(*filter, expected(offset, bytes));
+        assert!(bytes_match(filter, data));
+    }
+
+    fn vault_data() -> (Vault, Vec<u8>) {
+        let vault = Vault {
+            token_vault: key(1),
+            fee_vault: key(2),
+            token_mint: key(3),
+            lp_mint: key(4),
+            base: key(5),
+            admin: key(6),
+            operator: key(7),
+            ..Default::default()
+        };
+        let mut data = vec![];
+        vault.try_serialize(&mut data).unwrap();
+        (vault, data)
+    }
+
+    fn strategy_data(strategy_type: StrategyType) -> (Strategy, Vec<u8>) {
+        let strategy = Strategy {
+            reserve: key(1),
+            collateral_vault: key(2),
+            strategy_type,
+            current_liquidity: 100,
+            vault: key(3),
+            ..Default::default()
+        };
+        let mut data = vec![];
+        strategy.try_serialize(&mut data).unwrap();
+        (strategy, data)
+    }
+
+    #[test]
+    fn vault_filters_match_serialized_vault() {
+        let (vault, data) = vault_data();
+
+        assert_matches(&vault_discriminator(), 0, &Vault::discriminator(), &data);
+        for (filter, offset, expected) in [
+            (
+                vault_by_token_mint(&vault.token_mint),
+                VAULT_TOKEN_MINT_OFFSET,
+                vault.token_mint,
+            ),
+            (
+                vault_by_lp_mint(&vault.lp_mint),
+                VAULT_LP_MINT_OFFSET,
+                vault.lp_mint,
+            ),
+            (vault_by_base(&vault.base), VAULT_BASE_OFFSET, vault.base),
+            (
+                vault_by_admin(&vault.admin),
+                VAULT_ADMIN_OFFSET,
+                vault.admin,
+            ),
+            (
+                vault_by_operator(&vault.operator),
+                VAULT_OPERATOR_OFFSET,
+                vault.operator,
+            ),
+        ] {
+            assert_matches(&filter, offset, expected.as_ref(), &data);
+        }
+    }
+
+    #[test]
+    fn vault_filters_reject_other_keys() {
+        let (vault, data) = vault_data();
+        // the token mint at the offset of the lp mint, and no field holds key(42)
+        for filter in [
+            vault_by_lp_mint(&vault.token_mint),
+            vault_by_token_mint(&key(42)),
+            vault_by_admin(&vault.operator),
+            strategy_discriminator(),
+        ] {
+            assert!(!bytes_match(&filter, &data));
+        }
+    }
+
+    #[test]
+    fn strategy_filters_match_serialized_strategy() {
+        let (strategy, data) = strategy_data(StrategyType::Marginfi);
+
+        assert_matches(
+            &strategy_discriminator(),
+            0,
+            &Strategy::discriminator(),
+            &data,
+        );
+        assert_matches(
+            &strategy_by_vault(&strategy.vault),
+            STRATEGY_VAULT_OFFSET,
+            strategy.vault.as_ref(),
+            &data,
+        );
+        assert_matches(
+            &strategy_by_reserve(&strategy.reserve),
+            STRATEGY_RESERVE_OFFSET,
+            strategy.reserve.as_ref(),
+            &data,
+        );
+    }
+
+    #[test]
+    fn strategy_by_type_matches_known_and_unknown_types() {
+        for (strategy_type, discriminant) in [
+            (StrategyType::Marginfi, 11),
+            (StrategyType::Unknown(200), 200),
+        ] {
+            let (_, data) = strategy_data(strategy_type);
+            assert_matches(
+                &strategy_by_type(strategy_type),
+                STRATEGY_TYPE_OFFSET,
+                &[discriminant],
+                &data,
+            );
+
+            let (_, other_data) = strategy_data(StrategyType::Vault);
+            assert!(!bytes_match(&strategy_by_type(strategy_type), &other_data));
+        }
+    }
+}
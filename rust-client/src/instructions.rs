@@ -0,0 +1,560 @@
+//! Instruction builders which do not touch the network, to compose vault actions into custom transactions
+use crate::withdraw_planner::{StrategyWithReserve, WithdrawPlan};
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::solana_program::system_instruction;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use anyhow::{anyhow, Result};
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::state::Vault;
+use mercurial_vault::strategy::remaining_accounts::get_remaining_accounts_builder;
+
+/// Instructions of a vault action in execution order, with the accounts which must sign
+#[derive(Clone, Debug)]
+pub struct VaultInstructions {
+    /// e.g. creating the user token accounts
+    pub pre_instructions: Vec<Instruction>,
+    pub instruction: Instruction,
+    /// e.g. closing a temporary token account
+    pub post_instructions: Vec<Instruction>,
+    pub signers: Vec<Pubkey>,
+}
+
+impl VaultInstructions {
+    pub(crate) fn new(
+        payer: Pubkey,
+        user: Pubkey,
+        pre_instructions: Vec<Instruction>,
+        instruction: Instruction,
+    ) -> Self {
+        let mut signers = vec![payer];
+        if user != payer {
+            signers.push(user);
+        }
+        VaultInstructions {
+            pre_instructions,
+            instruction,
+            post_instructions: vec![],
+            signers,
+        }
+    }
+
+    /// Close the wrapped SOL account user_token after the vault instruction when the vault token is the native mint
+    pub(crate) fn unwrap_sol_after(
+        mut self,
+        vault_state: &Vault,
+        user: &Pubkey,
+        user_token: &Pubkey,
+        unwrap: bool,
+    ) -> Self {
+        if unwrap && is_native_mint(&vault_state.token_mint) {
+            self.post_instructions.push(unwrap_sol(user, user_token));
+        }
+        self
+    }
+
+    /// Pre-instructions, the vault instruction and post-instructions
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.pre_instructions
+            .iter()
+            .chain(std::iter::once(&self.instruction))
+            .chain(self.post_instructions.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Whether mint is wrapped SOL, whose vault is funded from native SOL by the builders
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::id()
+}
+
+/// Move amount lamports of user to its wrapped SOL account user_token and sync its token balance, same as `wrapSOLInstruction` of ts-client
+pub fn wrap_sol(user: &Pubkey, user_token: &Pubkey, amount: u64) -> Vec<Instruction> {
+    vec![
+        system_instruction::transfer(user, user_token, amount),
+        spl_token::instruction::sync_native(&spl_token::id(), user_token).unwrap(),
+    ]
+}
+
+/// Close the wrapped SOL account user_token, every lamport goes back to user, same as `unwrapSOLInstruction` of ts-client
+pub fn unwrap_sol(user: &Pubkey, user_token: &Pubkey) -> Instruction {
+    spl_token::instruction::close_account(&spl_token::id(), user_token, user, user, &[]).unwrap()
+}
+
+/// Token and lp associated token accounts of user, with idempotent instructions creating them
+pub fn user_token_accounts(
+    payer: &Pubkey,
+    user: &Pubkey,
+    vault_state: &Vault,
+) -> (Pubkey, Pubkey, Vec<Instruction>) {
+    let mints = [vault_state.token_mint, vault_state.lp_mint];
+    let instructions = mints
+        .iter()
+        .map(|mint| {
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                payer,
+                user,
+                mint,
+                &spl_token::id(),
+            )
+        })
+        .collect();
+    let [user_token, user_lp] =
+        mints.map(|mint| spl_associated_token_account::get_associated_token_address(user, &mint));
+    (user_token, user_lp, instructions)
+}
+
+/// Deposit token_amount from the associated token account of user.
+/// For a wrapped SOL vault, token_amount lamports of user are wrapped first
+pub fn deposit(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    vault_state: &Vault,
+    payer: Pubkey,
+    user: Pubkey,
+    token_amount: u64,
+    minimum_lp_token_amount: u64,
+) -> VaultInstructions {
+    let (user_token, user_lp, mut pre_instructions) =
+        user_token_accounts(&payer, &user, vault_state);
+    if is_native_mint(&vault_state.token_mint) {
+        pre_instructions.extend(wrap_sol(&user, &user_token, token_amount));
+    }
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts: mercurial_vault::accounts::DepositWithdrawLiquidity {
+            vault,
+            token_vault: vault_state.token_vault,
+            lp_mint: vault_state.lp_mint,
+            user_token,
+            user_lp,
+            user,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: mercurial_vault::instruction::Deposit {
+            token_amount,
+            minimum_lp_token_amount,
+        }
+        .data(),
+    };
+    VaultInstructions::new(payer, user, pre_instructions, instruction)
+}
+
+/// Withdraw from the reserve of the vault, fails on chain when token_vault does not cover the amount.
+/// For a wrapped SOL vault, the wrapped SOL account of user is closed afterwards unless unwrap_sol is false
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw(
+    program_id: &Pubkey,
+    vault: Pubkey,
+    vault_state: &Vault,
+    payer: Pubkey,
+    user: Pubkey,
+    unmint_amount: u64,
+    min_out_amount: u64,
+    unwrap_sol: bool,
+) -> VaultInstructions {
+    let (user_token, user_lp, pre_instructions) = user_token_accounts(&payer, &user, vault_state);
+    let instruction = Instruction {
+        program_id: *program_id,
+        accounts: mercurial_vault::accounts::DepositWithdrawLiquidity {
+            vault,
+            token_vault: vault_state.token_vault,
+            lp_mint: vault_state.lp_mint,
+            user_token,
+            user_lp,
+            user,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(None),
+        data: mercurial_vault::instruction::Withdraw {
+            unmint_amount,
+            min_out_amount,
+        }
+        .data(),
+    };
+    VaultInstructions::new(payer, user, pre_instructions, instruction).unwrap_sol_after(
+        vault_state,
+        &user,
+        &user_token,
+        unwrap_sol,
+    )
+}
+
+/// Accounts of `withdraw_directly_from_strategy`, including the remaining accounts of the strategy
+pub fn withdraw_directly_from_strategy_accounts(
+    config: &ClusterConfig,
+    vault: Pubkey,
+    vault_state: &Vault,
+    strategy: &StrategyWithReserve,
+    user: Pubkey,
+    user_token: Pubkey,
+    user_lp: Pubkey,
+) -> Result<Vec<AccountMeta>> {
+    let strategy_state = &strategy.state;
+    let strategy_program = config
+        .strategy_programs
+        .get(strategy_state.strategy_type)
+        .ok_or_else(|| {
+            anyhow!(
+                "Strategy {} can not be withdrawn from",
+                strategy_state.strategy_type
+            )
+        })?;
+    let mut accounts = mercurial_vault::accounts::WithdrawDirectlyFromStrategy {
+        vault,
+        strategy: strategy.pubkey,
+        reserve: strategy_state.reserve,
+        strategy_program,
+        collateral_vault: strategy_state.collateral_vault,
+        token_vault: vault_state.token_vault,
+        lp_mint: vault_state.lp_mint,
+        fee_vault: vault_state.fee_vault,
+        user_token,
+        user_lp,
+        user,
+        token_program: spl_token::id(),
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        get_remaining_accounts_builder(strategy_state.strategy_type)?.remaining_accounts(
+            config,
+            &strategy.pubkey,
+            strategy_state,
+            &strategy.reserve_data,
+        )?,
+    );
+    Ok(accounts)
+}
+
+/// Withdraw through strategy, topping up the reserve from the strategy when needed.
+/// For a wrapped SOL vault, the wrapped SOL account of user is closed afterwards unless unwrap_sol is false
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_directly_from_strategy(
+    config: &ClusterConfig,
+    vault: Pubkey,
+    vault_state: &Vault,
+    strategy: &StrategyWithReserve,
+    payer: Pubkey,
+    user: Pubkey,
+    unmint_amount: u64,
+    min_out_amount: u64,
+    unwrap_sol: bool,
+) -> Result<VaultInstructions> {
+    let (user_token, user_lp, pre_instructions) = user_token_accounts(&payer, &user, vault_state);
+    let instruction = Instruction {
+        program_id: config.vault_program_id,
+        accounts: withdraw_directly_from_strategy_accounts(
+            config,
+            vault,
+            vault_state,
+            strategy,
+            user,
+            user_token,
+            user_lp,
+        )?,
+        data: mercurial_vault::instruction::WithdrawDirectlyFromStrategy {
+            unmint_amount,
+            min_out_amount,
+        }
+        .data(),
+    };
+    Ok(
+        VaultInstructions::new(payer, user, pre_instructions, instruction).unwrap_sol_after(
+            vault_state,
+            &user,
+            &user_token,
+            unwrap_sol,
+        ),
+    )
+}
+
+/// Instructions of a plan of `WithdrawPlanner` made for the associated token accounts of user.
+/// For a wrapped SOL vault, the wrapped SOL account of user is closed afterwards unless unwrap_sol is false
+pub fn withdraw_with_plan(
+    plan: &WithdrawPlan,
+    vault_state: &Vault,
+    payer: Pubkey,
+    user: Pubkey,
+    min_out_amount: u64,
+    unwrap_sol: bool,
+) -> VaultInstructions {
+    let (user_token, _, pre_instructions) = user_token_accounts(&payer, &user, vault_state);
+    VaultInstructions::new(
+        payer,
+        user,
+        pre_instructions,
+        plan.instruction(min_out_amount),
+    )
+    .unwrap_sol_after(vault_state, &user, &user_token, unwrap_sol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mercurial_vault::state::Strategy;
+    use mercurial_vault::strategy::base::StrategyType;
+
+    fn vault_state(token_mint: Pubkey) -> Vault {
+        Vault {
+            token_mint,
+            token_vault: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            fee_vault: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    /// pubkey, is_writable and is_signer of each account
+    fn flags(accounts: &[AccountMeta]) -> Vec<(Pubkey, bool, bool)> {
+        accounts
+            .iter()
+            .map(|account| (account.pubkey, account.is_writable, account.is_signer))
+            .collect()
+    }
+
+    fn ata(user: &Pubkey, mint: &Pubkey) -> Pubkey {
+        spl_associated_token_account::get_associated_token_address(user, mint)
+    }
+
+    #[test]
+    fn deposit_and_withdraw_accounts_follow_idl() {
+        let program_id = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state(Pubkey::new_unique());
+        let (payer, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // vault, tokenVault, lpMint, userToken, userLp, user (signer), tokenProgram
+        let expected = vec![
+            (vault, true, false),
+            (vault_state.token_vault, true, false),
+            (vault_state.lp_mint, true, false),
+            (ata(&user, &vault_state.token_mint), true, false),
+            (ata(&user, &vault_state.lp_mint), true, false),
+            (user, false, true),
+            (spl_token::id(), false, false),
+        ];
+
+        let deposit = deposit(&program_id, vault, &vault_state, payer, user, 10, 9);
+        assert_eq!(deposit.instruction.program_id, program_id);
+        assert_eq!(flags(&deposit.instruction.accounts), expected);
+        assert_eq!(
+            deposit.instruction.data,
+            mercurial_vault::instruction::Deposit {
+                token_amount: 10,
+                minimum_lp_token_amount: 9
+            }
+            .data()
+        );
+
+        let withdraw = withdraw(&program_id, vault, &vault_state, payer, user, 10, 9, true);
+        assert_eq!(flags(&withdraw.instruction.accounts), expected);
+        assert_eq!(
+            withdraw.instruction.data,
+            mercurial_vault::instruction::Withdraw {
+                unmint_amount: 10,
+                min_out_amount: 9
+            }
+            .data()
+        );
+    }
+
+    #[test]
+    fn withdraw_directly_from_strategy_accounts_follow_idl() {
+        let config = ClusterConfig::mainnet();
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state(Pubkey::new_unique());
+        let user = Pubkey::new_unique();
+        let strategy = StrategyWithReserve {
+            pubkey: Pubkey::new_unique(),
+            state: Strategy {
+                reserve: Pubkey::new_unique(),
+                collateral_vault: Pubkey::new_unique(),
+                strategy_type: StrategyType::SolendWithoutLM,
+                ..Default::default()
+            },
+            reserve_data: vec![0u8; 619],
+        };
+
+        let instructions = withdraw_directly_from_strategy(
+            &config,
+            vault,
+            &vault_state,
+            &strategy,
+            user,
+            user,
+            10,
+            9,
+            true,
+        )
+        .unwrap();
+        let accounts = flags(&instructions.instruction.accounts);
+        // vault, strategy, reserve, strategyProgram, collateralVault, tokenVault, lpMint, feeVault,
+        // userToken, userLp, user (signer), tokenProgram, then the remaining accounts of the strategy
+        assert_eq!(
+            accounts[..12].to_vec(),
+            vec![
+                (vault, true, false),
+                (strategy.pubkey, true, false),
+                (strategy.state.reserve, true, false),
+                (config.strategy_programs.solend, false, false),
+                (strategy.state.collateral_vault, true, false),
+                (vault_state.token_vault, true, false),
+                (vault_state.lp_mint, true, false),
+                (vault_state.fee_vault, true, false),
+                (ata(&user, &vault_state.token_mint), true, false),
+                (ata(&user, &vault_state.lp_mint), true, false),
+                (user, false, true),
+                (spl_token::id(), false, false),
+            ]
+        );
+        assert_eq!(accounts.len(), 12 + 6);
+    }
+
+    #[test]
+    fn signers_are_deduplicated() {
+        let program_id = Pubkey::new_unique();
+        let vault_state = vault_state(Pubkey::new_unique());
+        let (payer, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let same = deposit(
+            &program_id,
+            Pubkey::new_unique(),
+            &vault_state,
+            user,
+            user,
+            1,
+            0,
+        );
+        assert_eq!(same.signers, vec![user]);
+        let different = deposit(
+            &program_id,
+            Pubkey::new_unique(),
+            &vault_state,
+            payer,
+            user,
+            1,
+            0,
+        );
+        assert_eq!(different.signers, vec![payer, user]);
+    }
+
+    #[test]
+    fn instructions_are_pre_main_post() {
+        let instruction = |seed: u8| Instruction {
+            program_id: Pubkey::new_from_array([seed; 32]),
+            accounts: vec![],
+            data: vec![],
+        };
+        let user = Pubkey::new_unique();
+        let mut vault_instructions = VaultInstructions::new(
+            user,
+            user,
+            vec![instruction(1), instruction(2)],
+            instruction(3),
+        );
+        vault_instructions.post_instructions.push(instruction(4));
+
+        let program_ids: Vec<_> = vault_instructions
+            .instructions()
+            .iter()
+            .map(|instruction| instruction.program_id)
+            .collect();
+        assert_eq!(
+            program_ids,
+            (1..=4)
+                .map(|seed| Pubkey::new_from_array([seed; 32]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn native_mint_deposit_wraps_sol_after_creating_accounts() {
+        let vault_state = vault_state(spl_token::native_mint::id());
+        let user = Pubkey::new_unique();
+        let user_token = ata(&user, &vault_state.token_mint);
+
+        let deposit = deposit(
+            &Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            &vault_state,
+            user,
+            user,
+            10,
+            9,
+        );
+        let (_, _, create_accounts) = user_token_accounts(&user, &user, &vault_state);
+        let mut expected = create_accounts;
+        expected.push(system_instruction::transfer(&user, &user_token, 10));
+        expected.push(spl_token::instruction::sync_native(&spl_token::id(), &user_token).unwrap());
+        assert_eq!(deposit.pre_instructions, expected);
+        assert!(deposit.post_instructions.is_empty());
+    }
+
+    #[test]
+    fn native_mint_withdraw_closes_account_only_when_unwrapping() {
+        let vault_state = vault_state(spl_token::native_mint::id());
+        let user = Pubkey::new_unique();
+        let user_token = ata(&user, &vault_state.token_mint);
+        let withdraw = |unwrap_sol: bool| {
+            withdraw(
+                &Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                &vault_state,
+                user,
+                user,
+                10,
+                9,
+                unwrap_sol,
+            )
+        };
+
+        let unwrapped = withdraw(true);
+        assert_eq!(
+            unwrapped.post_instructions,
+            vec![spl_token::instruction::close_account(
+                &spl_token::id(),
+                &user_token,
+                &user,
+                &user,
+                &[]
+            )
+            .unwrap()]
+        );
+        assert_eq!(unwrapped.pre_instructions.len(), 2);
+        assert!(withdraw(false).post_instructions.is_empty());
+    }
+
+    #[test]
+    fn other_mints_neither_wrap_nor_unwrap() {
+        let vault_state = vault_state(Pubkey::new_unique());
+        let user = Pubkey::new_unique();
+        let (_, _, create_accounts) = user_token_accounts(&user, &user, &vault_state);
+
+        let deposit = deposit(
+            &Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            &vault_state,
+            user,
+            user,
+            10,
+            9,
+        );
+        assert_eq!(deposit.pre_instructions, create_accounts);
+        assert!(deposit.post_instructions.is_empty());
+
+        let withdraw = withdraw(
+            &Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            &vault_state,
+            user,
+            user,
+            10,
+            9,
+            true,
+        );
+        assert_eq!(withdraw.pre_instructions, create_accounts);
+        assert!(withdraw.post_instructions.is_empty());
+    }
+}
@@ -1 +1,11 @@
+pub mod admin;
+pub mod affiliate;
+pub mod async_client;
+pub mod discovery;
+pub mod error;
+pub mod filters;
+pub mod instructions;
 pub mod user;
+pub mod utils;
+pub mod vault_client;
+pub mod withdraw_planner;
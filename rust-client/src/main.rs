@@ -1,22 +1,22 @@
-mod user;
-mod utils;
 use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
 use anchor_client::solana_sdk::pubkey::Pubkey;
 use anchor_client::solana_sdk::signature::Signer;
 use anchor_client::solana_sdk::signature::{read_keypair_file, Keypair};
 use anchor_client::Client;
 use anchor_client::Cluster;
-use anchor_lang::solana_program::clock::Clock;
-use anchor_lang::solana_program::sysvar;
-use anyhow::Result;
-use bincode::deserialize;
+use anyhow::{anyhow, Result};
 use clap::Parser;
-use mercurial_vault::get_base_key;
-use std::convert::TryFrom;
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::utils::VaultAddresses;
+use rust_client::discovery::{discover_vaults, VaultFilter};
+use rust_client::error::{SlippageExceeded, VaultTransactionError};
+use rust_client::user::*;
+use rust_client::utils;
+use rust_client::utils::get_current_node_clock_time;
+use rust_client::withdraw_planner::{WithdrawPlanner, WithdrawPlannerState};
 use std::ops::Deref;
 use std::rc::Rc;
 use std::str::FromStr;
-use user::*;
 
 #[derive(Default, Debug, Parser)]
 pub struct ConfigOverride {
@@ -37,20 +37,47 @@ pub struct ConfigOverride {
 
     #[clap(global = true, long = "provider.base")]
     pub base: Option<String>,
+
+    /// Vault config preset: mainnet, devnet or staging. Defaults to the preset of the cluster
+    #[clap(global = true, long = "provider.config")]
+    pub config: Option<ClusterConfig>,
 }
 
 #[derive(Debug, Parser)]
 pub enum Command {
     Show {},
     GetUnlockedAmount {},
+    /// List vaults of the program, filtered by --provider.token_mint when set
+    ListVaults {
+        #[clap(long)]
+        admin: Option<String>,
+        #[clap(long)]
+        operator: Option<String>,
+    },
     #[clap(flatten)]
     User(UserCommand),
 }
 
 #[derive(Debug, Parser)]
 pub enum UserCommand {
-    Deposit { token_amount: u64 },
-    Withdraw { unmint_amount: u64 },
+    Deposit {
+        token_amount: u64,
+        /// Max difference between the quoted and the minted lp, in basis points
+        #[clap(long, default_value = "50")]
+        slippage_bps: u64,
+    },
+    Withdraw {
+        unmint_amount: u64,
+        /// Max difference between the quoted and the received token, in basis points
+        #[clap(long, default_value = "50")]
+        slippage_bps: u64,
+        /// Keep the wrapped SOL account of a SOL vault instead of closing it
+        #[clap(long)]
+        keep_wsol: bool,
+        /// Split the withdrawal across strategies and transactions when no single strategy has enough liquidity
+        #[clap(long)]
+        multi_leg: bool,
+    },
 }
 
 #[derive(Parser)]
@@ -60,9 +87,41 @@ pub struct Opts {
     #[clap(subcommand)]
     pub command: Command,
 }
-fn main() -> Result<()> {
+fn main() {
     let opts = Opts::parse();
+    let vault_program_id = cluster_config(&opts.cfg_override).vault_program_id;
 
+    if let Err(err) = run(opts) {
+        if let Some(slippage) = err.downcast_ref::<SlippageExceeded>() {
+            eprintln!("Error: {}", slippage);
+        } else {
+            // the failed instructions are not known here, vault errors are decoded from the preflight logs
+            match VaultTransactionError::from_anyhow(&err, &[], &vault_program_id) {
+                Some(vault_error) => eprintln!("Error: {}", vault_error),
+                None => eprintln!("Error: {:?}", err),
+            }
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Vault config of the cluster, with the program id override applied
+fn cluster_config(cfg_override: &ConfigOverride) -> ClusterConfig {
+    let mut config = match cfg_override.config {
+        Some(config) => config,
+        None => match cfg_override.cluster {
+            Some(Cluster::Devnet) | None => ClusterConfig::devnet(),
+            Some(_) => ClusterConfig::mainnet(),
+        },
+    };
+    if let Some(program_id) = &cfg_override.program_id {
+        config.vault_program_id = Pubkey::from_str(program_id).unwrap();
+    }
+    config
+}
+
+fn run(opts: Opts) -> Result<()> {
+    let config = cluster_config(&opts.cfg_override);
     let payer = match opts.cfg_override.wallet {
         Some(wallet) => read_keypair_file(wallet).expect("Requires a keypair file"),
         None => default_keypair(),
@@ -78,24 +137,23 @@ fn main() -> Result<()> {
         CommitmentConfig::processed(),
     );
 
-    let program_id = match opts.cfg_override.program_id {
-        Some(program_id) => Pubkey::from_str(&program_id).unwrap(),
-        None => mercurial_vault::id(),
-    };
+    let program_id = config.vault_program_id;
 
     let program_client = client.program(program_id)?;
 
-    let token_mint = match opts.cfg_override.token_mint {
-        Some(token_mint) => Pubkey::from_str(&token_mint).unwrap(),
-        None => Pubkey::default(),
-    };
+    let token_mint_override = opts
+        .cfg_override
+        .token_mint
+        .map(|token_mint| Pubkey::from_str(&token_mint).unwrap());
+    let token_mint = token_mint_override.unwrap_or_default();
 
     let base = match opts.cfg_override.base {
         Some(base) => Pubkey::from_str(&base).unwrap(),
-        None => get_base_key(),
+        None => config.base_key,
     };
 
-    let (vault, _) = mercurial_vault::utils::derive_vault_address(token_mint, base);
+    let (vault, _) =
+        VaultAddresses::derive_with_program_id(token_mint, base, &config.vault_program_id).vault;
 
     println!("ProgramID {}", program_id);
     println!("TOKEN MINT {}", token_mint);
@@ -104,15 +162,50 @@ fn main() -> Result<()> {
 
     // Fee payer is the admin
     match opts.command {
-        Command::Show {} => show(&program_client, vault)?,
+        Command::Show {} => show(&program_client, &config, vault)?,
         Command::GetUnlockedAmount {} => get_unlocked_amount(&program_client, vault, &payer)?,
+        Command::ListVaults { admin, operator } => {
+            let filter = VaultFilter {
+                token_mint: token_mint_override,
+                base: None,
+                admin: admin
+                    .map(|admin| Pubkey::from_str(&admin))
+                    .transpose()
+                    .map_err(|err| anyhow!("invalid --admin: {}", err))?,
+                operator: operator
+                    .map(|operator| Pubkey::from_str(&operator))
+                    .transpose()
+                    .map_err(|err| anyhow!("invalid --operator: {}", err))?,
+            };
+            list_vaults(&program_client, &config, &filter)?
+        }
         Command::User(user) => match user {
-            UserCommand::Deposit { token_amount } => {
-                deposit(&program_client, token_mint, base, token_amount)?
-            }
-            UserCommand::Withdraw { unmint_amount } => {
-                withdraw(&program_client, token_mint, base, unmint_amount)?
-            }
+            UserCommand::Deposit {
+                token_amount,
+                slippage_bps,
+            } => deposit(
+                &program_client,
+                &config,
+                token_mint,
+                base,
+                token_amount,
+                slippage_bps,
+            )?,
+            UserCommand::Withdraw {
+                unmint_amount,
+                slippage_bps,
+                keep_wsol,
+                multi_leg,
+            } => withdraw(
+                &program_client,
+                &config,
+                token_mint,
+                base,
+                unmint_amount,
+                slippage_bps,
+                !keep_wsol,
+                multi_leg,
+            )?,
         },
     };
 
@@ -121,6 +214,7 @@ fn main() -> Result<()> {
 
 fn show<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
+    config: &ClusterConfig,
     vault: Pubkey,
 ) -> Result<()> {
     let vault_data: mercurial_vault::state::Vault = program_client.account(vault)?;
@@ -141,30 +235,81 @@ fn show<C: Deref<Target = impl Signer> + Clone>(
 
     println!("TOKEN AMOUNT: {}", token_data.amount);
 
+    let strategies = utils::get_strategies_with_reserve(program_client, &vault_data)?;
     let mut strategy_amount = 0u64;
-    for (_i, &strategy_pubkey) in vault_data.strategies.iter().enumerate() {
-        if strategy_pubkey != Pubkey::default() {
-            let strategy_state: mercurial_vault::state::Strategy =
-                program_client.account(strategy_pubkey)?;
+    for strategy in strategies.iter() {
+        println!("STRATEGY DATA {}: {:#?}", strategy.pubkey, strategy.state);
 
-            println!("STRATEGY DATA {}: {:#?}", strategy_pubkey, strategy_state);
-
-            strategy_amount += strategy_state.current_liquidity;
+        strategy_amount += strategy.state.current_liquidity;
+    }
+    for valuation in utils::get_strategy_valuations(program_client, config, &strategies)? {
+        println!(
+            "STRATEGY {} BOOKED: {}, ACTUAL: {}, UNREALIZED: {}",
+            valuation.pubkey,
+            valuation.booked_liquidity,
+            valuation
+                .actual_liquidity
+                .map_or_else(|| "unknown".to_string(), |amount| amount.to_string()),
+            valuation
+                .unrealized_yield()
+                .map_or_else(|| "unknown".to_string(), |amount| amount.to_string())
+        );
+    }
+    let planner_state = WithdrawPlannerState {
+        token_vault_amount: token_data.amount,
+        strategies,
+        current_time: current_timestamp,
+        lp_supply: token_mint.supply,
+    };
+    let withdrawable = WithdrawPlanner::new(config, vault, &vault_data, &planner_state)
+        .withdrawable_liquidity()?;
+    for strategy in withdrawable.strategies.iter() {
+        if let Some(reserve_error) = &strategy.reserve_error {
+            eprintln!(
+                "warning: reserve of strategy {} can not be decoded, strategy is not withdrawable: {}",
+                strategy.pubkey, reserve_error
+            );
         }
+        println!(
+            "STRATEGY {} WITHDRAWABLE: {}, RESERVE AVAILABLE: {}",
+            strategy.pubkey,
+            strategy.withdrawable_amount,
+            strategy
+                .reserve_available_liquidity
+                .map_or_else(|| "unknown".to_string(), |amount| amount.to_string())
+        );
     }
+    println!(
+        "MAX INSTANT WITHDRAW: {}, TOTAL WITHDRAWABLE: {}",
+        withdrawable.max_instant_withdraw(),
+        withdrawable.total_withdrawable()
+    );
     assert_eq!(vault_data.total_amount, token_data.amount + strategy_amount);
     println!("Ok");
     Ok(())
 }
 
-pub fn get_current_node_clock_time<C: Deref<Target = impl Signer> + Clone>(
+fn list_vaults<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
-) -> Result<u64> {
-    let rpc = program_client.rpc();
-    let clock_account = rpc.get_account(&sysvar::clock::id())?;
-    let clock = deserialize::<Clock>(&clock_account.data)?;
-    let current_time = u64::try_from(clock.unix_timestamp)?;
-    Ok(current_time)
+    config: &ClusterConfig,
+    filter: &VaultFilter,
+) -> Result<()> {
+    let vaults = discover_vaults(program_client, config, filter)?;
+    for vault in vaults.iter() {
+        println!(
+            "VAULT {} {:?} TOKEN MINT {} TVL {} LP SUPPLY {} STRATEGIES {}",
+            vault.pubkey,
+            vault.kind,
+            vault.token_mint(),
+            vault.tvl(),
+            vault
+                .lp_supply
+                .map_or_else(|| "unknown".to_string(), |supply| supply.to_string()),
+            vault.strategy_count()
+        );
+    }
+    println!("{} vaults", vaults.len());
+    Ok(())
 }
 
 fn get_unlocked_amount<C: Deref<Target = impl Signer> + Clone>(
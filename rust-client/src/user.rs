@@ -1,94 +1,201 @@
-use anchor_client::solana_sdk::signature::Signer;
+use crate::error::{map_slippage_error, SlippageSide};
+use crate::instructions;
+use crate::utils::{
+    apply_slippage, get_current_node_clock_time, get_strategies_with_reserve, quote_deposit_now,
+    quote_withdraw_now, request_with_instructions,
+};
+use crate::withdraw_planner::{WithdrawPlan, WithdrawPlanner, WithdrawPlannerState};
+use anchor_client::solana_sdk::signature::{Signature, Signer};
 use anchor_client::solana_sdk::signer::keypair::Keypair;
 use anchor_client::solana_sdk::system_instruction;
 use anchor_lang::solana_program::program_pack::Pack;
 use anchor_lang::solana_program::pubkey::Pubkey;
 use anchor_spl::token::spl_token;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::utils::VaultAddresses;
 use std::ops::Deref;
 
 pub fn deposit<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
+    config: &ClusterConfig,
     token_mint: Pubkey,
     base: Pubkey,
     token_amount: u64,
+    slippage_bps: u64,
 ) -> Result<()> {
     println!("deposit {}", token_amount);
 
-    let (vault, _vault_bump) = mercurial_vault::utils::derive_vault_address(token_mint, base);
-
-    let (token_vault, _token_vault_bump) =
-        mercurial_vault::utils::derive_token_vault_address(vault);
+    let (vault, _vault_bump) =
+        VaultAddresses::derive_with_program_id(token_mint, base, &config.vault_program_id).vault;
 
     let vault_state: mercurial_vault::state::Vault = program_client.account(vault)?;
     let lp_mint = vault_state.lp_mint;
 
-    let user_token = get_or_create_ata(program_client, token_mint, program_client.payer())?;
-    let user_lp = get_or_create_ata(program_client, lp_mint, program_client.payer())?;
-
-    let builder = program_client
-        .request()
-        .accounts(mercurial_vault::accounts::DepositWithdrawLiquidity {
-            vault,
-            token_vault,
-            lp_mint,
-            user_token,
-            user_lp,
-            user: program_client.payer(),
-            token_program: spl_token::id(),
-        })
-        .args(mercurial_vault::instruction::Deposit {
-            token_amount,
-            minimum_lp_token_amount: 0,
-        });
+    let lp_mint_state: anchor_spl::token::Mint = program_client.account(lp_mint)?;
+    let current_time = get_current_node_clock_time(program_client)?;
+    let quote = vault_state
+        .quote_deposit(current_time, token_amount, lp_mint_state.supply)
+        .ok_or_else(|| anyhow!("Cannot quote deposit"))?;
+    let minimum_lp_token_amount = apply_slippage(quote.lp_amount, slippage_bps)?;
+    println!(
+        "expected lp {}, minimum lp {}, rounding loss {}",
+        quote.lp_amount, minimum_lp_token_amount, quote.rounding_loss
+    );
 
-    let signature = builder.send()?;
+    let payer = program_client.payer();
+    let instructions = instructions::deposit(
+        &config.vault_program_id,
+        vault,
+        &vault_state,
+        payer,
+        payer,
+        token_amount,
+        minimum_lp_token_amount,
+    );
+    let instructions = instructions.instructions();
+    let builder = request_with_instructions(program_client, &instructions);
+
+    let signature = builder.send().map_err(|error| {
+        map_slippage_error(
+            error.into(),
+            &instructions,
+            &program_client.id(),
+            SlippageSide::Deposit,
+            quote.lp_amount,
+            minimum_lp_token_amount,
+            || quote_deposit_now(program_client, vault, token_amount).ok(),
+        )
+    })?;
     println!("{}", signature);
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn withdraw<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
+    config: &ClusterConfig,
     token_mint: Pubkey,
     base: Pubkey,
     unmint_amount: u64,
+    slippage_bps: u64,
+    unwrap_sol: bool,
+    multi_leg: bool,
 ) -> Result<()> {
     println!("withdraw {} lp token", unmint_amount);
 
-    let (vault, _vault_bump) = mercurial_vault::utils::derive_vault_address(token_mint, base);
-
-    let (token_vault, _token_vault_bump) =
-        mercurial_vault::utils::derive_token_vault_address(vault);
+    let (vault, _vault_bump) =
+        VaultAddresses::derive_with_program_id(token_mint, base, &config.vault_program_id).vault;
 
     let vault_state: mercurial_vault::state::Vault = program_client.account(vault)?;
     let lp_mint = vault_state.lp_mint;
 
-    let user_token = get_or_create_ata(program_client, token_mint, program_client.payer())?;
-    let user_lp = get_or_create_ata(program_client, lp_mint, program_client.payer())?;
-
-    let builder = program_client
-        .request()
-        .accounts(mercurial_vault::accounts::DepositWithdrawLiquidity {
-            vault,
-            token_vault,
-            lp_mint,
-            user_token,
-            user_lp,
-            user: program_client.payer(),
-            token_program: spl_token::id(),
-        })
-        .args(mercurial_vault::instruction::Withdraw {
-            unmint_amount,
-            min_out_amount: 0,
+    let token_vault: anchor_spl::token::TokenAccount =
+        program_client.account(vault_state.token_vault)?;
+    let lp_mint_state: anchor_spl::token::Mint = program_client.account(lp_mint)?;
+    let current_time = get_current_node_clock_time(program_client)?;
+
+    let planner_state = WithdrawPlannerState {
+        token_vault_amount: token_vault.amount,
+        strategies: get_strategies_with_reserve(program_client, &vault_state)?,
+        current_time,
+        lp_supply: lp_mint_state.supply,
+    };
+
+    let payer = program_client.payer();
+    let (user_token, user_lp, _) = instructions::user_token_accounts(&payer, &payer, &vault_state);
+
+    let planner = WithdrawPlanner::new(config, vault, &vault_state, &planner_state);
+    if multi_leg {
+        let plan = planner.plan_legs(unmint_amount, payer, user_token, user_lp)?;
+        println!(
+            "withdraw in {} legs, expected out {}",
+            plan.legs.len(),
+            plan.out_amount
+        );
+        let mut result = plan.send_legs_blocking(slippage_bps, |leg, min_out_amount| {
+            println!(
+                "leg path {:?}, unmint {}, expected out {}, minimum out {}",
+                leg.path, leg.unmint_amount, leg.out_amount, min_out_amount
+            );
+            let signature = send_withdraw(
+                program_client,
+                vault,
+                &vault_state,
+                leg,
+                min_out_amount,
+                unwrap_sol,
+            )?;
+            println!("{}", signature);
+            Ok(signature)
         });
+        if let Some(error) = result.error.take() {
+            println!(
+                "withdrawal partially completed: {} of {} legs, {} of {} lp burnt",
+                result.completed_legs().len(),
+                result.plan.legs.len(),
+                result.withdrawn_lp_amount(),
+                result.plan.unmint_amount
+            );
+            return Err(error);
+        }
+        return Ok(());
+    }
 
-    let signature = builder.send()?;
+    let plan = planner.plan(unmint_amount, payer, user_token, user_lp)?;
+    let min_out_amount = apply_slippage(plan.out_amount, slippage_bps)?;
+    println!(
+        "withdraw path {:?}, expected out {}, minimum out {}",
+        plan.path, plan.out_amount, min_out_amount
+    );
+    let signature = send_withdraw(
+        program_client,
+        vault,
+        &vault_state,
+        &plan,
+        min_out_amount,
+        unwrap_sol,
+    )?;
     println!("{}", signature);
 
     Ok(())
 }
 
+/// Send the withdrawal of plan for the payer, mapping a failed minimum out amount to `SlippageExceeded`
+fn send_withdraw<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &anchor_client::Program<C>,
+    vault: Pubkey,
+    vault_state: &mercurial_vault::state::Vault,
+    plan: &WithdrawPlan,
+    min_out_amount: u64,
+    unwrap_sol: bool,
+) -> Result<Signature> {
+    let payer = program_client.payer();
+    let instructions = instructions::withdraw_with_plan(
+        plan,
+        vault_state,
+        payer,
+        payer,
+        min_out_amount,
+        unwrap_sol,
+    );
+    let instructions = instructions.instructions();
+    request_with_instructions(program_client, &instructions)
+        .send()
+        .map_err(|error| {
+            map_slippage_error(
+                error.into(),
+                &instructions,
+                &program_client.id(),
+                SlippageSide::Withdraw,
+                plan.out_amount,
+                min_out_amount,
+                || quote_withdraw_now(program_client, vault, plan.unmint_amount).ok(),
+            )
+        })
+}
+
 pub fn get_or_create_ata<C: Deref<Target = impl Signer> + Clone>(
     program_client: &anchor_client::Program<C>,
     token_mint: Pubkey,
@@ -101,10 +208,11 @@ pub fn get_or_create_ata<C: Deref<Target = impl Signer> + Clone>(
         println!("Create ATA for TOKEN {} \n", &token_mint);
 
         let builder = program_client.request().instruction(
-            spl_associated_token_account::create_associated_token_account(
+            spl_associated_token_account::instruction::create_associated_token_account(
                 &program_client.payer(),
                 &user,
                 &token_mint,
+                &spl_token::id(),
             ),
         );
 
@@ -1,12 +1,27 @@
+use crate::withdraw_planner::StrategyWithReserve;
 use anchor_client::solana_client::rpc_response::RpcSimulateTransactionResult;
+use anchor_client::solana_sdk::instruction::Instruction;
+use anchor_client::solana_sdk::pubkey::Pubkey;
 use anchor_client::RequestBuilder;
 use anchor_client::{
     solana_client::rpc_response::Response,
     solana_sdk::{signature::Signer, transaction::Transaction},
     Program,
 };
+use anchor_lang::solana_program::clock::Clock;
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::AccountDeserialize;
+use anyhow::anyhow;
+use bincode::deserialize;
 use core::ops::Deref;
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::state::{Strategy, Vault};
+use mercurial_vault::strategy::valuer::get_strategy_valuer;
+use mercurial_vault::VaultEvent;
+use std::convert::TryFrom;
+use std::str::FromStr;
 
+#[allow(clippy::ptr_arg, clippy::unnecessary_unwrap)]
 pub fn parse_event_log<
     T: anchor_lang::AnchorDeserialize + anchor_lang::AnchorSerialize + anchor_lang::Discriminator,
 >(
@@ -32,6 +47,50 @@ pub fn parse_event_log<
     None
 }
 
+/// Decode every vault event in the logs of a transaction, in order.
+/// Logs are attributed to the program on top of the invoke stack, so events of CPI callers and callees are not mixed up.
+pub fn parse_vault_events(logs: &[String], program_id: &Pubkey) -> Vec<VaultEvent> {
+    let program_id = program_id.to_string();
+    let mut invoke_stack: Vec<&str> = vec![];
+    let mut events = vec![];
+    for log in logs.iter() {
+        if let Some(data) = log.strip_prefix("Program data: ") {
+            if invoke_stack.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+            if let Some(event) = anchor_lang::__private::base64::decode(data)
+                .ok()
+                .and_then(|data| VaultEvent::decode(&data))
+            {
+                events.push(event);
+            }
+        } else if let Some(rest) = log.strip_prefix("Program ") {
+            let words: Vec<&str> = rest.split_whitespace().collect();
+            match words.as_slice() {
+                [program, "invoke", depth] if is_program_id(program) && is_invoke_depth(depth) => {
+                    invoke_stack.push(program)
+                }
+                [program, "success"] | [program, "failed:", ..] if is_program_id(program) => {
+                    invoke_stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    events
+}
+
+fn is_program_id(word: &str) -> bool {
+    Pubkey::from_str(word).is_ok()
+}
+
+/// `[<n>]` ending an invoke log
+fn is_invoke_depth(word: &str) -> bool {
+    word.strip_prefix('[')
+        .and_then(|word| word.strip_suffix(']'))
+        .map_or(false, |depth| depth.parse::<u32>().is_ok())
+}
+
 pub fn simulate_transaction<C: Deref<Target = impl Signer> + Clone>(
     builder: &RequestBuilder<C>,
     program: &Program<C>,
@@ -49,3 +108,313 @@ pub fn simulate_transaction<C: Deref<Target = impl Signer> + Clone>(
     let simulation = rpc_client.simulate_transaction(&tx)?;
     Ok(simulation)
 }
+
+pub fn get_current_node_clock_time<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &Program<C>,
+) -> anyhow::Result<u64> {
+    let rpc = program_client.rpc();
+    let clock_account = rpc.get_account(&sysvar::clock::id())?;
+    let clock = deserialize::<Clock>(&clock_account.data)?;
+    let current_time = u64::try_from(clock.unix_timestamp)?;
+    Ok(current_time)
+}
+
+/// Fetch every strategy of the vault and its reserve, in two getMultipleAccounts calls
+pub fn get_strategies_with_reserve<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &Program<C>,
+    vault_state: &Vault,
+) -> anyhow::Result<Vec<StrategyWithReserve>> {
+    let rpc = program_client.rpc();
+    let strategy_pubkeys: Vec<Pubkey> = vault_state
+        .strategies
+        .iter()
+        .filter(|strategy| **strategy != Pubkey::default())
+        .copied()
+        .collect();
+    if strategy_pubkeys.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut strategies = vec![];
+    for (pubkey, account) in strategy_pubkeys
+        .iter()
+        .zip(rpc.get_multiple_accounts(&strategy_pubkeys)?)
+    {
+        let account = account.ok_or_else(|| anyhow!("Strategy {} not found", pubkey))?;
+        strategies.push((
+            *pubkey,
+            Strategy::try_deserialize(&mut account.data.as_slice())?,
+        ));
+    }
+
+    let reserves: Vec<Pubkey> = strategies.iter().map(|(_, state)| state.reserve).collect();
+    let reserve_accounts = rpc.get_multiple_accounts(&reserves)?;
+    strategies
+        .into_iter()
+        .zip(reserve_accounts)
+        .map(|((pubkey, state), reserve)| {
+            StrategyWithReserve::new(pubkey, state, reserve.map(|account| account.data))
+        })
+        .collect()
+}
+
+/// Booked and live value of a strategy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StrategyValuation {
+    pub pubkey: Pubkey,
+    /// `Strategy::current_liquidity`, as of the last rebalance
+    pub booked_liquidity: u64,
+    /// value of the position at the current reserve state, None when the strategy is only known by its booked liquidity,
+    /// the position account is missing or the position or reserve can not be decoded
+    pub actual_liquidity: Option<u64>,
+}
+
+impl StrategyValuation {
+    /// Yield earned since the last rebalance, negative on a loss, None without actual liquidity
+    pub fn unrealized_yield(&self) -> Option<i128> {
+        self.actual_liquidity.map(|actual_liquidity| {
+            i128::from(actual_liquidity) - i128::from(self.booked_liquidity)
+        })
+    }
+}
+
+/// Value every strategy with `StrategyValuer`, fetching the position accounts in one getMultipleAccounts call.
+/// A strategy which can not be valued has no actual liquidity, it does not fail the others
+pub fn get_strategy_valuations<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &Program<C>,
+    config: &ClusterConfig,
+    strategies: &[StrategyWithReserve],
+) -> anyhow::Result<Vec<StrategyValuation>> {
+    let valuers: Vec<_> = strategies
+        .iter()
+        .map(|strategy| {
+            let valuer = get_strategy_valuer(strategy.state.strategy_type);
+            let position_account = valuer
+                .position_account(config, &strategy.pubkey, &strategy.state)
+                .ok();
+            (valuer, position_account)
+        })
+        .collect();
+    let position_accounts: Vec<Pubkey> = valuers
+        .iter()
+        .filter_map(|(_, position_account)| position_account.flatten())
+        .collect();
+    let mut position_data = if position_accounts.is_empty() {
+        vec![]
+    } else {
+        program_client
+            .rpc()
+            .get_multiple_accounts(&position_accounts)?
+    }
+    .into_iter();
+
+    let mut valuations = vec![];
+    for (strategy, (valuer, position_account)) in strategies.iter().zip(valuers) {
+        let data = match position_account {
+            Some(Some(_)) => position_data.next().flatten().map(|account| account.data),
+            Some(None) => Some(vec![]),
+            None => None,
+        };
+        valuations.push(StrategyValuation {
+            pubkey: strategy.pubkey,
+            booked_liquidity: strategy.state.current_liquidity,
+            actual_liquidity: data.and_then(|data| {
+                valuer
+                    .value(&strategy.state, &data, &strategy.reserve_data)
+                    .ok()
+                    .flatten()
+            }),
+        });
+    }
+    Ok(valuations)
+}
+
+/// Request sending instructions in order, e.g. `VaultInstructions::instructions`
+pub fn request_with_instructions<'a, C: Deref<Target = impl Signer> + Clone>(
+    program_client: &'a Program<C>,
+    instructions: &[Instruction],
+) -> RequestBuilder<'a, C> {
+    instructions
+        .iter()
+        .cloned()
+        .fold(program_client.request(), |builder, instruction| {
+            builder.instruction(instruction)
+        })
+}
+
+/// Max slippage, the minimum amount is then 0
+pub const MAX_SLIPPAGE_BPS: u64 = 10_000;
+
+/// Minimum amount accepted for a quoted amount, rounded down
+pub fn apply_slippage(amount: u64, slippage_bps: u64) -> anyhow::Result<u64> {
+    if slippage_bps > MAX_SLIPPAGE_BPS {
+        return Err(anyhow!(
+            "Slippage {} bps is over {} bps",
+            slippage_bps,
+            MAX_SLIPPAGE_BPS
+        ));
+    }
+    let minimum = u128::from(amount) * u128::from(MAX_SLIPPAGE_BPS - slippage_bps)
+        / u128::from(MAX_SLIPPAGE_BPS);
+    Ok(u64::try_from(minimum)?)
+}
+
+/// Vault state and lp supply fetched now, with the on-chain time
+fn fetch_vault_now<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &Program<C>,
+    vault: Pubkey,
+) -> anyhow::Result<(Vault, u64, u64)> {
+    let vault_state: Vault = program_client.account(vault)?;
+    let lp_mint: anchor_spl::token::Mint = program_client.account(vault_state.lp_mint)?;
+    let current_time = get_current_node_clock_time(program_client)?;
+    Ok((vault_state, lp_mint.supply, current_time))
+}
+
+/// Lp minted by depositing token_amount in the current vault state
+pub fn quote_deposit_now<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &Program<C>,
+    vault: Pubkey,
+    token_amount: u64,
+) -> anyhow::Result<u64> {
+    let (vault_state, lp_supply, current_time) = fetch_vault_now(program_client, vault)?;
+    vault_state
+        .quote_deposit(current_time, token_amount, lp_supply)
+        .map(|quote| quote.lp_amount)
+        .ok_or_else(|| anyhow!("Cannot quote deposit"))
+}
+
+/// Token paid for unmint_amount of lp in the current vault state
+pub fn quote_withdraw_now<C: Deref<Target = impl Signer> + Clone>(
+    program_client: &Program<C>,
+    vault: Pubkey,
+    unmint_amount: u64,
+) -> anyhow::Result<u64> {
+    let (vault_state, lp_supply, current_time) = fetch_vault_now(program_client, vault)?;
+    vault_state
+        .get_amount_by_share(current_time, unmint_amount, lp_supply)
+        .ok_or_else(|| anyhow!("Cannot calculate withdraw amount"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{AnchorSerialize, Discriminator};
+    use mercurial_vault::strategy::base::StrategyType;
+    use mercurial_vault::{AddLiquidity, RemoveLiquidity};
+
+    const VAULT: &str = "24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi";
+    const AFFILIATE: &str = "GacY9YuN16HNRTy7ZWwULPccwvfFSBeNLuAQP7y38Du3";
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGhPGJuE2ayuapeCQNyFkMM";
+
+    fn event_log<T: Discriminator + AnchorSerialize>(event: &T) -> String {
+        let mut data = T::discriminator().to_vec();
+        data.extend(event.try_to_vec().unwrap());
+        format!(
+            "Program data: {}",
+            anchor_lang::__private::base64::encode(data)
+        )
+    }
+
+    fn add_liquidity(lp_mint_amount: u64) -> String {
+        event_log(&AddLiquidity {
+            lp_mint_amount,
+            token_amount: 2 * lp_mint_amount,
+        })
+    }
+
+    fn lp_mint_amounts(events: &[VaultEvent]) -> Vec<u64> {
+        events
+            .iter()
+            .map(|event| match event {
+                VaultEvent::AddLiquidity(event) => event.lp_mint_amount,
+                VaultEvent::RemoveLiquidity(event) => event.lpunmint_amount,
+                _ => panic!("unexpected event {:?}", event),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_vault_events_of_direct_call() {
+        let logs = vec![
+            format!("Program {} invoke [1]", VAULT),
+            "Program log: Instruction: Deposit".to_string(),
+            format!("Program {} invoke [2]", TOKEN),
+            format!("Program {} success", TOKEN),
+            add_liquidity(10),
+            format!("Program {} consumed 30000 of 200000 compute units", VAULT),
+            format!("Program {} success", VAULT),
+        ];
+        let events = parse_vault_events(&logs, &Pubkey::from_str(VAULT).unwrap());
+        assert_eq!(lp_mint_amounts(&events), vec![10]);
+    }
+
+    #[test]
+    fn parse_vault_events_inside_nested_cpi() {
+        let logs = vec![
+            format!("Program {} invoke [1]", AFFILIATE),
+            // same layout as a vault event, emitted by the caller
+            add_liquidity(1),
+            format!("Program {} invoke [2]", VAULT),
+            format!("Program {} invoke [3]", TOKEN),
+            format!("Program {} success", TOKEN),
+            add_liquidity(20),
+            format!("Program {} success", VAULT),
+            add_liquidity(2),
+            format!("Program {} invoke [2]", VAULT),
+            event_log(&RemoveLiquidity {
+                lpunmint_amount: 5,
+                token_amount: 10,
+            }),
+            format!("Program {} failed: custom program error: 0x1770", VAULT),
+            format!("Program {} failed: custom program error: 0x1770", AFFILIATE),
+        ];
+        let events = parse_vault_events(&logs, &Pubkey::from_str(VAULT).unwrap());
+        assert_eq!(lp_mint_amounts(&events), vec![20, 5]);
+    }
+
+    #[test]
+    fn parse_vault_events_ignores_program_logs_looking_like_invoke() {
+        let logs = vec![
+            format!("Program {} invoke [1]", AFFILIATE),
+            "Program log: invoke vault".to_string(),
+            "Program log: success".to_string(),
+            format!("Program {} invoke [2]", VAULT),
+            "Program log: invoke [2]".to_string(),
+            "Program log: success".to_string(),
+            add_liquidity(30),
+            format!("Program {} success", VAULT),
+            add_liquidity(3),
+            format!("Program {} success", AFFILIATE),
+        ];
+        let events = parse_vault_events(&logs, &Pubkey::from_str(VAULT).unwrap());
+        assert_eq!(lp_mint_amounts(&events), vec![30]);
+    }
+
+    #[test]
+    fn parse_vault_events_decodes_claim_reward() {
+        // sha256("event:ClaimReward")[..8], the name in the vault idl
+        let mut data = vec![148, 116, 134, 204, 22, 171, 85, 95];
+        // strategy_type SolendWithLM, token_amount 7, mint_account
+        data.push(4);
+        data.extend(7u64.to_le_bytes());
+        data.extend([9u8; 32]);
+        let logs = vec![
+            format!("Program {} invoke [1]", VAULT),
+            "Program log: Instruction: ClaimRewards".to_string(),
+            format!(
+                "Program data: {}",
+                anchor_lang::__private::base64::encode(data)
+            ),
+            format!("Program {} success", VAULT),
+        ];
+        let events = parse_vault_events(&logs, &Pubkey::from_str(VAULT).unwrap());
+        match events.as_slice() {
+            [VaultEvent::ClaimReward(event)] => {
+                assert_eq!(event.strategy_type, StrategyType::SolendWithLM);
+                assert_eq!(event.token_amount, 7);
+                assert_eq!(event.mint_account, Pubkey::new_from_array([9u8; 32]));
+            }
+            _ => panic!("unexpected events {:?}", events),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use crate::error::{map_slippage_error, SlippageSide};
+use crate::instructions;
+use crate::utils::{
+    apply_slippage, get_current_node_clock_time, get_strategies_with_reserve, quote_deposit_now,
+    quote_withdraw_now, request_with_instructions,
+};
+use crate::withdraw_planner::{
+    MultiLegWithdrawResult, WithdrawPlan, WithdrawPlanner, WithdrawPlannerState,
+    WithdrawableLiquidity,
+};
+use anchor_client::solana_sdk::signature::{Signature, Signer};
+use anchor_client::Program;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_spl::token::spl_token::state::{Account as TokenAccount, Mint};
+use anyhow::{anyhow, Result};
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::state::{DepositQuote, Strategy, Vault};
+use mercurial_vault::utils::VaultAddresses;
+use std::ops::Deref;
+
+/// Vault loaded from chain, same role as `VaultImpl` of ts-client.
+/// Vault state and mints are cached, call `refresh_vault_state` to refetch them or `get_vault_supply` for the lp mint only
+pub struct VaultClient<C> {
+    pub program_client: Program<C>,
+    pub config: ClusterConfig,
+    pub vault: Pubkey,
+    pub vault_state: Vault,
+    pub token_mint: Mint,
+    pub lp_mint: Mint,
+    /// Close the wrapped SOL account after a withdrawal from a SOL vault, true by default
+    pub unwrap_sol: bool,
+}
+
+/// Result of `VaultClient::deposit`
+#[derive(Clone, Copy, Debug)]
+pub struct DepositResult {
+    pub signature: Signature,
+    pub quote: DepositQuote,
+}
+
+/// Result of `VaultClient::withdraw`
+#[derive(Clone, Debug)]
+pub struct WithdrawResult {
+    pub signature: Signature,
+    pub plan: WithdrawPlan,
+}
+
+impl<C: Deref<Target = impl Signer> + Clone> VaultClient<C> {
+    /// Load the vault of token_mint with the standard base key of config
+    pub fn from_token_mint(
+        program_client: Program<C>,
+        config: ClusterConfig,
+        token_mint: Pubkey,
+    ) -> Result<Self> {
+        let base = config.base_key;
+        Self::from_token_mint_and_base(program_client, config, token_mint, base)
+    }
+
+    pub fn from_token_mint_and_base(
+        program_client: Program<C>,
+        config: ClusterConfig,
+        token_mint: Pubkey,
+        base: Pubkey,
+    ) -> Result<Self> {
+        let (vault, _) =
+            VaultAddresses::derive_with_program_id(token_mint, base, &config.vault_program_id)
+                .vault;
+        Self::from_pda(program_client, config, vault)
+    }
+
+    pub fn from_pda(
+        program_client: Program<C>,
+        config: ClusterConfig,
+        vault: Pubkey,
+    ) -> Result<Self> {
+        let vault_state: Vault = program_client.account(vault)?;
+        let rpc = program_client.rpc();
+        let token_mint = Mint::unpack(&rpc.get_account_data(&vault_state.token_mint)?)?;
+        let lp_mint = Mint::unpack(&rpc.get_account_data(&vault_state.lp_mint)?)?;
+        Ok(VaultClient {
+            program_client,
+            config,
+            vault,
+            vault_state,
+            token_mint,
+            lp_mint,
+            unwrap_sol: true,
+        })
+    }
+
+    /// Lp balance of the associated token account of owner, 0 if it does not exist
+    pub fn get_user_balance(&self, owner: Pubkey) -> Result<u64> {
+        let user_lp = spl_associated_token_account::get_associated_token_address(
+            &owner,
+            &self.vault_state.lp_mint,
+        );
+        let rpc = self.program_client.rpc();
+        match rpc
+            .get_account_with_commitment(&user_lp, rpc.commitment())?
+            .value
+        {
+            Some(account) => Ok(TokenAccount::unpack(&account.data)?.amount),
+            None => Ok(0),
+        }
+    }
+
+    /// Refetch the lp mint and return its supply
+    pub fn get_vault_supply(&mut self) -> Result<u64> {
+        let data = self
+            .program_client
+            .rpc()
+            .get_account_data(&self.vault_state.lp_mint)?;
+        self.lp_mint = Mint::unpack(&data)?;
+        Ok(self.lp_mint.supply)
+    }
+
+    /// Unlocked amount of the cached vault state at the on-chain time
+    pub fn get_withdrawable_amount(&self) -> Result<u64> {
+        let current_time = get_current_node_clock_time(&self.program_client)?;
+        self.vault_state
+            .get_unlocked_amount(current_time)
+            .ok_or_else(|| anyhow!("Math overflow"))
+    }
+
+    /// Liquidity withdrawable right now by source, from the cached vault state and lp supply
+    pub fn get_withdrawable_liquidity(&self) -> Result<WithdrawableLiquidity> {
+        let planner_state = self.fetch_planner_state()?;
+        WithdrawPlanner::new(&self.config, self.vault, &self.vault_state, &planner_state)
+            .withdrawable_liquidity()
+    }
+
+    /// Fetch the state to plan withdrawals, with the cached vault state and lp supply
+    fn fetch_planner_state(&self) -> Result<WithdrawPlannerState> {
+        let current_time = get_current_node_clock_time(&self.program_client)?;
+        let token_vault = TokenAccount::unpack(
+            &self
+                .program_client
+                .rpc()
+                .get_account_data(&self.vault_state.token_vault)?,
+        )?;
+        let strategies = get_strategies_with_reserve(&self.program_client, &self.vault_state)?;
+        Ok(WithdrawPlannerState {
+            token_vault_amount: token_vault.amount,
+            strategies,
+            current_time,
+            lp_supply: self.lp_mint.supply,
+        })
+    }
+
+    /// Refetch vault state and both mints
+    pub fn refresh_vault_state(&mut self) -> Result<()> {
+        self.vault_state = self.program_client.account(self.vault)?;
+        let data = self
+            .program_client
+            .rpc()
+            .get_account_data(&self.vault_state.token_mint)?;
+        self.token_mint = Mint::unpack(&data)?;
+        let data = self
+            .program_client
+            .rpc()
+            .get_account_data(&self.vault_state.lp_mint)?;
+        self.lp_mint = Mint::unpack(&data)?;
+        Ok(())
+    }
+
+    pub fn get_strategies_state(&self) -> Result<Vec<(Pubkey, Strategy)>> {
+        Ok(
+            get_strategies_with_reserve(&self.program_client, &self.vault_state)?
+                .into_iter()
+                .map(|strategy| (strategy.pubkey, strategy.state))
+                .collect(),
+        )
+    }
+
+    /// Deposit token_amount of the payer, the token and lp accounts are created in the same transaction
+    pub fn deposit(
+        &mut self,
+        token_amount: u64,
+        minimum_lp_token_amount: u64,
+    ) -> Result<DepositResult> {
+        self.deposit_with_minimum(token_amount, |_| Ok(minimum_lp_token_amount))
+    }
+
+    /// Deposit with the minimum lp set slippage_bps below a quote at the on-chain time.
+    /// Fails with `SlippageExceeded` when the vault mints less
+    pub fn deposit_with_slippage(
+        &mut self,
+        token_amount: u64,
+        slippage_bps: u64,
+    ) -> Result<DepositResult> {
+        self.deposit_with_minimum(token_amount, |quote| {
+            apply_slippage(quote.lp_amount, slippage_bps)
+        })
+    }
+
+    fn deposit_with_minimum(
+        &mut self,
+        token_amount: u64,
+        minimum_lp_token_amount: impl FnOnce(&DepositQuote) -> Result<u64>,
+    ) -> Result<DepositResult> {
+        self.refresh_vault_state()?;
+        let current_time = get_current_node_clock_time(&self.program_client)?;
+        let quote = self
+            .vault_state
+            .quote_deposit(current_time, token_amount, self.lp_mint.supply)
+            .ok_or_else(|| anyhow!("Cannot quote deposit"))?;
+        let minimum_lp_token_amount = minimum_lp_token_amount(&quote)?;
+
+        let user = self.program_client.payer();
+        let instructions = instructions::deposit(
+            &self.config.vault_program_id,
+            self.vault,
+            &self.vault_state,
+            user,
+            user,
+            token_amount,
+            minimum_lp_token_amount,
+        );
+        let instructions = instructions.instructions();
+        let signature = request_with_instructions(&self.program_client, &instructions)
+            .send()
+            .map_err(|error| {
+                map_slippage_error(
+                    error.into(),
+                    &instructions,
+                    &self.config.vault_program_id,
+                    SlippageSide::Deposit,
+                    quote.lp_amount,
+                    minimum_lp_token_amount,
+                    || quote_deposit_now(&self.program_client, self.vault, token_amount).ok(),
+                )
+            })?;
+        Ok(DepositResult { signature, quote })
+    }
+
+    /// Burn unmint_amount of lp of the payer, routed like ts-client through the reserve or the strategy with highest liquidity
+    pub fn withdraw(&mut self, unmint_amount: u64, min_out_amount: u64) -> Result<WithdrawResult> {
+        self.withdraw_with_minimum(unmint_amount, |_| Ok(min_out_amount))
+    }
+
+    /// Withdraw with the minimum out amount set slippage_bps below the planned out amount.
+    /// Fails with `SlippageExceeded` when the vault pays less
+    pub fn withdraw_with_slippage(
+        &mut self,
+        unmint_amount: u64,
+        slippage_bps: u64,
+    ) -> Result<WithdrawResult> {
+        self.withdraw_with_minimum(unmint_amount, |plan| {
+            apply_slippage(plan.out_amount, slippage_bps)
+        })
+    }
+
+    fn withdraw_with_minimum(
+        &mut self,
+        unmint_amount: u64,
+        min_out_amount: impl FnOnce(&WithdrawPlan) -> Result<u64>,
+    ) -> Result<WithdrawResult> {
+        self.refresh_vault_state()?;
+        let planner_state = self.fetch_planner_state()?;
+
+        let user = self.program_client.payer();
+        let (user_token, user_lp, _) =
+            instructions::user_token_accounts(&user, &user, &self.vault_state);
+
+        let plan =
+            WithdrawPlanner::new(&self.config, self.vault, &self.vault_state, &planner_state)
+                .plan(unmint_amount, user, user_token, user_lp)?;
+        let min_out_amount = min_out_amount(&plan)?;
+        let signature = self.send_withdraw(&plan, min_out_amount)?;
+        Ok(WithdrawResult { signature, plan })
+    }
+
+    /// Burn unmint_amount of lp of the payer in several transactions when no single strategy has enough liquidity,
+    /// see `WithdrawPlanner::plan_legs`. Each leg has its minimum out amount slippage_bps below its planned out amount.
+    /// Legs are sent in order, a failed leg stops the withdrawal and is reported in the result with the completed legs
+    pub fn withdraw_multi_leg(
+        &mut self,
+        unmint_amount: u64,
+        slippage_bps: u64,
+    ) -> Result<MultiLegWithdrawResult> {
+        self.refresh_vault_state()?;
+        let planner_state = self.fetch_planner_state()?;
+
+        let user = self.program_client.payer();
+        let (user_token, user_lp, _) =
+            instructions::user_token_accounts(&user, &user, &self.vault_state);
+
+        let plan =
+            WithdrawPlanner::new(&self.config, self.vault, &self.vault_state, &planner_state)
+                .plan_legs(unmint_amount, user, user_token, user_lp)?;
+        Ok(
+            plan.send_legs_blocking(slippage_bps, |leg, min_out_amount| {
+                self.send_withdraw(leg, min_out_amount)
+            }),
+        )
+    }
+
+    /// Send the withdrawal of plan, mapping a failed minimum out amount to `SlippageExceeded`
+    fn send_withdraw(&self, plan: &WithdrawPlan, min_out_amount: u64) -> Result<Signature> {
+        let user = self.program_client.payer();
+        let instructions = instructions::withdraw_with_plan(
+            plan,
+            &self.vault_state,
+            user,
+            user,
+            min_out_amount,
+            self.unwrap_sol,
+        );
+        let instructions = instructions.instructions();
+        request_with_instructions(&self.program_client, &instructions)
+            .send()
+            .map_err(|error| {
+                map_slippage_error(
+                    error.into(),
+                    &instructions,
+                    &self.config.vault_program_id,
+                    SlippageSide::Withdraw,
+                    plan.out_amount,
+                    min_out_amount,
+                    || {
+                        quote_withdraw_now(&self.program_client, self.vault, plan.unmint_amount)
+                            .ok()
+                    },
+                )
+            })
+    }
+}
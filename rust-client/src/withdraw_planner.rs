@@ -0,0 +1,966 @@
+use crate::instructions::withdraw_directly_from_strategy_accounts;
+use crate::utils::apply_slippage;
+use anchor_client::solana_sdk::signature::Signature;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::pubkey::Pubkey;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use anyhow::{anyhow, Result};
+use mercurial_vault::cluster::ClusterConfig;
+use mercurial_vault::state::{Strategy, Vault};
+use mercurial_vault::strategy::base::StrategyType;
+use mercurial_vault::strategy::valuer::get_strategy_valuer;
+use std::cmp::Reverse;
+use std::convert::TryFrom;
+use std::future::Future;
+
+/// Where the withdrawal is paid from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WithdrawPath {
+    /// `withdraw`, paid from token_vault
+    Reserve,
+    /// `withdraw_directly_from_strategy`, token_vault is topped up from the strategy
+    Strategy {
+        strategy: Pubkey,
+        strategy_type: StrategyType,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct WithdrawPlan {
+    pub program_id: Pubkey,
+    pub path: WithdrawPath,
+    pub unmint_amount: u64,
+    /// token amount expected to be received
+    pub out_amount: u64,
+    pub accounts: Vec<AccountMeta>,
+}
+
+impl WithdrawPlan {
+    pub fn instruction(&self, min_out_amount: u64) -> Instruction {
+        let data = match self.path {
+            WithdrawPath::Reserve => mercurial_vault::instruction::Withdraw {
+                unmint_amount: self.unmint_amount,
+                min_out_amount,
+            }
+            .data(),
+            WithdrawPath::Strategy { .. } => {
+                mercurial_vault::instruction::WithdrawDirectlyFromStrategy {
+                    unmint_amount: self.unmint_amount,
+                    min_out_amount,
+                }
+                .data()
+            }
+        };
+        Instruction {
+            program_id: self.program_id,
+            accounts: self.accounts.clone(),
+            data,
+        }
+    }
+}
+
+/// Withdrawal split in legs, each sent in its own transaction and burning part of the lp
+#[derive(Clone, Debug)]
+pub struct MultiLegWithdrawPlan {
+    pub unmint_amount: u64,
+    /// token amount expected to be received over all legs
+    pub out_amount: u64,
+    pub legs: Vec<WithdrawPlan>,
+}
+
+impl MultiLegWithdrawPlan {
+    /// Send the legs in order with send_leg, each with its minimum out amount slippage_bps below its planned out amount.
+    /// A failed leg stops the withdrawal and is reported in the result with the completed legs
+    pub async fn send_legs<F, Fut>(
+        self,
+        slippage_bps: u64,
+        mut send_leg: F,
+    ) -> MultiLegWithdrawResult
+    where
+        F: FnMut(WithdrawPlan, u64) -> Fut,
+        Fut: Future<Output = Result<Signature>>,
+    {
+        let mut signatures = vec![];
+        let mut error = None;
+        for leg in self.legs.iter() {
+            let sent = match apply_slippage(leg.out_amount, slippage_bps) {
+                Ok(min_out_amount) => send_leg(leg.clone(), min_out_amount).await,
+                Err(slippage_error) => Err(slippage_error),
+            };
+            match sent {
+                Ok(signature) => signatures.push(signature),
+                Err(leg_error) => {
+                    error = Some(leg_error);
+                    break;
+                }
+            }
+        }
+        MultiLegWithdrawResult {
+            plan: self,
+            signatures,
+            error,
+        }
+    }
+
+    /// `send_legs` with a blocking send_leg
+    pub fn send_legs_blocking(
+        self,
+        slippage_bps: u64,
+        mut send_leg: impl FnMut(&WithdrawPlan, u64) -> Result<Signature>,
+    ) -> MultiLegWithdrawResult {
+        futures::executor::block_on(self.send_legs(slippage_bps, |leg, min_out_amount| {
+            futures::future::ready(send_leg(&leg, min_out_amount))
+        }))
+    }
+}
+
+/// Outcome of sending the legs of a `MultiLegWithdrawPlan` in order, stopping at the first failed leg
+#[derive(Debug)]
+pub struct MultiLegWithdrawResult {
+    pub plan: MultiLegWithdrawPlan,
+    /// signature of each confirmed leg, in the order of plan.legs
+    pub signatures: Vec<Signature>,
+    /// error of the first failed leg, the legs after it are not sent
+    pub error: Option<anyhow::Error>,
+}
+
+impl MultiLegWithdrawResult {
+    pub fn is_complete(&self) -> bool {
+        self.signatures.len() == self.plan.legs.len()
+    }
+
+    pub fn completed_legs(&self) -> &[WithdrawPlan] {
+        &self.plan.legs[..self.signatures.len()]
+    }
+
+    /// Failed leg followed by the legs not sent
+    pub fn pending_legs(&self) -> &[WithdrawPlan] {
+        &self.plan.legs[self.signatures.len()..]
+    }
+
+    /// Lp burnt by the completed legs
+    pub fn withdrawn_lp_amount(&self) -> u64 {
+        self.completed_legs()
+            .iter()
+            .map(|leg| leg.unmint_amount)
+            .sum()
+    }
+
+    /// Token expected to be received from the completed legs
+    pub fn withdrawn_out_amount(&self) -> u64 {
+        self.completed_legs().iter().map(|leg| leg.out_amount).sum()
+    }
+}
+
+/// Strategy account with the data of its reserve
+#[derive(Clone, Debug)]
+pub struct StrategyWithReserve {
+    pub pubkey: Pubkey,
+    pub state: Strategy,
+    pub reserve_data: Vec<u8>,
+}
+
+impl StrategyWithReserve {
+    /// reserve_data is None when the reserve account was not found, which fails for an enabled strategy.
+    /// The reserve of a disabled strategy is not read and may be closed, its data is then empty
+    pub fn new(pubkey: Pubkey, state: Strategy, reserve_data: Option<Vec<u8>>) -> Result<Self> {
+        let reserve_data = match reserve_data {
+            Some(reserve_data) => reserve_data,
+            None if state.is_disable != 0 => vec![],
+            None => {
+                return Err(anyhow!(
+                    "Reserve {} of strategy {} not found",
+                    state.reserve,
+                    pubkey
+                ))
+            }
+        };
+        Ok(StrategyWithReserve {
+            pubkey,
+            state,
+            reserve_data,
+        })
+    }
+}
+
+/// Liquidity a strategy can pay out now
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StrategyWithdrawable {
+    pub pubkey: Pubkey,
+    pub strategy_type: StrategyType,
+    pub current_liquidity: u64,
+    /// `StrategyValuer::available_liquidity` of the reserve, None when the reserve does not tell it,
+    /// can not be decoded or the strategy is not withdrawable
+    pub reserve_available_liquidity: Option<u64>,
+    /// current_liquidity capped by the reserve, 0 for disabled or unsupported strategies
+    /// and strategies whose reserve can not be decoded
+    pub withdrawable_amount: u64,
+    /// why the reserve of a withdrawable strategy could not be decoded, the strategy is then not withdrawn from
+    pub reserve_error: Option<String>,
+}
+
+/// Liquidity withdrawable right now, by source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawableLiquidity {
+    /// token_vault balance, paid by `withdraw`
+    pub token_vault_amount: u64,
+    /// every strategy in vault_state.strategies
+    pub strategies: Vec<StrategyWithdrawable>,
+    /// unlocked amount of the vault, no withdrawal pays more
+    pub unlocked_amount: u64,
+}
+
+impl WithdrawableLiquidity {
+    /// Strategy paying the most now
+    pub fn best_strategy(&self) -> Option<&StrategyWithdrawable> {
+        self.strategies
+            .iter()
+            .filter(|strategy| strategy.withdrawable_amount > 0)
+            .max_by_key(|strategy| strategy.withdrawable_amount)
+    }
+
+    /// Largest amount paid by a single withdrawal: token_vault topped up from the best strategy
+    pub fn max_instant_withdraw(&self) -> u64 {
+        let best_strategy_amount = self
+            .best_strategy()
+            .map_or(0, |strategy| strategy.withdrawable_amount);
+        self.token_vault_amount
+            .saturating_add(best_strategy_amount)
+            .min(self.unlocked_amount)
+    }
+
+    /// Amount paid by withdrawing from token_vault and every strategy, over several withdrawals
+    pub fn total_withdrawable(&self) -> u64 {
+        self.strategies
+            .iter()
+            .fold(self.token_vault_amount, |total, strategy| {
+                total.saturating_add(strategy.withdrawable_amount)
+            })
+            .min(self.unlocked_amount)
+    }
+}
+
+/// Snapshot of the vault state needed to route a withdrawal
+pub struct WithdrawPlanner<'a> {
+    pub config: &'a ClusterConfig,
+    pub vault: Pubkey,
+    pub vault_state: &'a Vault,
+    /// token_vault balance
+    pub token_vault_amount: u64,
+    /// every strategy in vault_state.strategies
+    pub strategies: &'a [StrategyWithReserve],
+    pub current_time: u64,
+    pub lp_supply: u64,
+}
+
+/// State fetched from the cluster to plan a withdrawal, see `WithdrawPlanner::new`
+pub struct WithdrawPlannerState {
+    /// token_vault balance
+    pub token_vault_amount: u64,
+    /// every strategy in vault_state.strategies
+    pub strategies: Vec<StrategyWithReserve>,
+    pub current_time: u64,
+    pub lp_supply: u64,
+}
+
+impl<'a> WithdrawPlanner<'a> {
+    pub fn new(
+        config: &'a ClusterConfig,
+        vault: Pubkey,
+        vault_state: &'a Vault,
+        state: &'a WithdrawPlannerState,
+    ) -> Self {
+        WithdrawPlanner {
+            config,
+            vault,
+            vault_state,
+            token_vault_amount: state.token_vault_amount,
+            strategies: &state.strategies,
+            current_time: state.current_time,
+            lp_supply: state.lp_supply,
+        }
+    }
+
+    /// Same routing as ts-client: use the reserve if it covers the amount, otherwise the strategy paying the most now,
+    /// see `WithdrawableLiquidity::best_strategy`
+    pub fn plan(
+        &self,
+        unmint_amount: u64,
+        user: Pubkey,
+        user_token: Pubkey,
+        user_lp: Pubkey,
+    ) -> Result<WithdrawPlan> {
+        let out_amount = self
+            .vault_state
+            .get_amount_by_share(self.current_time, unmint_amount, self.lp_supply)
+            .ok_or_else(|| anyhow!("Cannot calculate withdraw amount"))?;
+
+        if out_amount < self.token_vault_amount {
+            let accounts = mercurial_vault::accounts::DepositWithdrawLiquidity {
+                vault: self.vault,
+                token_vault: self.vault_state.token_vault,
+                lp_mint: self.vault_state.lp_mint,
+                user_token,
+                user_lp,
+                user,
+                token_program: spl_token::id(),
+            }
+            .to_account_metas(None);
+            return Ok(WithdrawPlan {
+                program_id: self.config.vault_program_id,
+                path: WithdrawPath::Reserve,
+                unmint_amount,
+                out_amount,
+                accounts,
+            });
+        }
+
+        let withdrawable = self.withdrawable_liquidity()?;
+        let best_strategy = withdrawable
+            .best_strategy()
+            .ok_or_else(|| anyhow!("No strategy to withdraw from"))?;
+        let strategy = self
+            .strategies
+            .iter()
+            .find(|strategy| strategy.pubkey == best_strategy.pubkey)
+            .ok_or_else(|| anyhow!("Strategy {} not found", best_strategy.pubkey))?;
+        let strategy_state = &strategy.state;
+
+        let available_amount = best_strategy
+            .withdrawable_amount
+            .checked_add(self.token_vault_amount)
+            .ok_or_else(|| anyhow!("Math overflow"))?;
+        if out_amount > available_amount {
+            return Err(anyhow!(
+                "Selected strategy does not have enough liquidity, available {}, required {}",
+                available_amount,
+                out_amount
+            ));
+        }
+
+        let accounts = withdraw_directly_from_strategy_accounts(
+            self.config,
+            self.vault,
+            self.vault_state,
+            strategy,
+            user,
+            user_token,
+            user_lp,
+        )?;
+
+        Ok(WithdrawPlan {
+            program_id: self.config.vault_program_id,
+            path: WithdrawPath::Strategy {
+                strategy: strategy.pubkey,
+                strategy_type: strategy_state.strategy_type,
+            },
+            unmint_amount,
+            out_amount,
+            accounts,
+        })
+    }
+
+    /// Split the withdrawal of unmint_amount in legs when no single path covers it.
+    /// The first leg is paid from token_vault topped up by the strategy paying the most,
+    /// the next legs by the other strategies in decreasing withdrawable liquidity.
+    /// A withdrawal the reserve covers is a single `WithdrawPath::Reserve` leg
+    pub fn plan_legs(
+        &self,
+        unmint_amount: u64,
+        user: Pubkey,
+        user_token: Pubkey,
+        user_lp: Pubkey,
+    ) -> Result<MultiLegWithdrawPlan> {
+        let out_amount = self
+            .vault_state
+            .get_amount_by_share(self.current_time, unmint_amount, self.lp_supply)
+            .ok_or_else(|| anyhow!("Cannot calculate withdraw amount"))?;
+
+        if out_amount < self.token_vault_amount {
+            return Ok(MultiLegWithdrawPlan {
+                unmint_amount,
+                out_amount,
+                legs: vec![self.plan(unmint_amount, user, user_token, user_lp)?],
+            });
+        }
+
+        let withdrawable = self.withdrawable_liquidity()?;
+        let mut sources: Vec<_> = self
+            .strategies
+            .iter()
+            .zip(withdrawable.strategies.iter())
+            .filter(|(_, strategy_withdrawable)| strategy_withdrawable.withdrawable_amount > 0)
+            .collect();
+        sources.sort_by_key(|(_, strategy_withdrawable)| {
+            Reverse(strategy_withdrawable.withdrawable_amount)
+        });
+
+        let mut legs = vec![];
+        let mut remaining_lp_amount = unmint_amount;
+        let mut token_vault_amount = self.token_vault_amount;
+        for (strategy, strategy_withdrawable) in sources {
+            if remaining_lp_amount == 0 {
+                break;
+            }
+            let leg_capacity = strategy_withdrawable
+                .withdrawable_amount
+                .checked_add(token_vault_amount)
+                .ok_or_else(|| anyhow!("Math overflow"))?;
+            let leg_lp_amount = self
+                .lp_amount_for(leg_capacity, withdrawable.unlocked_amount)
+                .min(remaining_lp_amount);
+            if leg_lp_amount == 0 {
+                continue;
+            }
+            let leg_out_amount = self
+                .vault_state
+                .get_amount_by_share(self.current_time, leg_lp_amount, self.lp_supply)
+                .ok_or_else(|| anyhow!("Cannot calculate withdraw amount"))?;
+            legs.push(WithdrawPlan {
+                program_id: self.config.vault_program_id,
+                path: WithdrawPath::Strategy {
+                    strategy: strategy.pubkey,
+                    strategy_type: strategy.state.strategy_type,
+                },
+                unmint_amount: leg_lp_amount,
+                out_amount: leg_out_amount,
+                accounts: withdraw_directly_from_strategy_accounts(
+                    self.config,
+                    self.vault,
+                    self.vault_state,
+                    strategy,
+                    user,
+                    user_token,
+                    user_lp,
+                )?,
+            });
+            remaining_lp_amount -= leg_lp_amount;
+            // the first leg drains token_vault before the strategy
+            token_vault_amount = 0;
+        }
+
+        if remaining_lp_amount > 0 {
+            return Err(anyhow!(
+                "Strategies do not have enough liquidity, {} of {} lp can be withdrawn now",
+                unmint_amount - remaining_lp_amount,
+                unmint_amount
+            ));
+        }
+        Ok(MultiLegWithdrawPlan {
+            unmint_amount,
+            out_amount,
+            legs,
+        })
+    }
+
+    /// Largest lp amount paying at most amount, rounded down
+    fn lp_amount_for(&self, amount: u64, unlocked_amount: u64) -> u64 {
+        if unlocked_amount == 0 {
+            return 0;
+        }
+        let lp_amount =
+            u128::from(amount) * u128::from(self.lp_supply) / u128::from(unlocked_amount);
+        u64::try_from(lp_amount).unwrap_or(u64::MAX)
+    }
+
+    /// Liquidity withdrawable now from token_vault and each strategy, a strategy is capped by the liquidity its reserve can pay out
+    pub fn withdrawable_liquidity(&self) -> Result<WithdrawableLiquidity> {
+        let mut strategies = vec![];
+        for strategy in self.strategies.iter() {
+            let strategy_state = &strategy.state;
+            // reserve of a disabled strategy may not be fetched,
+            // an undecodable reserve may not pay anything so the strategy is not withdrawn from
+            let (reserve_available_liquidity, withdrawable_amount, reserve_error) =
+                if self.is_withdrawable(strategy) {
+                    match get_strategy_valuer(strategy_state.strategy_type)
+                        .available_liquidity(&strategy.reserve_data)
+                    {
+                        Ok(reserve_available_liquidity) => {
+                            let withdrawable_amount = reserve_available_liquidity
+                                .map_or(strategy_state.current_liquidity, |available| {
+                                    available.min(strategy_state.current_liquidity)
+                                });
+                            (reserve_available_liquidity, withdrawable_amount, None)
+                        }
+                        Err(err) => (None, 0, Some(err.to_string())),
+                    }
+                } else {
+                    (None, 0, None)
+                };
+            strategies.push(StrategyWithdrawable {
+                pubkey: strategy.pubkey,
+                strategy_type: strategy_state.strategy_type,
+                current_liquidity: strategy_state.current_liquidity,
+                reserve_available_liquidity,
+                withdrawable_amount,
+                reserve_error,
+            });
+        }
+        Ok(WithdrawableLiquidity {
+            token_vault_amount: self.token_vault_amount,
+            strategies,
+            unlocked_amount: self
+                .vault_state
+                .get_unlocked_amount(self.current_time)
+                .ok_or_else(|| anyhow!("Math overflow"))?,
+        })
+    }
+
+    /// Enabled strategy of a protocol which can still be withdrawn from
+    fn is_withdrawable(&self, strategy: &StrategyWithReserve) -> bool {
+        strategy.state.is_disable == 0
+            && self
+                .config
+                .strategy_programs
+                .get(strategy.state.strategy_type)
+                .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mercurial_vault::strategy::reserve::SOLEND_RESERVE_LAYOUT;
+
+    /// Solend reserve with available_amount not borrowed, other fields zero
+    fn solend_reserve_data(available_amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 619];
+        let offset = SOLEND_RESERVE_LAYOUT.liquidity_available_amount;
+        data[offset..offset + 8].copy_from_slice(&available_amount.to_le_bytes());
+        data
+    }
+
+    fn strategy(
+        strategy_type: StrategyType,
+        current_liquidity: u64,
+        is_disable: u8,
+        reserve_data: Vec<u8>,
+    ) -> StrategyWithReserve {
+        StrategyWithReserve {
+            pubkey: Pubkey::new_unique(),
+            state: Strategy {
+                strategy_type,
+                current_liquidity,
+                is_disable,
+                ..Default::default()
+            },
+            reserve_data,
+        }
+    }
+
+    fn planner_state(strategies: Vec<StrategyWithReserve>) -> WithdrawPlannerState {
+        WithdrawPlannerState {
+            token_vault_amount: 100,
+            strategies,
+            current_time: 0,
+            lp_supply: 1_000,
+        }
+    }
+
+    fn vault_state() -> Vault {
+        Vault {
+            total_amount: 1_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn strategy_is_capped_by_reserve_liquidity() {
+        let config = ClusterConfig::mainnet();
+        let vault_state = vault_state();
+        let state = planner_state(vec![
+            strategy(
+                StrategyType::SolendWithoutLM,
+                500,
+                0,
+                solend_reserve_data(200),
+            ),
+            strategy(
+                StrategyType::SolendWithoutLM,
+                400,
+                0,
+                solend_reserve_data(1_000),
+            ),
+        ]);
+
+        let withdrawable =
+            WithdrawPlanner::new(&config, Pubkey::new_unique(), &vault_state, &state)
+                .withdrawable_liquidity()
+                .unwrap();
+        let amounts: Vec<_> = withdrawable
+            .strategies
+            .iter()
+            .map(|strategy| {
+                (
+                    strategy.reserve_available_liquidity,
+                    strategy.withdrawable_amount,
+                )
+            })
+            .collect();
+        assert_eq!(amounts, vec![(Some(200), 200), (Some(1_000), 400)]);
+        // token_vault topped up from the best strategy
+        assert_eq!(withdrawable.max_instant_withdraw(), 500);
+        assert_eq!(withdrawable.total_withdrawable(), 700);
+    }
+
+    #[test]
+    fn disabled_and_undecodable_strategies_are_not_withdrawable() {
+        let config = ClusterConfig::mainnet();
+        let vault_state = vault_state();
+        let state = planner_state(vec![
+            // disabled strategy whose reserve was not fetched
+            strategy(StrategyType::SolendWithoutLM, 300, 1, vec![]),
+            strategy(StrategyType::Mango, 300, 0, vec![]),
+            strategy(StrategyType::SolendWithoutLM, 300, 0, vec![0u8; 10]),
+        ]);
+
+        let withdrawable =
+            WithdrawPlanner::new(&config, Pubkey::new_unique(), &vault_state, &state)
+                .withdrawable_liquidity()
+                .unwrap();
+        let amounts: Vec<_> = withdrawable
+            .strategies
+            .iter()
+            .map(|strategy| {
+                (
+                    strategy.reserve_available_liquidity,
+                    strategy.withdrawable_amount,
+                )
+            })
+            .collect();
+        assert_eq!(amounts, vec![(None, 0), (None, 0), (None, 0)]);
+        assert_eq!(withdrawable.max_instant_withdraw(), 100);
+        let reserve_errors: Vec<_> = withdrawable
+            .strategies
+            .iter()
+            .map(|strategy| strategy.reserve_error.is_some())
+            .collect();
+        assert_eq!(reserve_errors, vec![false, false, true]);
+    }
+
+    #[test]
+    fn missing_reserve_fails_for_enabled_strategy_only() {
+        let disabled = Strategy {
+            strategy_type: StrategyType::SolendWithoutLM,
+            is_disable: 1,
+            ..Default::default()
+        };
+        let strategy = StrategyWithReserve::new(Pubkey::new_unique(), disabled, None).unwrap();
+        assert!(strategy.reserve_data.is_empty());
+
+        let enabled = Strategy {
+            strategy_type: StrategyType::SolendWithoutLM,
+            ..Default::default()
+        };
+        assert!(StrategyWithReserve::new(Pubkey::new_unique(), enabled.clone(), None).is_err());
+        let strategy =
+            StrategyWithReserve::new(Pubkey::new_unique(), enabled, Some(solend_reserve_data(1)))
+                .unwrap();
+        assert_eq!(strategy.reserve_data, solend_reserve_data(1));
+    }
+
+    fn strategy_pubkey(plan: &WithdrawPlan) -> Option<Pubkey> {
+        match plan.path {
+            WithdrawPath::Reserve => None,
+            WithdrawPath::Strategy { strategy, .. } => Some(strategy),
+        }
+    }
+
+    #[test]
+    fn plan_uses_reserve_when_token_vault_covers_amount() {
+        let config = ClusterConfig::mainnet();
+        let vault = Pubkey::new_unique();
+        let vault_state = vault_state();
+        let state = planner_state(vec![strategy(
+            StrategyType::SolendWithoutLM,
+            500,
+            0,
+            solend_reserve_data(1_000),
+        )]);
+        let (user, user_token, user_lp) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let planner = WithdrawPlanner::new(&config, vault, &vault_state, &state);
+
+        let plan = planner.plan(50, user, user_token, user_lp).unwrap();
+        assert_eq!(plan.path, WithdrawPath::Reserve);
+        assert_eq!((plan.unmint_amount, plan.out_amount), (50, 50));
+        let accounts: Vec<_> = plan.accounts.iter().map(|account| account.pubkey).collect();
+        assert_eq!(
+            accounts,
+            vec![
+                vault,
+                vault_state.token_vault,
+                vault_state.lp_mint,
+                user_token,
+                user_lp,
+                user,
+                spl_token::id()
+            ]
+        );
+
+        // token_vault is drained only through a strategy
+        let plan = planner.plan(100, user, user_token, user_lp).unwrap();
+        assert_eq!(strategy_pubkey(&plan), Some(state.strategies[0].pubkey));
+    }
+
+    #[test]
+    fn plan_picks_enabled_strategy_with_highest_liquidity() {
+        let config = ClusterConfig::mainnet();
+        let vault_state = vault_state();
+        let state = planner_state(vec![
+            strategy(
+                StrategyType::SolendWithoutLM,
+                900,
+                1,
+                solend_reserve_data(1_000),
+            ),
+            strategy(StrategyType::Mango, 900, 0, vec![]),
+            strategy(
+                StrategyType::SolendWithoutLM,
+                300,
+                0,
+                solend_reserve_data(1_000),
+            ),
+            strategy(
+                StrategyType::SolendWithoutLM,
+                500,
+                0,
+                solend_reserve_data(1_000),
+            ),
+        ]);
+        let planner = WithdrawPlanner::new(&config, Pubkey::new_unique(), &vault_state, &state);
+
+        let plan = planner
+            .plan(
+                400,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            )
+            .unwrap();
+        let selected = &state.strategies[3];
+        assert_eq!(
+            plan.path,
+            WithdrawPath::Strategy {
+                strategy: selected.pubkey,
+                strategy_type: StrategyType::SolendWithoutLM,
+            }
+        );
+        assert_eq!((plan.unmint_amount, plan.out_amount), (400, 400));
+        assert_eq!(plan.accounts[1].pubkey, selected.pubkey);
+        assert_eq!(plan.accounts[3].pubkey, config.strategy_programs.solend);
+    }
+
+    #[test]
+    fn plan_picks_strategy_paying_the_most_over_booked_liquidity() {
+        let config = ClusterConfig::mainnet();
+        let vault_state = vault_state();
+        let state = planner_state(vec![
+            // most booked liquidity, reserve almost fully borrowed
+            strategy(
+                StrategyType::SolendWithoutLM,
+                800,
+                0,
+                solend_reserve_data(50),
+            ),
+            strategy(
+                StrategyType::SolendWithoutLM,
+                400,
+                0,
+                solend_reserve_data(1_000),
+            ),
+        ]);
+        let planner = WithdrawPlanner::new(&config, Pubkey::new_unique(), &vault_state, &state);
+        let plan = |unmint_amount| {
+            planner.plan(
+                unmint_amount,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            )
+        };
+
+        let selected = plan(400).unwrap();
+        assert_eq!(strategy_pubkey(&selected), Some(state.strategies[1].pubkey));
+        let withdrawable = planner.withdrawable_liquidity().unwrap();
+        assert_eq!(withdrawable.max_instant_withdraw(), 500);
+        assert!(plan(500).is_ok());
+
+        // checked against the capped amount, not the booked 800 of the first strategy
+        assert_eq!(
+            plan(600).unwrap_err().to_string(),
+            "Selected strategy does not have enough liquidity, available 500, required 600"
+        );
+    }
+
+    #[test]
+    fn plan_fails_when_selected_strategy_lacks_liquidity() {
+        let config = ClusterConfig::mainnet();
+        let vault_state = vault_state();
+        let state = planner_state(vec![
+            strategy(
+                StrategyType::SolendWithoutLM,
+                300,
+                0,
+                solend_reserve_data(1_000),
+            ),
+            strategy(
+                StrategyType::SolendWithoutLM,
+                200,
+                0,
+                solend_reserve_data(1_000),
+            ),
+        ]);
+        let planner = WithdrawPlanner::new(&config, Pubkey::new_unique(), &vault_state, &state);
+
+        // 300 from the strategy and 100 from token_vault
+        let error = planner
+            .plan(
+                500,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Selected strategy does not have enough liquidity, available 400, required 500"
+        );
+    }
+
+    #[test]
+    fn plan_fails_without_withdrawable_strategy() {
+        let config = ClusterConfig::mainnet();
+        let vault_state = vault_state();
+        let state = planner_state(vec![
+            strategy(
+                StrategyType::SolendWithoutLM,
+                900,
+                1,
+                solend_reserve_data(1_000),
+            ),
+            strategy(StrategyType::Unknown(13), 900, 0, vec![]),
+        ]);
+        let planner = WithdrawPlanner::new(&config, Pubkey::new_unique(), &vault_state, &state);
+
+        let error = planner
+            .plan(
+                200,
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            )
+            .unwrap_err();
+        assert_eq!(error.to_string(), "No strategy to withdraw from");
+    }
+
+    fn multi_leg_state() -> WithdrawPlannerState {
+        planner_state(vec![
+            strategy(
+                StrategyType::SolendWithoutLM,
+                900,
+                1,
+                solend_reserve_data(1_000),
+            ),
+            // capped at 200 by its reserve
+            strategy(
+                StrategyType::SolendWithoutLM,
+                300,
+                0,
+                solend_reserve_data(200),
+            ),
+            strategy(
+                StrategyType::SolendWithoutLM,
+                500,
+                0,
+                solend_reserve_data(1_000),
+            ),
+        ])
+    }
+
+    fn plan_legs(state: &WithdrawPlannerState, unmint_amount: u64) -> Result<MultiLegWithdrawPlan> {
+        let config = ClusterConfig::mainnet();
+        let vault_state = vault_state();
+        WithdrawPlanner::new(&config, Pubkey::new_unique(), &vault_state, state).plan_legs(
+            unmint_amount,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )
+    }
+
+    #[test]
+    fn plan_legs_splits_lp_across_strategies() {
+        let state = multi_leg_state();
+
+        let plan = plan_legs(&state, 800).unwrap();
+        let legs: Vec<_> = plan
+            .legs
+            .iter()
+            .map(|leg| (strategy_pubkey(leg), leg.unmint_amount, leg.out_amount))
+            .collect();
+        // first leg takes token_vault and the largest strategy, the next leg only its strategy
+        assert_eq!(
+            legs,
+            vec![
+                (Some(state.strategies[2].pubkey), 600, 600),
+                (Some(state.strategies[1].pubkey), 200, 200),
+            ]
+        );
+        assert_eq!((plan.unmint_amount, plan.out_amount), (800, 800));
+        assert_eq!(
+            plan.legs.iter().map(|leg| leg.unmint_amount).sum::<u64>(),
+            plan.unmint_amount
+        );
+
+        // remaining lp fits in the first leg
+        let plan = plan_legs(&state, 550).unwrap();
+        assert_eq!(plan.legs.len(), 1);
+        assert_eq!(plan.legs[0].unmint_amount, 550);
+    }
+
+    #[test]
+    fn plan_legs_uses_single_reserve_leg_when_token_vault_covers_amount() {
+        let plan = plan_legs(&multi_leg_state(), 50).unwrap();
+        assert_eq!(plan.legs.len(), 1);
+        assert_eq!(plan.legs[0].path, WithdrawPath::Reserve);
+        assert_eq!(
+            (plan.legs[0].unmint_amount, plan.legs[0].out_amount),
+            (50, 50)
+        );
+    }
+
+    #[test]
+    fn plan_legs_fails_when_strategies_lack_liquidity() {
+        let error = plan_legs(&multi_leg_state(), 801).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Strategies do not have enough liquidity, 800 of 801 lp can be withdrawn now"
+        );
+    }
+
+    #[test]
+    fn send_legs_stops_at_first_failed_leg() {
+        let plan = plan_legs(&multi_leg_state(), 800).unwrap();
+        let mut min_out_amounts = vec![];
+        let result = plan.send_legs_blocking(100, |leg, min_out_amount| {
+            min_out_amounts.push(min_out_amount);
+            if leg.unmint_amount == 600 {
+                Ok(Signature::default())
+            } else {
+                Err(anyhow!("leg failed"))
+            }
+        });
+        // 1% below the planned out amount of each sent leg
+        assert_eq!(min_out_amounts, vec![594, 198]);
+        assert!(!result.is_complete());
+        assert_eq!(result.completed_legs().len(), 1);
+        assert_eq!(result.pending_legs().len(), 1);
+        assert_eq!(result.withdrawn_lp_amount(), 600);
+        assert_eq!(result.error.unwrap().to_string(), "leg failed");
+    }
+}